@@ -0,0 +1,224 @@
+#![allow(unused)]
+use crate::{
+    Result,
+    Error,
+};
+use core::time::Duration;
+#[cfg(feature = "alloc")]
+use alloc::{
+    collections::VecDeque,
+    vec::Vec,
+};
+
+// A network interface that frames are sent through and received from,
+// e.g. a raw socket or the driver of an embedded NIC.
+pub trait Device {
+    // Send one frame.
+    fn transmit(&mut self, buf: &[u8]) -> Result<()>;
+
+    // Receive one frame, if there is one, with its metadata; the frame
+    // is valid until the next call.
+    fn receive(&mut self) -> Result<Option<(RxMeta, &[u8])>>;
+
+    // Which checksums must be handled in software.
+    fn checksum_capabilities(&self) -> ChecksumCapabilities {
+        ChecksumCapabilities::default()
+    }
+}
+
+// What a device knows about a received frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RxMeta {
+    // When the frame arrived, on a monotonic clock of the device; only
+    // differences between timestamps of one device are meaningful.
+    pub timestamp: Duration,
+    pub len: usize,
+}
+
+// In which directions a checksum is computed and verified in software;
+// the rest is offloaded to the NIC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Checksum {
+    #[default]
+    Both,
+    Rx,
+    Tx,
+    None,
+}
+
+impl Checksum {
+    // Verify received checksums in software.
+    pub fn rx(&self) -> bool {
+        matches!(*self, Checksum::Both | Checksum::Rx)
+    }
+
+    // Fill transmitted checksums in software.
+    pub fn tx(&self) -> bool {
+        matches!(*self, Checksum::Both | Checksum::Tx)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChecksumCapabilities {
+    pub ipv4: Checksum,
+    pub udp: Checksum,
+    pub tcp: Checksum,
+    pub icmp: Checksum,
+}
+
+impl ChecksumCapabilities {
+    // Everything is offloaded.
+    pub fn ignored() -> Self {
+        ChecksumCapabilities {
+            ipv4: Checksum::None,
+            udp: Checksum::None,
+            tcp: Checksum::None,
+            icmp: Checksum::None,
+        }
+    }
+}
+
+// Hands transmitted frames back as received ones, in order. Frames are
+// stamped with a counter that advances by a microsecond per receive.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Default)]
+pub struct Loopback {
+    queue: VecDeque<Vec<u8>>,
+    rx_buffer: Vec<u8>,
+    clock: u64,
+}
+
+#[cfg(feature = "alloc")]
+impl Loopback {
+    pub fn new() -> Loopback {
+        Loopback::default()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Device for Loopback {
+    fn transmit(&mut self, buf: &[u8]) -> Result<()> {
+        self.queue.push_back(buf.to_vec());
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<Option<(RxMeta, &[u8])>> {
+        match self.queue.pop_front() {
+            Some(frame) => {
+                self.clock += 1;
+                self.rx_buffer = frame;
+                let meta = RxMeta {
+                    timestamp: Duration::from_micros(self.clock),
+                    len: self.rx_buffer.len(),
+                };
+                Ok(Some((meta, &self.rx_buffer)))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+// A device backed by a rawsock interface, e.g. libpcap on eth0.
+#[cfg(all(feature = "std", feature = "rawsock"))]
+pub struct RawSocketDevice<'a> {
+    interface: Box<dyn rawsock::traits::DynamicInterface<'a> + 'a>,
+    rx_buffer: Vec<u8>,
+    // timestamps count from when the device was opened
+    opened_at: std::time::Instant,
+}
+
+#[cfg(all(feature = "std", feature = "rawsock"))]
+impl<'a> RawSocketDevice<'a> {
+    pub fn new(library: &'a dyn rawsock::traits::Library, name: &str) -> Result<RawSocketDevice<'a>> {
+        let interface = library.open_interface(name).map_err(|_| Error::Unaddressable)?;
+        Ok(RawSocketDevice {
+            interface,
+            rx_buffer: Vec::new(),
+            opened_at: std::time::Instant::now(),
+        })
+    }
+}
+
+#[cfg(all(feature = "std", feature = "rawsock"))]
+impl<'a> Device for RawSocketDevice<'a> {
+    fn transmit(&mut self, buf: &[u8]) -> Result<()> {
+        self.interface.send(buf).map_err(|_| Error::Exhausted)
+    }
+
+    fn receive(&mut self) -> Result<Option<(RxMeta, &[u8])>> {
+        // the received packet borrows the interface, so keep a copy
+        match self.interface.receive() {
+            Ok(packet) => {
+                self.rx_buffer.clear();
+                self.rx_buffer.extend_from_slice(&packet);
+            }
+            Err(_) => return Ok(None),
+        }
+        let meta = RxMeta {
+            timestamp: self.opened_at.elapsed(),
+            len: self.rx_buffer.len(),
+        };
+        Ok(Some((meta, &self.rx_buffer)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dev::MockDevice;
+    #[cfg(feature = "alloc")]
+    use super::Loopback;
+    use super::{
+        Device,
+        Checksum,
+        ChecksumCapabilities,
+    };
+
+    #[test]
+    fn test_mock_device() {
+        let mut device = MockDevice::default();
+        device.queue_rx(&[1, 2, 3]);
+
+        let dyn_device: &mut dyn Device = &mut device;
+        dyn_device.transmit(&[0xaa; 14]).unwrap();
+        let (meta, frame) = dyn_device.receive().unwrap().unwrap();
+        assert_eq!(frame, &[1, 2, 3][..]);
+        assert_eq!(meta.len, 3);
+        assert_eq!(dyn_device.receive(), Ok(None));
+
+        assert_eq!(device.sent, vec![vec![0xaa; 14]]);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_rx_timestamps() {
+        let mut device = Loopback::new();
+        assert_eq!(device.receive(), Ok(None));
+        device.transmit(&[1, 2, 3]).unwrap();
+        device.transmit(&[4; 60]).unwrap();
+
+        let (first, frame) = device.receive().unwrap().unwrap();
+        assert_eq!(frame, &[1, 2, 3][..]);
+        assert_eq!(first.len, 3);
+        let (second, frame) = device.receive().unwrap().unwrap();
+        assert_eq!(frame, &[4; 60][..]);
+        assert_eq!(second.len, 60);
+        assert!(second.timestamp > first.timestamp);
+        assert_eq!(device.receive(), Ok(None));
+
+        device.transmit(&[5]).unwrap();
+        let (third, _) = device.receive().unwrap().unwrap();
+        assert!(third.timestamp > second.timestamp);
+    }
+
+    #[test]
+    fn test_checksum_capabilities() {
+        assert!(Checksum::Both.rx() && Checksum::Both.tx());
+        assert!(Checksum::Rx.rx() && !Checksum::Rx.tx());
+        assert!(!Checksum::Tx.rx() && Checksum::Tx.tx());
+        assert!(!Checksum::None.rx() && !Checksum::None.tx());
+
+        let device = MockDevice::default();
+        assert_eq!(device.checksum_capabilities().ipv4, Checksum::Both);
+        assert_eq!(ChecksumCapabilities::ignored().tcp, Checksum::None);
+    }
+}
@@ -1,9 +1,103 @@
-mod protocol;
-mod socket;
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub mod protocol;
+pub mod socket;
+pub mod device;
+#[cfg(feature = "alloc")]
+pub use socket::build_echo_response;
+#[cfg(feature = "std")]
+pub mod pcap;
 
 pub type Field = core::ops::Range<usize>;
 pub type FieldFrom = core::ops::RangeFrom<usize>;
 
+// Bounds-checked reads of header fields. The `*_checked` accessors use
+// these so that a short buffer wrapped with `new_unchecked` gives
+// Error::Truncated instead of a panic. Every plain getter reads through
+// these as well, via its `*_checked` twin where there is one, and turns
+// the error into a panic with `checked` below.
+pub(crate) mod bytes {
+    use core::slice::SliceIndex;
+    use byteorder::{
+        ByteOrder,
+        NetworkEndian,
+    };
+    use crate::{
+        Result,
+        Error,
+        Field,
+    };
+
+    pub fn get_u8(data: &[u8], index: usize) -> Result<u8> {
+        data.get(index).copied().ok_or(Error::Truncated)
+    }
+
+    pub fn get_u16(data: &[u8], field: Field) -> Result<u16> {
+        get_slice(data, field).map(NetworkEndian::read_u16)
+    }
+
+    pub fn get_u32(data: &[u8], field: Field) -> Result<u32> {
+        get_slice(data, field).map(NetworkEndian::read_u32)
+    }
+
+    pub fn get_slice<I>(data: &[u8], field: I) -> Result<&[u8]>
+    where
+        I: SliceIndex<[u8], Output = [u8]>,
+    {
+        data.get(field).ok_or(Error::Truncated)
+    }
+
+    // The plain accessors expect a buffer that passed check_len, so a
+    // field outside of it is a bug in the caller, as with indexing.
+    #[track_caller]
+    pub fn checked<V>(value: Result<V>) -> V {
+        match value {
+            Ok(value) => value,
+            Err(_) => panic!("field outside the buffer; check_len first"),
+        }
+    }
+}
+
+// A classic offset/hex/ASCII listing of a buffer, 16 bytes per line,
+// for looking at packets while debugging.
+pub(crate) mod hexdump {
+    use core::fmt;
+
+    const LINE_LEN: usize = 16;
+
+    pub struct HexDump<'a>(pub &'a [u8]);
+
+    impl<'a> fmt::Display for HexDump<'a> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            for (index, line) in self.0.chunks(LINE_LEN).enumerate() {
+                if index > 0 {
+                    writeln!(f)?;
+                }
+                write!(f, "{:08x} ", index * LINE_LEN)?;
+                for column in 0..LINE_LEN {
+                    if column % 8 == 0 {
+                        write!(f, " ")?;
+                    }
+                    match line.get(column) {
+                        Some(byte) => write!(f, "{:02x} ", byte)?,
+                        None => write!(f, "   ")?,
+                    }
+                }
+                write!(f, " |")?;
+                for &byte in line {
+                    let c = if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' };
+                    write!(f, "{}", c)?;
+                }
+                write!(f, "|")?;
+            }
+            Ok(())
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum Error {
@@ -38,6 +132,26 @@ pub enum Error {
     Dropped,
 }
 
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match *self {
+            Error::Exhausted     => write!(f, "buffer space exhausted"),
+            Error::Illegal       => write!(f, "illegal operation"),
+            Error::Unaddressable => write!(f, "unaddressable destination"),
+            Error::Finished      => write!(f, "operation finished"),
+            Error::Truncated     => write!(f, "truncated packet"),
+            Error::Checksum      => write!(f, "checksum error"),
+            Error::Unrecognized  => write!(f, "unrecognized packet"),
+            Error::Fragmented    => write!(f, "fragmented packet"),
+            Error::Malformed     => write!(f, "malformed packet"),
+            Error::Dropped       => write!(f, "dropped by socket"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
 /// The result type for the networking stack.
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -46,6 +160,8 @@ pub mod checksum {
         ByteOrder, 
         NetworkEndian
     };
+    use crate::protocol::ip::Protocol;
+    use crate::protocol::ip::ipv4::Address as IPv4Address;
 
     fn propagate_carries(word: u32) -> u16 {
         let sum = (word >> 16) + (word & 0xffff);
@@ -84,6 +200,23 @@ pub mod checksum {
         propagate_carries(accum)
     }
 
+    /// Compute the same checksum as `data` over the concatenation of
+    /// `chunks`, e.g. a header and a payload kept in separate buffers.
+    pub fn data_chunks(chunks: &[&[u8]]) -> u16 {
+        let mut accum: u32 = 0;
+        let mut odd = false;
+        for chunk in chunks {
+            let sum = data(chunk);
+            // A chunk starting at an odd offset has its bytes summed in the
+            // wrong lanes; the one's complement sum of the byte-swapped
+            // words is the byte-swapped sum (RFC 1071, section 2).
+            let sum = if odd { sum.swap_bytes() } else { sum };
+            accum = propagate_carries(accum + sum as u32) as u32;
+            odd ^= chunk.len() % 2 == 1;
+        }
+        accum as u16
+    }
+
     /// Combine several RFC 1071 compliant checksums.
     pub fn combine(checksums: &[u16]) -> u16 {
         let mut accum: u32 = 0;
@@ -92,12 +225,199 @@ pub mod checksum {
         }
         propagate_carries(accum)
     }
+
+    /// Update a checksum after a 16-bit word of the checksummed data changed,
+    /// as described in RFC 1624 (`HC' = ~(~HC + ~m + m')`).
+    pub fn update(old_checksum: u16, old_word: u16, new_word: u16) -> u16 {
+        !combine(&[!old_checksum, !old_word, new_word])
+    }
+
+    /// Compute an IPv4 pseudo-header checksum.
+    pub fn pseudo_header(
+        src_addr: &IPv4Address, 
+        dst_addr: &IPv4Address,
+        protocol: Protocol, 
+        length: u32
+    ) -> u16 {
+        let mut proto_len = [0u8; 4];
+        proto_len[1] = protocol.into();
+        NetworkEndian::write_u16(&mut proto_len[2..4], length as u16);
+
+        combine(&[
+            data(src_addr.as_bytes()),
+            data(dst_addr.as_bytes()),
+            data(&proto_len[..])
+        ])
+    }
+
+    /// Compute an IPv6 pseudo-header checksum (RFC 8200, section 8.1),
+    /// shared by UDP, TCP and ICMPv6. `length` is the upper-layer packet
+    /// length and `next_header` the upper-layer protocol number.
+    pub fn pseudo_header_v6(
+        src_addr: &[u8; 16],
+        dst_addr: &[u8; 16],
+        next_header: u8,
+        length: u32
+    ) -> u16 {
+        let mut len_nxt = [0u8; 8];
+        NetworkEndian::write_u32(&mut len_nxt[..4], length);
+        len_nxt[7] = next_header;
+
+        combine(&[
+            data(&src_addr[..]),
+            data(&dst_addr[..]),
+            data(&len_nxt[..])
+        ])
+    }
+
+    /// Verify the checksum of a UDP or TCP segment carried over IPv4, with
+    /// the transport header and the payload passed separately.
+    ///
+    /// For UDP a received checksum of zero means that the sender did not
+    /// compute one, so the segment is accepted; a computed zero is sent as
+    /// 0xFFFF instead (RFC 768), which verifies like any other value.
+    pub fn verify_transport_v4(
+        header: &[u8],
+        payload: &[u8],
+        src_addr: &IPv4Address,
+        dst_addr: &IPv4Address,
+        protocol: Protocol
+    ) -> bool {
+        const UDP_CHECKSUM: core::ops::Range<usize> = 6..8;
+
+        if protocol == Protocol::UDP {
+            match header.get(UDP_CHECKSUM) {
+                Some(checksum) if NetworkEndian::read_u16(checksum) == 0 => return true,
+                _ => (),
+            }
+        }
+        let length = (header.len() + payload.len()) as u32;
+        combine(&[
+            pseudo_header(src_addr, dst_addr, protocol, length),
+            data_chunks(&[header, payload]),
+        ]) == !0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Error;
+
+    #[test]
+    fn test_error_display() {
+        let errors = [
+            (Error::Exhausted,     "buffer space exhausted"),
+            (Error::Illegal,       "illegal operation"),
+            (Error::Unaddressable, "unaddressable destination"),
+            (Error::Finished,      "operation finished"),
+            (Error::Truncated,     "truncated packet"),
+            (Error::Checksum,      "checksum error"),
+            (Error::Unrecognized,  "unrecognized packet"),
+            (Error::Fragmented,    "fragmented packet"),
+            (Error::Malformed,     "malformed packet"),
+            (Error::Dropped,       "dropped by socket"),
+        ];
+        for (error, message) in errors.iter() {
+            assert!(!error.to_string().is_empty());
+            assert_eq!(error.to_string(), *message);
+        }
+    }
+
+    #[test]
+    fn test_checksum_data() {
+        use super::checksum;
+
+        assert_eq!(checksum::data(&[]), 0);
+        assert_eq!(checksum::data(&[0; 64]), 0);
+        assert_eq!(checksum::data(&[0xff; 64]), 0xffff);
+        assert_eq!(checksum::data(&[0x12]), 0x1200);
+    }
+
+    #[test]
+    fn test_checksum_data_chunks() {
+        use super::checksum;
+
+        let data: Vec<u8> = (0..41).map(|i| (i * 37 + 11) as u8).collect();
+        for split in 0..=data.len() {
+            let (a, b) = data.split_at(split);
+            assert_eq!(checksum::data_chunks(&[a, b]), checksum::data(&data));
+        }
+        assert_eq!(
+            checksum::data_chunks(&[&data[..3], &data[3..4], &data[4..9], &data[9..]]),
+            checksum::data(&data)
+        );
+        assert_eq!(checksum::data_chunks(&[]), 0);
+
+        fn prop(data: Vec<u8>, split: usize) -> bool {
+            let split = if data.is_empty() { 0 } else { split % data.len() };
+            let (a, b) = data.split_at(split);
+            checksum::data_chunks(&[a, b]) == checksum::data(&data)
+        }
+        quickcheck::quickcheck(prop as fn(Vec<u8>, usize) -> bool);
+    }
+
+    #[test]
+    fn test_checksum_pseudo_header_v6() {
+        use super::checksum;
+
+        let src_addr = [
+            0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01
+        ];
+        let dst_addr = [
+            0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xfb
+        ];
+        // an mDNS query header sent from port 5353 to 5353
+        let udp = [
+            0x14, 0xe9, 0x14, 0xe9, 0x00, 0x14, 0xd7, 0x73,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00,
+        ];
+        let pseudo = checksum::pseudo_header_v6(&src_addr, &dst_addr, 17, udp.len() as u32);
+        assert_eq!(checksum::combine(&[pseudo, checksum::data(&udp)]), !0);
+
+        let mut zeroed = udp;
+        zeroed[6..8].copy_from_slice(&[0, 0]);
+        assert_eq!(!checksum::combine(&[pseudo, checksum::data(&zeroed)]), 0xd773);
+    }
+
+    #[test]
+    fn test_hexdump() {
+        use super::hexdump::HexDump;
+
+        let bytes: Vec<u8> = (0x40..0x54).collect();
+        assert_eq!(
+            HexDump(&bytes).to_string(),
+            "00000000  40 41 42 43 44 45 46 47  48 49 4a 4b 4c 4d 4e 4f  |@ABCDEFGHIJKLMNO|\n\
+             00000010  50 51 52 53                                       |PQRS|"
+        );
+        assert_eq!(HexDump(&[0x00, 0x20, 0x7f]).to_string(),
+            "00000000  00 20 7f                                          |. .|");
+        assert_eq!(HexDump(&[]).to_string(), "");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_error_boxed() {
+        let error: Box<dyn std::error::Error> = Box::new(Error::Truncated);
+        assert_eq!(error.to_string(), "truncated packet");
+    }
 }
 
 #[cfg(test)]
 pub mod dev {
     use rawsock::open_best_library;
     use mac_address;
+    use std::collections::VecDeque;
+    use crate::device::{
+        Device,
+        RxMeta,
+    };
+    use crate::protocol::ethernet::{
+        Address,
+        EtherType,
+        Frame,
+    };
+    use std::time::Duration;
 
     pub fn src_mac() -> [u8; 6] {
         mac_address::mac_address_by_name("eth0").unwrap().unwrap().bytes()
@@ -116,4 +436,79 @@ pub mod dev {
             Err(_) => {}
         }
     }
+
+    // An unfragmented UDP datagram from 10.0.0.2 to 10.0.0.1 with a
+    // 20-byte header and a payload counting up from 0.
+    pub fn ipv4_datagram(ident: u16, payload_len: usize) -> Vec<u8> {
+        use crate::protocol::ip::Protocol;
+        use crate::protocol::ip::ipv4::{
+            Address,
+            Packet,
+        };
+
+        let mut packet = Packet::new_unchecked(vec![0; 20 + payload_len]);
+        packet.set_version(4);
+        packet.set_header_len(20);
+        packet.set_total_len((20 + payload_len) as u16);
+        packet.set_ident(ident);
+        packet.set_hop_limit(64);
+        packet.set_protocol(Protocol::UDP);
+        packet.set_src_addr(Address([10, 0, 0, 2]));
+        packet.set_dst_addr(Address([10, 0, 0, 1]));
+        for (i, byte) in packet.payload_mut().iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        packet.fill_checksum();
+        packet.into_inner()
+    }
+
+    // A frame from 02:00:00:00:00:01 carrying `payload`, which is not
+    // padded to the minimum frame length.
+    pub fn ethernet_frame(dst_addr: Address, ether_type: EtherType, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0; 14 + payload.len()];
+        let mut frame = Frame::new_unchecked(&mut bytes[..]);
+        frame.set_dst_addr(dst_addr);
+        frame.set_src_addr(Address([0x02, 0, 0, 0, 0, 0x01]));
+        frame.set_ether_type(ether_type);
+        frame.payload_mut().copy_from_slice(payload);
+        bytes
+    }
+
+    // Records transmitted frames and hands out queued ones, stamped with
+    // a counter that advances by a microsecond per receive.
+    #[derive(Default)]
+    pub struct MockDevice {
+        pub sent: Vec<Vec<u8>>,
+        rx_queue: VecDeque<Vec<u8>>,
+        rx_buffer: Vec<u8>,
+        rx_count: u64,
+    }
+
+    impl MockDevice {
+        pub fn queue_rx(&mut self, frame: &[u8]) {
+            self.rx_queue.push_back(frame.to_vec());
+        }
+    }
+
+    impl Device for MockDevice {
+        fn transmit(&mut self, buf: &[u8]) -> crate::Result<()> {
+            self.sent.push(buf.to_vec());
+            Ok(())
+        }
+
+        fn receive(&mut self) -> crate::Result<Option<(RxMeta, &[u8])>> {
+            match self.rx_queue.pop_front() {
+                Some(frame) => {
+                    self.rx_count += 1;
+                    self.rx_buffer = frame;
+                    let meta = RxMeta {
+                        timestamp: Duration::from_micros(self.rx_count),
+                        len: self.rx_buffer.len(),
+                    };
+                    Ok(Some((meta, &self.rx_buffer)))
+                }
+                None => Ok(None),
+            }
+        }
+    }
 }
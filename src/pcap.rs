@@ -0,0 +1,80 @@
+// Writing frames to capture files for Wireshark and tcpdump.
+
+use std::io::{self, Write};
+use std::time::Duration;
+use byteorder::{
+    ByteOrder,
+    LittleEndian
+};
+
+pub const PCAP_MAGIC: u32 = 0xa1b2c3d4;
+pub const PCAP_LINKTYPE_ETHERNET: u32 = 1;
+pub const PCAP_SNAPLEN: u32 = 65535;
+
+// Writes frames in the classic (libpcap) capture format, readable by
+// Wireshark and tcpdump. All fields are little-endian.
+pub struct PcapWriter<W: Write> {
+    sink: W,
+}
+
+impl<W: Write> PcapWriter<W> {
+    // Wrap `sink`, writing the global header right away.
+    pub fn new(mut sink: W) -> io::Result<PcapWriter<W>> {
+        let mut header = [0u8; 24];
+        LittleEndian::write_u32(&mut header[0..4], PCAP_MAGIC);
+        LittleEndian::write_u16(&mut header[4..6], 2);
+        LittleEndian::write_u16(&mut header[6..8], 4);
+        // thiszone and sigfigs are zero
+        LittleEndian::write_u32(&mut header[16..20], PCAP_SNAPLEN);
+        LittleEndian::write_u32(&mut header[20..24], PCAP_LINKTYPE_ETHERNET);
+        sink.write_all(&header)?;
+        Ok(PcapWriter { sink })
+    }
+
+    // Write one Ethernet frame captured at `timestamp` (since the epoch).
+    // Frames longer than the snapshot length are truncated.
+    pub fn write_frame(&mut self, bytes: &[u8], timestamp: Duration) -> io::Result<()> {
+        let captured = &bytes[..bytes.len().min(PCAP_SNAPLEN as usize)];
+        let mut header = [0u8; 16];
+        LittleEndian::write_u32(&mut header[0..4], timestamp.as_secs() as u32);
+        LittleEndian::write_u32(&mut header[4..8], timestamp.subsec_micros());
+        LittleEndian::write_u32(&mut header[8..12], captured.len() as u32);
+        LittleEndian::write_u32(&mut header[12..16], bytes.len() as u32);
+        self.sink.write_all(&header)?;
+        self.sink.write_all(captured)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.sink
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pcap_writer() {
+        let mut writer = PcapWriter::new(Vec::new()).unwrap();
+        writer.write_frame(&[0xaa; 60], Duration::new(1, 500_000_000)).unwrap();
+        writer.write_frame(&[0xbb; 14], Duration::from_micros(2_000_001)).unwrap();
+        let file = writer.into_inner();
+
+        assert_eq!(file.len(), 24 + 16 + 60 + 16 + 14);
+        assert_eq!(&file[0..4], &[0xd4, 0xc3, 0xb2, 0xa1]);
+        assert_eq!(LittleEndian::read_u32(&file[20..24]), PCAP_LINKTYPE_ETHERNET);
+
+        let first = &file[24..40];
+        assert_eq!(LittleEndian::read_u32(&first[0..4]), 1);
+        assert_eq!(LittleEndian::read_u32(&first[4..8]), 500_000);
+        assert_eq!(LittleEndian::read_u32(&first[8..12]), 60);
+        assert_eq!(LittleEndian::read_u32(&first[12..16]), 60);
+        assert_eq!(file[40], 0xaa);
+
+        let second = &file[100..116];
+        assert_eq!(LittleEndian::read_u32(&second[0..4]), 2);
+        assert_eq!(LittleEndian::read_u32(&second[4..8]), 1);
+        assert_eq!(LittleEndian::read_u32(&second[8..12]), 14);
+        assert_eq!(&file[116..], &[0xbb; 14]);
+    }
+}
@@ -1,3 +1,197 @@
+#![allow(unused)]
+
 pub mod ethernet;
+pub mod arp;
 pub mod ip;
 pub mod icmp;
+pub mod udp;
+pub mod tcp;
+pub mod igmp;
+pub mod gre;
+pub mod dhcpv4;
+pub mod port;
+pub mod dns;
+pub mod stp;
+
+use crate::{
+    Result,
+    Error,
+};
+use ethernet::{
+    EtherType,
+    Frame,
+};
+use ip::Protocol;
+use ip::ipv4::Packet as IPv4Packet;
+use ip::ipv6::Packet as IPv6Packet;
+use arp::Packet as ArpPacket;
+use icmp::icmpv4::Packet as ICMPv4Packet;
+use icmp::icmpv6::Packet as ICMPv6Packet;
+use udp::Packet as UdpPacket;
+use tcp::Packet as TcpPacket;
+
+// The network layer of a received frame.
+pub enum Layer<'a> {
+    Ipv4(IPv4Packet<&'a [u8]>),
+    Ipv6(IPv6Packet<&'a [u8]>),
+    Arp(ArpPacket<&'a [u8]>),
+    Unknown(EtherType),
+}
+
+// The transport layer of a received IP packet.
+pub enum Transport<'a> {
+    Icmpv4(ICMPv4Packet<&'a [u8]>),
+    Icmpv6(ICMPv6Packet<&'a [u8]>),
+    Udp(UdpPacket<&'a [u8]>),
+    Tcp(TcpPacket<&'a [u8]>),
+    Unknown(Protocol),
+}
+
+// Classify a received frame by its EtherType. Truncated packets are
+// reported as Error::Truncated rather than returned. The layer borrows
+// the frame's buffer, not the frame.
+pub fn parse_ethernet(frame: Frame<&[u8]>) -> Result<Layer<'_>> {
+    frame.check_len()?;
+    let ether_type = frame.ether_type();
+    let payload = &frame.into_inner()[ethernet::HEADER_LEN..];
+    match ether_type {
+        EtherType::IPv4 => Ok(Layer::Ipv4(IPv4Packet::from_padded(payload)?)),
+        EtherType::IPv6 => Ok(Layer::Ipv6(IPv6Packet::new_checked(payload)?)),
+        EtherType::ARP  => Ok(Layer::Arp(ArpPacket::new_checked(payload)?)),
+        ether_type => Ok(Layer::Unknown(ether_type)),
+    }
+}
+
+impl<'a> Layer<'a> {
+    // Peel the transport layer off an IP packet. IPv4 fragments are
+    // rejected with Error::Fragmented; they need reassembly first.
+    pub fn parse_transport(self) -> Result<Transport<'a>> {
+        let (protocol, payload) = match self {
+            Layer::Ipv4(packet) => {
+                if packet.more_frags() || packet.frag_offset() != 0 {
+                    return Err(Error::Fragmented);
+                }
+                let header_len = packet.header_len() as usize;
+                let total_len = packet.total_len() as usize;
+                (packet.protocol(), &packet.into_inner()[header_len..total_len])
+            }
+            Layer::Ipv6(packet) => {
+                let payload_len = packet.payload_len() as usize;
+                let header_len = IPv6Packet::<&[u8]>::header_len();
+                (packet.next_header(), &packet.into_inner()[header_len..header_len + payload_len])
+            }
+            _ => return Err(Error::Unrecognized),
+        };
+        match protocol {
+            Protocol::ICMP   => Ok(Transport::Icmpv4(ICMPv4Packet::new_checked(payload)?)),
+            Protocol::ICMPv6 => Ok(Transport::Icmpv6(ICMPv6Packet::new_checked(payload)?)),
+            Protocol::UDP    => Ok(Transport::Udp(UdpPacket::new_checked(payload)?)),
+            Protocol::TCP    => Ok(Transport::Tcp(TcpPacket::new_checked(payload)?)),
+            protocol => Ok(Transport::Unknown(protocol)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Error;
+    use super::ethernet::{
+        EtherType,
+        Frame,
+    };
+    use super::ip::ipv4::Address as IPv4Address;
+    use super::arp::Operation;
+    use super::{
+        parse_ethernet,
+        Layer,
+        Transport,
+    };
+
+    // echo request 10.0.0.2 -> 10.0.0.1
+    static ICMP_FRAME: [u8; 50] = [
+        0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x02, 0x00,
+        0x00, 0x00, 0x00, 0x02, 0x08, 0x00, 0x45, 0x00,
+        0x00, 0x24, 0xbe, 0xef, 0x40, 0x00, 0x40, 0x01,
+        0x67, 0xe7, 0x0a, 0x00, 0x00, 0x02, 0x0a, 0x00,
+        0x00, 0x01, 0x08, 0x00, 0xd4, 0xb5, 0x12, 0x34,
+        0x00, 0x01, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46,
+        0x47, 0x48,
+    ];
+
+    // who-has 10.10.10.1 tell 10.10.10.2
+    static ARP_FRAME: [u8; 42] = [
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02, 0xfc,
+        0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x01,
+        0x08, 0x00, 0x06, 0x04, 0x00, 0x01, 0x02, 0xfc,
+        0x00, 0x00, 0x00, 0x01, 0x0a, 0x0a, 0x0a, 0x02,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x0a,
+        0x0a, 0x01,
+    ];
+
+    #[test]
+    fn test_ipv4_icmp() {
+        let frame = Frame::new_checked(&ICMP_FRAME[..]).unwrap();
+        let layer = parse_ethernet(frame).unwrap();
+        match layer {
+            Layer::Ipv4(ref packet) => {
+                assert!(packet.verify_checksum());
+                assert_eq!(packet.src_addr(), IPv4Address([10, 0, 0, 2]));
+            }
+            _ => panic!("expected an IPv4 packet"),
+        }
+        match layer.parse_transport().unwrap() {
+            Transport::Icmpv4(packet) => {
+                assert!(packet.verify_checksum());
+                assert_eq!(packet.echo_ident(), 0x1234);
+                assert_eq!(packet.data(), b"ABCDEFGH");
+            }
+            _ => panic!("expected an ICMPv4 packet"),
+        }
+    }
+
+    #[test]
+    fn test_ipv4_fragment() {
+        // first fragment, then one further into the datagram
+        for &(flags, offset) in [(0x20, 0x00), (0x00, 0x01)].iter() {
+            let mut bytes = ICMP_FRAME;
+            bytes[20] = flags;
+            bytes[21] = offset;
+            let layer = parse_ethernet(Frame::new_checked(&bytes[..]).unwrap()).unwrap();
+            assert_eq!(layer.parse_transport().err(), Some(Error::Fragmented));
+        }
+    }
+
+    #[test]
+    fn test_arp() {
+        let frame = Frame::new_checked(&ARP_FRAME[..]).unwrap();
+        let layer = parse_ethernet(frame).unwrap();
+        match layer {
+            Layer::Arp(ref packet) => {
+                assert_eq!(packet.operation(), Operation::Request);
+                assert_eq!(packet.target_protocol_addr(), IPv4Address([10, 10, 10, 1]));
+            }
+            _ => panic!("expected an ARP packet"),
+        }
+        assert_eq!(layer.parse_transport().err(), Some(Error::Unrecognized));
+    }
+
+    #[test]
+    fn test_truncated() {
+        let frame = Frame::new_checked(&ICMP_FRAME[..40]).unwrap();
+        assert_eq!(parse_ethernet(frame).err(), Some(Error::Truncated));
+
+        let frame = Frame::new_checked(&ARP_FRAME[..41]).unwrap();
+        assert_eq!(parse_ethernet(frame).err(), Some(Error::Truncated));
+    }
+
+    #[test]
+    fn test_unknown() {
+        let mut bytes = ARP_FRAME;
+        bytes[12..14].copy_from_slice(&[0x88, 0xcc]);
+        let frame = Frame::new_checked(&bytes[..]).unwrap();
+        match parse_ethernet(frame).unwrap() {
+            Layer::Unknown(ether_type) => assert_eq!(ether_type, EtherType::Lldp),
+            _ => panic!("expected an unknown layer"),
+        }
+    }
+}
@@ -0,0 +1,540 @@
+// 0                   1                   2                   3
+// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |         Hardware Type         |         Protocol Type         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  Hardware Len |  Protocol Len |           Operation           |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                  Sender Hardware Address                      |
+// +                               +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                               |   Sender Protocol Address     |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  Sender Protocol Address      |                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               +
+// |                  Target Hardware Address                      |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                  Target Protocol Address                      |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+#![allow(unused)]
+use byteorder::{
+    ByteOrder,
+    NetworkEndian,
+};
+use crate::{
+    Result,
+    Error,
+};
+use crate::bytes;
+use super::ethernet::{
+    Address as MacAddress,
+    EtherType,
+};
+use super::ip::ipv4::Address as IPv4Address;
+use core::time::Duration;
+#[cfg(feature = "alloc")]
+use alloc::{
+    vec,
+    vec::Vec,
+};
+
+#[repr(u16)]
+pub enum Hardware {
+    Ethernet = 1,
+    Unsupported = 0xFFFF,
+}
+
+impl From<u16> for Hardware {
+    fn from(val: u16) -> Self {
+        match val {
+            1 => Self::Ethernet,
+            _ => Self::Unsupported,
+        }
+    }
+}
+
+impl From<Hardware> for u16 {
+    fn from(hardware: Hardware) -> Self {
+        match hardware {
+            Hardware::Ethernet => 1,
+            Hardware::Unsupported => 0xFFFF,
+        }
+    }
+}
+
+#[repr(u16)]
+#[derive(Debug, PartialEq)]
+pub enum Operation {
+    Request = 1,
+    Reply   = 2,
+    Unsupported = 0xFFFF,
+}
+
+impl From<u16> for Operation {
+    fn from(val: u16) -> Self {
+        match val {
+            1 => Self::Request,
+            2 => Self::Reply,
+            _ => Self::Unsupported,
+        }
+    }
+}
+
+impl From<Operation> for u16 {
+    fn from(operation: Operation) -> Self {
+        match operation {
+            Operation::Request => 1,
+            Operation::Reply => 2,
+            Operation::Unsupported => 0xFFFF,
+        }
+    }
+}
+
+// Only Ethernet hardware addresses with IPv4 protocol addresses
+// are supported, so the address fields have a fixed layout.
+mod field {
+    use crate::Field;
+
+    pub const HTYPE: Field = 0..2;
+    pub const PTYPE: Field = 2..4;
+    pub const HLEN:  usize = 4;
+    pub const PLEN:  usize = 5;
+    pub const OPER:  Field = 6..8;
+    pub const SHA:   Field = 8..14;
+    pub const SPA:   Field = 14..18;
+    pub const THA:   Field = 18..24;
+    pub const TPA:   Field = 24..28;
+}
+
+pub const PACKET_LEN: usize = field::TPA.end;
+
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T
+}
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    pub fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < PACKET_LEN {
+            Err(Error::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    pub fn hardware_type(&self) -> Hardware {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::HTYPE)).into()
+    }
+
+    pub fn protocol_type(&self) -> EtherType {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::PTYPE)).into()
+    }
+
+    pub fn hardware_len(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u8(data, field::HLEN))
+    }
+
+    pub fn protocol_len(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u8(data, field::PLEN))
+    }
+
+    pub fn operation(&self) -> Operation {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::OPER)).into()
+    }
+
+    pub fn source_hardware_addr(&self) -> MacAddress {
+        let data = self.buffer.as_ref();
+        MacAddress::from_bytes(bytes::checked(bytes::get_slice(data, field::SHA)))
+    }
+
+    pub fn source_protocol_addr(&self) -> IPv4Address {
+        let data = self.buffer.as_ref();
+        IPv4Address::from_bytes(bytes::checked(bytes::get_slice(data, field::SPA)))
+    }
+
+    pub fn target_hardware_addr(&self) -> MacAddress {
+        let data = self.buffer.as_ref();
+        MacAddress::from_bytes(bytes::checked(bytes::get_slice(data, field::THA)))
+    }
+
+    pub fn target_protocol_addr(&self) -> IPv4Address {
+        let data = self.buffer.as_ref();
+        IPv4Address::from_bytes(bytes::checked(bytes::get_slice(data, field::TPA)))
+    }
+
+    // A host announcing (or defending) its own address asks for itself:
+    // the sender and target protocol addresses are the same (RFC 5227).
+    pub fn is_gratuitous(&self) -> bool {
+        self.source_protocol_addr() == self.target_protocol_addr()
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    pub fn set_hardware_type(&mut self, hardware: Hardware) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::HTYPE], hardware.into());
+    }
+
+    pub fn set_protocol_type(&mut self, protocol: EtherType) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::PTYPE], protocol.into());
+    }
+
+    pub fn set_hardware_len(&mut self, len: u8) {
+        let data = self.buffer.as_mut();
+        data[field::HLEN] = len;
+    }
+
+    pub fn set_protocol_len(&mut self, len: u8) {
+        let data = self.buffer.as_mut();
+        data[field::PLEN] = len;
+    }
+
+    pub fn set_operation(&mut self, operation: Operation) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::OPER], operation.into());
+    }
+
+    pub fn set_source_hardware_addr(&mut self, addr: MacAddress) {
+        let data = self.buffer.as_mut();
+        data[field::SHA].copy_from_slice(addr.as_bytes());
+    }
+
+    pub fn set_source_protocol_addr(&mut self, addr: IPv4Address) {
+        let data = self.buffer.as_mut();
+        data[field::SPA].copy_from_slice(addr.as_bytes());
+    }
+
+    pub fn set_target_hardware_addr(&mut self, addr: MacAddress) {
+        let data = self.buffer.as_mut();
+        data[field::THA].copy_from_slice(addr.as_bytes());
+    }
+
+    pub fn set_target_protocol_addr(&mut self, addr: IPv4Address) {
+        let data = self.buffer.as_mut();
+        data[field::TPA].copy_from_slice(addr.as_bytes());
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+// Build an ARP announcement of `ip` at `mac`: a request with the target
+// protocol address set to the sender's and no target hardware address.
+#[cfg(feature = "alloc")]
+pub fn build_gratuitous(ip: IPv4Address, mac: MacAddress) -> Vec<u8> {
+    let mut bytes = vec![0; PACKET_LEN];
+    let mut packet = Packet::new_unchecked(&mut bytes[..]);
+    packet.set_hardware_type(Hardware::Ethernet);
+    packet.set_protocol_type(EtherType::IPv4);
+    packet.set_hardware_len(6);
+    packet.set_protocol_len(4);
+    packet.set_operation(Operation::Request);
+    packet.set_source_hardware_addr(mac);
+    packet.set_source_protocol_addr(ip);
+    packet.set_target_hardware_addr(MacAddress([0; 6]));
+    packet.set_target_protocol_addr(ip);
+    bytes
+}
+
+// Number of neighbors the cache holds.
+pub const CACHE_SIZE: usize = 8;
+// How long a learned mapping stays valid by default.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy)]
+struct CacheEntry {
+    protocol_addr: IPv4Address,
+    hardware_addr: MacAddress,
+    expires_at: Duration,
+}
+
+// IPv4 to Ethernet address mappings learned from ARP. Time is whatever
+// monotonic clock the caller advances with set_time; entries expire
+// `ttl` after they were last refreshed. When the cache is full the entry
+// closest to expiry makes room for a new one.
+pub struct Cache {
+    entries: [Option<CacheEntry>; CACHE_SIZE],
+    ttl: Duration,
+    now: Duration,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self::new(DEFAULT_TTL)
+    }
+}
+
+impl Cache {
+    pub fn new(ttl: Duration) -> Cache {
+        Cache {
+            entries: [None; CACHE_SIZE],
+            ttl,
+            now: Duration::from_secs(0),
+        }
+    }
+
+    pub fn set_time(&mut self, now: Duration) {
+        self.now = now;
+    }
+
+    pub fn insert(&mut self, protocol_addr: IPv4Address, hardware_addr: MacAddress) {
+        let entry = CacheEntry {
+            protocol_addr,
+            hardware_addr,
+            // a ttl of Duration::MAX never expires
+            expires_at: self.now.saturating_add(self.ttl),
+        };
+        let now = self.now;
+        let slot = self.entries.iter()
+            .position(|slot| matches!(slot, Some(e) if e.protocol_addr == protocol_addr))
+            .or_else(|| self.entries.iter().position(|slot| match slot {
+                Some(e) => e.expires_at <= now,
+                None => true,
+            }))
+            .or_else(|| self.entries.iter()
+                .enumerate()
+                .min_by_key(|(_, slot)| slot.map(|e| e.expires_at))
+                .map(|(i, _)| i))
+            .unwrap_or(0);
+        self.entries[slot] = Some(entry);
+    }
+
+    pub fn lookup(&self, protocol_addr: IPv4Address) -> Option<MacAddress> {
+        self.entries.iter()
+            .flatten()
+            .find(|e| e.protocol_addr == protocol_addr && e.expires_at > self.now)
+            .map(|e| e.hardware_addr)
+    }
+
+    // Learn the sender's mapping from a received packet. Replies and
+    // gratuitous packets add it; other requests only refresh a mapping
+    // that is already cached (RFC 826), so that every host asking around
+    // doesn't fill the cache.
+    pub fn fill_from_packet(&mut self, arp: &Packet<&[u8]>) {
+        if arp.check_len().is_err() ||
+           !matches!(arp.hardware_type(), Hardware::Ethernet) ||
+           arp.protocol_type() != EtherType::IPv4 ||
+           arp.hardware_len() != 6 || arp.protocol_len() != 4 {
+            return;
+        }
+        let protocol_addr = arp.source_protocol_addr();
+        let hardware_addr = arp.source_hardware_addr();
+        if !protocol_addr.is_unicast() || !hardware_addr.is_unicast() {
+            return;
+        }
+        if arp.is_gratuitous() {
+            self.insert(protocol_addr, hardware_addr);
+            return;
+        }
+        match arp.operation() {
+            Operation::Reply => self.insert(protocol_addr, hardware_addr),
+            Operation::Request if self.lookup(protocol_addr).is_some() => {
+                self.insert(protocol_addr, hardware_addr)
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::protocol::ethernet::{
+        Address as MacAddress,
+        EtherType,
+    };
+    use crate::protocol::ip::ipv4::Address as IPv4Address;
+    use super::{
+        Packet,
+        Hardware,
+        Operation,
+        Cache,
+        CACHE_SIZE,
+    };
+    use core::time::Duration;
+
+    static REQUEST_BYTES: [u8; 28] = [
+        0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x01,
+        0x02, 0xfc, 0x00, 0x00, 0x00, 0x01, 0x0a, 0x0a,
+        0x0a, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x0a, 0x0a, 0x0a, 0x01,
+    ];
+
+    #[test]
+    fn test_request() {
+        let mut bytes = vec![0; 28];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_hardware_type(Hardware::Ethernet);
+        packet.set_protocol_type(EtherType::IPv4);
+        packet.set_hardware_len(6);
+        packet.set_protocol_len(4);
+        packet.set_operation(Operation::Request);
+        packet.set_source_hardware_addr(MacAddress([0x02, 0xfc, 0, 0, 0, 0x01]));
+        packet.set_source_protocol_addr(IPv4Address([10, 10, 10, 2]));
+        packet.set_target_hardware_addr(MacAddress([0; 6]));
+        packet.set_target_protocol_addr(IPv4Address([10, 10, 10, 1]));
+        assert_eq!(packet.as_ref(), &REQUEST_BYTES[..]);
+
+        let packet = Packet::new_checked(&REQUEST_BYTES[..]).unwrap();
+        assert_eq!(packet.protocol_type(), EtherType::IPv4);
+        assert_eq!(packet.hardware_len(), 6);
+        assert_eq!(packet.protocol_len(), 4);
+        assert_eq!(packet.operation(), Operation::Request);
+        assert_eq!(packet.source_hardware_addr(), MacAddress([0x02, 0xfc, 0, 0, 0, 0x01]));
+        assert_eq!(packet.source_protocol_addr(), IPv4Address([10, 10, 10, 2]));
+        assert_eq!(packet.target_hardware_addr(), MacAddress([0; 6]));
+        assert_eq!(packet.target_protocol_addr(), IPv4Address([10, 10, 10, 1]));
+    }
+
+    #[test]
+    fn test_truncated() {
+        assert!(Packet::new_checked(&REQUEST_BYTES[..27]).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "check_len first")]
+    fn test_plain_accessor_truncated() {
+        let packet = Packet::new_unchecked(&REQUEST_BYTES[..27]);
+        packet.target_protocol_addr();
+    }
+
+    #[test]
+    fn test_cache() {
+        let mut cache = Cache::new(Duration::from_secs(10));
+        let mac = MacAddress([0x02, 0xfc, 0, 0, 0, 0x01]);
+        assert_eq!(cache.lookup(IPv4Address([10, 10, 10, 2])), None);
+
+        cache.insert(IPv4Address([10, 10, 10, 2]), mac);
+        assert_eq!(cache.lookup(IPv4Address([10, 10, 10, 2])), Some(mac));
+        assert_eq!(cache.lookup(IPv4Address([10, 10, 10, 3])), None);
+
+        let other = MacAddress([0x02, 0xfc, 0, 0, 0, 0x02]);
+        cache.insert(IPv4Address([10, 10, 10, 2]), other);
+        assert_eq!(cache.lookup(IPv4Address([10, 10, 10, 2])), Some(other));
+
+        // a full cache replaces the entry closest to expiry
+        for i in 0..CACHE_SIZE as u8 {
+            cache.set_time(Duration::from_secs(1 + i as u64));
+            cache.insert(IPv4Address([10, 0, 0, i]), mac);
+        }
+        assert_eq!(cache.lookup(IPv4Address([10, 10, 10, 2])), None);
+        assert_eq!(cache.lookup(IPv4Address([10, 0, 0, 0])), Some(mac));
+        cache.insert(IPv4Address([10, 10, 10, 2]), other);
+        assert_eq!(cache.lookup(IPv4Address([10, 0, 0, 0])), None);
+        assert_eq!(cache.lookup(IPv4Address([10, 0, 0, 1])), Some(mac));
+    }
+
+    #[test]
+    fn test_cache_expiry() {
+        let mut cache = Cache::new(Duration::from_secs(10));
+        let mac = MacAddress([0x02, 0xfc, 0, 0, 0, 0x01]);
+        cache.set_time(Duration::from_secs(100));
+        cache.insert(IPv4Address([10, 10, 10, 2]), mac);
+
+        cache.set_time(Duration::from_millis(109_999));
+        assert_eq!(cache.lookup(IPv4Address([10, 10, 10, 2])), Some(mac));
+        cache.set_time(Duration::from_secs(110));
+        assert_eq!(cache.lookup(IPv4Address([10, 10, 10, 2])), None);
+
+        // refreshing restarts the lifetime
+        cache.insert(IPv4Address([10, 10, 10, 2]), mac);
+        cache.set_time(Duration::from_secs(119));
+        assert_eq!(cache.lookup(IPv4Address([10, 10, 10, 2])), Some(mac));
+
+        let mut cache = Cache::new(Duration::MAX);
+        cache.set_time(Duration::from_secs(100));
+        cache.insert(IPv4Address([10, 10, 10, 2]), mac);
+        cache.set_time(Duration::from_secs(1_000_000));
+        assert_eq!(cache.lookup(IPv4Address([10, 10, 10, 2])), Some(mac));
+    }
+
+    #[test]
+    fn test_cache_fill_from_packet() {
+        let mut cache = Cache::default();
+
+        // requests don't add unknown senders
+        let request = Packet::new_checked(&REQUEST_BYTES[..]).unwrap();
+        cache.fill_from_packet(&request);
+        assert_eq!(cache.lookup(IPv4Address([10, 10, 10, 2])), None);
+
+        let mut bytes = REQUEST_BYTES;
+        let mut reply = Packet::new_unchecked(&mut bytes[..]);
+        reply.set_operation(Operation::Reply);
+        reply.set_source_hardware_addr(MacAddress([0x02, 0xfc, 0, 0, 0, 0x02]));
+        reply.set_source_protocol_addr(IPv4Address([10, 10, 10, 1]));
+        reply.set_target_hardware_addr(MacAddress([0x02, 0xfc, 0, 0, 0, 0x01]));
+        reply.set_target_protocol_addr(IPv4Address([10, 10, 10, 2]));
+        cache.fill_from_packet(&Packet::new_checked(&bytes[..]).unwrap());
+        assert_eq!(
+            cache.lookup(IPv4Address([10, 10, 10, 1])),
+            Some(MacAddress([0x02, 0xfc, 0, 0, 0, 0x02]))
+        );
+
+        // a request from a known sender refreshes its mapping
+        cache.insert(IPv4Address([10, 10, 10, 2]), MacAddress([0x02, 0, 0, 0, 0, 0x09]));
+        cache.fill_from_packet(&request);
+        assert_eq!(
+            cache.lookup(IPv4Address([10, 10, 10, 2])),
+            Some(MacAddress([0x02, 0xfc, 0, 0, 0, 0x01]))
+        );
+
+        // nothing is learned from a sender without an address
+        let mut bytes = REQUEST_BYTES;
+        let mut reply = Packet::new_unchecked(&mut bytes[..]);
+        reply.set_operation(Operation::Reply);
+        reply.set_source_protocol_addr(IPv4Address::UNSPECIFIED);
+        cache.fill_from_packet(&Packet::new_checked(&bytes[..]).unwrap());
+        assert_eq!(cache.lookup(IPv4Address::UNSPECIFIED), None);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_gratuitous() {
+        use super::build_gratuitous;
+
+        assert!(!Packet::new_checked(&REQUEST_BYTES[..]).unwrap().is_gratuitous());
+
+        let ip = IPv4Address([10, 10, 10, 2]);
+        let mac = MacAddress([0x02, 0xfc, 0, 0, 0, 0x09]);
+        let bytes = build_gratuitous(ip, mac);
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert!(packet.is_gratuitous());
+        assert_eq!(packet.operation(), Operation::Request);
+        assert_eq!(packet.source_hardware_addr(), mac);
+        assert_eq!(packet.target_hardware_addr(), MacAddress([0; 6]));
+        assert_eq!(packet.target_protocol_addr(), ip);
+
+        // updates an existing entry, unlike an ordinary request
+        let mut cache = Cache::default();
+        cache.insert(ip, MacAddress([0x02, 0xfc, 0, 0, 0, 0x01]));
+        cache.fill_from_packet(&packet);
+        assert_eq!(cache.lookup(ip), Some(mac));
+
+        // and adds a new one
+        let mut cache = Cache::default();
+        cache.fill_from_packet(&packet);
+        assert_eq!(cache.lookup(ip), Some(mac));
+    }
+}
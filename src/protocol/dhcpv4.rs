@@ -0,0 +1,497 @@
+// 0                   1                   2                   3
+// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     op (1)    |   htype (1)   |   hlen (1)    |   hops (1)    |
+// +---------------+---------------+---------------+---------------+
+// |                            xid (4)                            |
+// +-------------------------------+-------------------------------+
+// |           secs (2)            |           flags (2)           |
+// +-------------------------------+-------------------------------+
+// |                          ciaddr  (4)                          |
+// +---------------------------------------------------------------+
+// |                          yiaddr  (4)                          |
+// +---------------------------------------------------------------+
+// |                          siaddr  (4)                          |
+// +---------------------------------------------------------------+
+// |                          giaddr  (4)                          |
+// +---------------------------------------------------------------+
+// |                          chaddr  (16)                         |
+// +---------------------------------------------------------------+
+// |                          sname   (64)                         |
+// +---------------------------------------------------------------+
+// |                          file    (128)                        |
+// +---------------------------------------------------------------+
+// |                          options (variable)                   |
+// +---------------------------------------------------------------+
+
+#![allow(unused)]
+use byteorder::{
+    ByteOrder,
+    NetworkEndian,
+};
+use crate::{
+    Result,
+    Error,
+};
+use crate::bytes;
+use super::ethernet::Address as MacAddress;
+use super::ip::ipv4::Address as IPv4Address;
+use super::port::Port;
+
+pub const SERVER_PORT: Port = Port::DHCP_SERVER;
+pub const CLIENT_PORT: Port = Port::DHCP_CLIENT;
+
+pub const MAGIC_COOKIE: u32 = 0x63825363;
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpCode {
+    Request = 1,
+    Reply   = 2,
+    Unsupported = 0xFF,
+}
+
+impl From<u8> for OpCode {
+    fn from(val: u8) -> Self {
+        match val {
+            1 => Self::Request,
+            2 => Self::Reply,
+            _ => Self::Unsupported,
+        }
+    }
+}
+
+impl From<OpCode> for u8 {
+    fn from(op: OpCode) -> Self {
+        match op {
+            OpCode::Request => 1,
+            OpCode::Reply => 2,
+            OpCode::Unsupported => 0xFF,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DhcpMessageType {
+    Discover,
+    Offer,
+    Request,
+    Decline,
+    Ack,
+    Nak,
+    Release,
+    Inform,
+    Unknown(u8),
+}
+
+impl From<u8> for DhcpMessageType {
+    fn from(val: u8) -> Self {
+        match val {
+            1 => Self::Discover,
+            2 => Self::Offer,
+            3 => Self::Request,
+            4 => Self::Decline,
+            5 => Self::Ack,
+            6 => Self::Nak,
+            7 => Self::Release,
+            8 => Self::Inform,
+            _ => Self::Unknown(val),
+        }
+    }
+}
+
+impl From<DhcpMessageType> for u8 {
+    fn from(msg_type: DhcpMessageType) -> Self {
+        match msg_type {
+            DhcpMessageType::Discover => 1,
+            DhcpMessageType::Offer => 2,
+            DhcpMessageType::Request => 3,
+            DhcpMessageType::Decline => 4,
+            DhcpMessageType::Ack => 5,
+            DhcpMessageType::Nak => 6,
+            DhcpMessageType::Release => 7,
+            DhcpMessageType::Inform => 8,
+            DhcpMessageType::Unknown(val) => val,
+        }
+    }
+}
+
+pub mod option {
+    pub const PAD:            u8 = 0;
+    pub const SUBNET_MASK:    u8 = 1;
+    pub const ROUTER:         u8 = 3;
+    pub const DNS_SERVER:     u8 = 6;
+    pub const REQUESTED_IP:   u8 = 50;
+    pub const LEASE_TIME:     u8 = 51;
+    pub const MESSAGE_TYPE:   u8 = 53;
+    pub const SERVER_ID:      u8 = 54;
+    pub const END:            u8 = 255;
+}
+
+mod field {
+    use crate::Field;
+
+    pub const OP:     usize = 0;
+    pub const HTYPE:  usize = 1;
+    pub const HLEN:   usize = 2;
+    pub const HOPS:   usize = 3;
+    pub const XID:    Field = 4..8;
+    pub const SECS:   Field = 8..10;
+    pub const FLAGS:  Field = 10..12;
+    pub const CIADDR: Field = 12..16;
+    pub const YIADDR: Field = 16..20;
+    pub const SIADDR: Field = 20..24;
+    pub const GIADDR: Field = 24..28;
+    pub const CHADDR: Field = 28..44;
+    pub const SNAME:  Field = 44..108;
+    pub const FILE:   Field = 108..236;
+    pub const MAGIC:  Field = 236..240;
+}
+
+pub const HEADER_LEN: usize = field::MAGIC.end;
+
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T
+}
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    pub fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < HEADER_LEN {
+            Err(Error::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    pub fn op(&self) -> OpCode {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u8(data, field::OP)).into()
+    }
+
+    pub fn htype(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u8(data, field::HTYPE))
+    }
+
+    pub fn hlen(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u8(data, field::HLEN))
+    }
+
+    pub fn hops(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u8(data, field::HOPS))
+    }
+
+    pub fn xid(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u32(data, field::XID))
+    }
+
+    pub fn secs(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::SECS))
+    }
+
+    pub fn flags(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::FLAGS))
+    }
+
+    pub fn ciaddr(&self) -> IPv4Address {
+        let data = self.buffer.as_ref();
+        IPv4Address::from_bytes(bytes::checked(bytes::get_slice(data, field::CIADDR)))
+    }
+
+    pub fn yiaddr(&self) -> IPv4Address {
+        let data = self.buffer.as_ref();
+        IPv4Address::from_bytes(bytes::checked(bytes::get_slice(data, field::YIADDR)))
+    }
+
+    pub fn siaddr(&self) -> IPv4Address {
+        let data = self.buffer.as_ref();
+        IPv4Address::from_bytes(bytes::checked(bytes::get_slice(data, field::SIADDR)))
+    }
+
+    pub fn giaddr(&self) -> IPv4Address {
+        let data = self.buffer.as_ref();
+        IPv4Address::from_bytes(bytes::checked(bytes::get_slice(data, field::GIADDR)))
+    }
+
+    // the client hardware address, assuming Ethernet
+    pub fn chaddr(&self) -> MacAddress {
+        let data = self.buffer.as_ref();
+        MacAddress::from_bytes(bytes::checked(bytes::get_slice(data, field::CHADDR.start..field::CHADDR.start + 6)))
+    }
+
+    pub fn magic_cookie(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u32(data, field::MAGIC))
+    }
+
+    // Options follow the magic cookie; a BOOTP message without it
+    // carries no DHCP options and is rejected as Error::Malformed.
+    pub fn options(&self) -> Result<OptionsIter<'_>> {
+        if self.magic_cookie() != MAGIC_COOKIE {
+            return Err(Error::Malformed);
+        }
+        let data = self.buffer.as_ref();
+        Ok(OptionsIter::new(bytes::get_slice(data, HEADER_LEN..)?))
+    }
+
+    pub fn message_type(&self) -> Option<DhcpMessageType> {
+        self.options().ok()?
+            .filter_map(|option| option.ok())
+            .find(|&(kind, data)| kind == option::MESSAGE_TYPE && data.len() == 1)
+            .map(|(_, data)| data[0].into())
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    pub fn set_op(&mut self, op: OpCode) {
+        let data = self.buffer.as_mut();
+        data[field::OP] = op.into();
+    }
+
+    pub fn set_htype(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::HTYPE] = value;
+    }
+
+    pub fn set_hlen(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::HLEN] = value;
+    }
+
+    pub fn set_hops(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::HOPS] = value;
+    }
+
+    pub fn set_xid(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[field::XID], value);
+    }
+
+    pub fn set_secs(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::SECS], value);
+    }
+
+    pub fn set_flags(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::FLAGS], value);
+    }
+
+    pub fn set_ciaddr(&mut self, addr: IPv4Address) {
+        let data = self.buffer.as_mut();
+        data[field::CIADDR].copy_from_slice(addr.as_bytes());
+    }
+
+    pub fn set_yiaddr(&mut self, addr: IPv4Address) {
+        let data = self.buffer.as_mut();
+        data[field::YIADDR].copy_from_slice(addr.as_bytes());
+    }
+
+    pub fn set_siaddr(&mut self, addr: IPv4Address) {
+        let data = self.buffer.as_mut();
+        data[field::SIADDR].copy_from_slice(addr.as_bytes());
+    }
+
+    pub fn set_giaddr(&mut self, addr: IPv4Address) {
+        let data = self.buffer.as_mut();
+        data[field::GIADDR].copy_from_slice(addr.as_bytes());
+    }
+
+    pub fn set_chaddr(&mut self, addr: MacAddress) {
+        let data = self.buffer.as_mut();
+        data[field::CHADDR].copy_from_slice(&[0; 16]);
+        data[field::CHADDR.start..field::CHADDR.start + 6].copy_from_slice(addr.as_bytes());
+    }
+
+    pub fn set_magic_cookie(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[field::MAGIC], value);
+    }
+
+    pub fn options_mut(&mut self) -> &mut [u8] {
+        let data = self.buffer.as_mut();
+        &mut data[HEADER_LEN..]
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+// Yields (code, data) for each option. Pad options are skipped and
+// the End option ends the iteration.
+pub struct OptionsIter<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> OptionsIter<'a> {
+    pub fn new(data: &'a [u8]) -> OptionsIter<'a> {
+        OptionsIter { data }
+    }
+}
+
+impl<'a> Iterator for OptionsIter<'a> {
+    type Item = Result<(u8, &'a [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let &kind = self.data.first()?;
+            match kind {
+                option::PAD => {
+                    self.data = &self.data[1..];
+                }
+                option::END => {
+                    self.data = &[];
+                    return None;
+                }
+                _ => {
+                    // unlike IPv4 and TCP, the length excludes the type
+                    // and length octets
+                    let len = match self.data.get(1) {
+                        Some(&len) => len as usize,
+                        None => 0,
+                    };
+                    if self.data.len() < 2 || len > self.data.len() - 2 {
+                        self.data = &[];
+                        return Some(Err(Error::Malformed));
+                    }
+                    let value = &self.data[2..2 + len];
+                    self.data = &self.data[2 + len..];
+                    return Some(Ok((kind, value)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Error;
+    use crate::protocol::ethernet::Address as MacAddress;
+    use crate::protocol::ip::ipv4::Address as IPv4Address;
+    use super::{
+        Packet,
+        OpCode,
+        DhcpMessageType,
+        option,
+    };
+
+    // DHCPOFFER of 192.168.1.100 from 192.168.1.1
+    fn offer_bytes() -> Vec<u8> {
+        let mut bytes = vec![0; 240];
+        bytes[..4].copy_from_slice(&[0x02, 0x01, 0x06, 0x00]);
+        bytes[4..8].copy_from_slice(&[0x39, 0x03, 0xf3, 0x26]);
+        bytes[16..20].copy_from_slice(&[192, 168, 1, 100]);
+        bytes[20..24].copy_from_slice(&[192, 168, 1, 1]);
+        bytes[28..34].copy_from_slice(&[0x00, 0x0b, 0x82, 0x01, 0xfc, 0x42]);
+        bytes[236..240].copy_from_slice(&[0x63, 0x82, 0x53, 0x63]);
+        bytes.extend_from_slice(&[
+            0x35, 0x01, 0x02,
+            0x01, 0x04, 0xff, 0xff, 0xff, 0x00,
+            0x3a, 0x04, 0x00, 0x00, 0x07, 0x08,
+            0x33, 0x04, 0x00, 0x00, 0x0e, 0x10,
+            0x36, 0x04, 0xc0, 0xa8, 0x01, 0x01,
+            0x00,
+            0xff,
+        ]);
+        bytes
+    }
+
+    #[test]
+    fn test_offer() {
+        let bytes = offer_bytes();
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert_eq!(packet.op(), OpCode::Reply);
+        assert_eq!(packet.htype(), 1);
+        assert_eq!(packet.hlen(), 6);
+        assert_eq!(packet.xid(), 0x3903f326);
+        assert_eq!(packet.yiaddr(), IPv4Address([192, 168, 1, 100]));
+        assert_eq!(packet.siaddr(), IPv4Address([192, 168, 1, 1]));
+        assert_eq!(packet.chaddr(), MacAddress([0x00, 0x0b, 0x82, 0x01, 0xfc, 0x42]));
+        assert_eq!(packet.message_type(), Some(DhcpMessageType::Offer));
+
+        let mut options = packet.options().unwrap();
+        assert_eq!(options.next(), Some(Ok((option::MESSAGE_TYPE, &[2][..]))));
+        assert_eq!(options.next(), Some(Ok((option::SUBNET_MASK, &[255, 255, 255, 0][..]))));
+        assert_eq!(options.by_ref().count(), 3);
+        assert_eq!(options.next(), None);
+    }
+
+    #[test]
+    fn test_construct() {
+        let mut bytes = vec![0; 240 + 4];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_op(OpCode::Request);
+        packet.set_htype(1);
+        packet.set_hlen(6);
+        packet.set_xid(0x3903f326);
+        packet.set_chaddr(MacAddress([0x00, 0x0b, 0x82, 0x01, 0xfc, 0x42]));
+        packet.set_magic_cookie(super::MAGIC_COOKIE);
+        packet.options_mut().copy_from_slice(&[option::MESSAGE_TYPE, 1, 1, option::END]);
+
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert_eq!(packet.op(), OpCode::Request);
+        assert_eq!(packet.message_type(), Some(DhcpMessageType::Discover));
+    }
+
+    #[test]
+    fn test_malformed() {
+        let mut bytes = offer_bytes();
+        bytes[236] = 0;
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert_eq!(packet.options().err(), Some(Error::Malformed));
+        assert_eq!(packet.message_type(), None);
+
+        let mut bytes = offer_bytes();
+        bytes.truncate(240 + 5);
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        let mut options = packet.options().unwrap();
+        assert!(options.next().unwrap().is_ok());
+        assert_eq!(options.next(), Some(Err(Error::Malformed)));
+        assert_eq!(options.next(), None);
+
+        assert_eq!(Packet::new_checked(&bytes[..239]).err(), Some(Error::Truncated));
+    }
+
+    #[test]
+    fn test_truncated_option() {
+        use super::OptionsIter;
+
+        // no length octet
+        let mut options = OptionsIter::new(&[53]);
+        assert_eq!(options.next(), Some(Err(Error::Malformed)));
+        assert_eq!(options.next(), None);
+
+        // a length octet but no value
+        let mut options = OptionsIter::new(&[53, 1]);
+        assert_eq!(options.next(), Some(Err(Error::Malformed)));
+        assert_eq!(options.next(), None);
+
+        let mut options = OptionsIter::new(&[53, 1, 5]);
+        assert_eq!(options.next(), Some(Ok((53, &[5][..]))));
+        assert_eq!(options.next(), None);
+    }
+}
@@ -0,0 +1,517 @@
+// 0                   1                   2                   3
+// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |              ID               |Q| Opcode|A|T|R|R|Z|A|C| RCODE |
+// |                               |R|       |A|C|D|A| |D|D|       |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |            QDCOUNT            |            ANCOUNT            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |            NSCOUNT            |            ARCOUNT            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     Questions, answers, authority and additional records ...
+// +-+-+-+-+-
+
+#![allow(unused)]
+use core::fmt;
+use byteorder::{
+    ByteOrder,
+    NetworkEndian,
+};
+use crate::{
+    Result,
+    Error,
+};
+use crate::bytes;
+
+mod field {
+    use crate::Field;
+
+    pub const ID:      Field = 0..2;
+    pub const FLAGS:   Field = 2..4;
+    pub const QDCOUNT: Field = 4..6;
+    pub const ANCOUNT: Field = 6..8;
+    pub const NSCOUNT: Field = 8..10;
+    pub const ARCOUNT: Field = 10..12;
+
+    pub const FLG_QR:      u16 = 0x8000;
+    pub const OPCODE_MASK: u16 = 0x7800;
+    pub const OPCODE_SHIFT: u16 = 11;
+    pub const FLG_AA:      u16 = 0x0400;
+    pub const FLG_TC:      u16 = 0x0200;
+    pub const FLG_RD:      u16 = 0x0100;
+    pub const FLG_RA:      u16 = 0x0080;
+    pub const RCODE_MASK:  u16 = 0x000f;
+}
+
+pub const HEADER_LEN: usize = field::ARCOUNT.end;
+
+// Names are at most 255 octets long on the wire (RFC 1035, section 2.3.4).
+pub const MAX_NAME_LEN: usize = 255;
+
+// Every jump of a legitimate name goes to an earlier label, so a name
+// that needs more jumps than this contains a pointer cycle.
+const MAX_POINTER_JUMPS: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Query,
+    IQuery,
+    Status,
+    Notify,
+    Update,
+    Unknown(u8),
+}
+
+impl From<u8> for Opcode {
+    fn from(val: u8) -> Self {
+        match val {
+            0 => Self::Query,
+            1 => Self::IQuery,
+            2 => Self::Status,
+            4 => Self::Notify,
+            5 => Self::Update,
+            _ => Self::Unknown(val),
+        }
+    }
+}
+
+impl From<Opcode> for u8 {
+    fn from(opcode: Opcode) -> Self {
+        match opcode {
+            Opcode::Query => 0,
+            Opcode::IQuery => 1,
+            Opcode::Status => 2,
+            Opcode::Notify => 4,
+            Opcode::Update => 5,
+            Opcode::Unknown(val) => val,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rcode {
+    NoError,
+    FormErr,
+    ServFail,
+    NXDomain,
+    NotImp,
+    Refused,
+    Unknown(u8),
+}
+
+impl From<u8> for Rcode {
+    fn from(val: u8) -> Self {
+        match val {
+            0 => Self::NoError,
+            1 => Self::FormErr,
+            2 => Self::ServFail,
+            3 => Self::NXDomain,
+            4 => Self::NotImp,
+            5 => Self::Refused,
+            _ => Self::Unknown(val),
+        }
+    }
+}
+
+impl From<Rcode> for u8 {
+    fn from(rcode: Rcode) -> Self {
+        match rcode {
+            Rcode::NoError => 0,
+            Rcode::FormErr => 1,
+            Rcode::ServFail => 2,
+            Rcode::NXDomain => 3,
+            Rcode::NotImp => 4,
+            Rcode::Refused => 5,
+            Rcode::Unknown(val) => val,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    A,
+    Ns,
+    Cname,
+    Soa,
+    Ptr,
+    Mx,
+    Txt,
+    Aaaa,
+    Srv,
+    Any,
+    Unknown(u16),
+}
+
+impl From<u16> for Type {
+    fn from(val: u16) -> Self {
+        match val {
+            1 => Self::A,
+            2 => Self::Ns,
+            5 => Self::Cname,
+            6 => Self::Soa,
+            12 => Self::Ptr,
+            15 => Self::Mx,
+            16 => Self::Txt,
+            28 => Self::Aaaa,
+            33 => Self::Srv,
+            255 => Self::Any,
+            _ => Self::Unknown(val),
+        }
+    }
+}
+
+impl From<Type> for u16 {
+    fn from(qtype: Type) -> Self {
+        match qtype {
+            Type::A => 1,
+            Type::Ns => 2,
+            Type::Cname => 5,
+            Type::Soa => 6,
+            Type::Ptr => 12,
+            Type::Mx => 15,
+            Type::Txt => 16,
+            Type::Aaaa => 28,
+            Type::Srv => 33,
+            Type::Any => 255,
+            Type::Unknown(val) => val,
+        }
+    }
+}
+
+pub const CLASS_IN: u16 = 1;
+
+pub struct Message<T: AsRef<[u8]>> {
+    buffer: T
+}
+
+impl<T: AsRef<[u8]>> Message<T> {
+    pub fn new_unchecked(buffer: T) -> Message<T> {
+        Message { buffer }
+    }
+
+    pub fn new_checked(buffer: T) -> Result<Message<T>> {
+        let message = Self::new_unchecked(buffer);
+        message.check_len()?;
+        Ok(message)
+    }
+
+    pub fn check_len(&self) -> Result<()> {
+        if self.buffer.as_ref().len() < HEADER_LEN {
+            Err(Error::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    pub fn id(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::ID))
+    }
+
+    pub fn flags(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::FLAGS))
+    }
+
+    // true for a response, false for a query
+    pub fn qr(&self) -> bool {
+        self.flags() & field::FLG_QR != 0
+    }
+
+    pub fn opcode(&self) -> Opcode {
+        Opcode::from(((self.flags() & field::OPCODE_MASK) >> field::OPCODE_SHIFT) as u8)
+    }
+
+    pub fn authoritative(&self) -> bool {
+        self.flags() & field::FLG_AA != 0
+    }
+
+    pub fn truncated(&self) -> bool {
+        self.flags() & field::FLG_TC != 0
+    }
+
+    pub fn recursion_desired(&self) -> bool {
+        self.flags() & field::FLG_RD != 0
+    }
+
+    pub fn recursion_available(&self) -> bool {
+        self.flags() & field::FLG_RA != 0
+    }
+
+    pub fn rcode(&self) -> Rcode {
+        Rcode::from((self.flags() & field::RCODE_MASK) as u8)
+    }
+
+    pub fn question_count(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::QDCOUNT))
+    }
+
+    pub fn answer_count(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::ANCOUNT))
+    }
+
+    pub fn authority_count(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::NSCOUNT))
+    }
+
+    pub fn additional_count(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::ARCOUNT))
+    }
+
+    pub fn questions(&self) -> QuestionIter<'_> {
+        QuestionIter {
+            data: self.buffer.as_ref(),
+            offset: HEADER_LEN,
+            remaining: self.question_count(),
+        }
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Message<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+// A possibly compressed domain name inside a message.
+#[derive(Debug, Clone, Copy)]
+pub struct Name<'a> {
+    message: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Name<'a> {
+    // Validate the name starting at `offset` of `message`, following
+    // compression pointers. Returns the name and the offset just past
+    // it in place, i.e. where the next field starts.
+    pub fn parse(message: &'a [u8], offset: usize) -> Result<(Name<'a>, usize)> {
+        let mut pos = offset;
+        let mut end = None;
+        let mut jumps = 0;
+        let mut name_len = 0;
+        loop {
+            let len = *message.get(pos).ok_or(Error::Truncated)? as usize;
+            match len & 0xc0 {
+                0x00 => {
+                    name_len += len + 1;
+                    if name_len > MAX_NAME_LEN {
+                        return Err(Error::Malformed);
+                    }
+                    if len == 0 {
+                        let name = Name { message, offset };
+                        return Ok((name, end.unwrap_or(pos + 1)));
+                    }
+                    if pos + 1 + len > message.len() {
+                        return Err(Error::Truncated);
+                    }
+                    pos += 1 + len;
+                }
+                0xc0 => {
+                    let pointer = message.get(pos..pos + 2).ok_or(Error::Truncated)?;
+                    end.get_or_insert(pos + 2);
+                    jumps += 1;
+                    if jumps > MAX_POINTER_JUMPS {
+                        return Err(Error::Malformed);
+                    }
+                    pos = (NetworkEndian::read_u16(pointer) & 0x3fff) as usize;
+                }
+                // the extended and binary label types are obsolete
+                _ => return Err(Error::Malformed),
+            }
+        }
+    }
+
+    pub fn labels(&self) -> LabelIter<'a> {
+        LabelIter { message: self.message, pos: self.offset, jumps: 0 }
+    }
+}
+
+// Prints the name with dots between labels, e.g. "example.com";
+// the root name prints as ".".
+impl<'a> fmt::Display for Name<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut labels = self.labels().peekable();
+        if labels.peek().is_none() {
+            return write!(f, ".");
+        }
+        for (i, label) in labels.enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            for &byte in label {
+                write!(f, "{}", byte as char)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Labels of a name already validated by `Name::parse`.
+pub struct LabelIter<'a> {
+    message: &'a [u8],
+    pos: usize,
+    jumps: usize,
+}
+
+impl<'a> Iterator for LabelIter<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let len = *self.message.get(self.pos)? as usize;
+            match len & 0xc0 {
+                0x00 if len > 0 => {
+                    let label = self.message.get(self.pos + 1..self.pos + 1 + len)?;
+                    self.pos += 1 + len;
+                    return Some(label);
+                }
+                0xc0 if self.jumps < MAX_POINTER_JUMPS => {
+                    let pointer = self.message.get(self.pos..self.pos + 2)?;
+                    self.pos = (NetworkEndian::read_u16(pointer) & 0x3fff) as usize;
+                    self.jumps += 1;
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Question<'a> {
+    pub name: Name<'a>,
+    pub qtype: Type,
+    pub qclass: u16,
+}
+
+pub struct QuestionIter<'a> {
+    data: &'a [u8],
+    offset: usize,
+    remaining: u16,
+}
+
+impl<'a> QuestionIter<'a> {
+    fn parse(&self) -> Result<(Question<'a>, usize)> {
+        let (name, end) = Name::parse(self.data, self.offset)?;
+        let fixed = self.data.get(end..end + 4).ok_or(Error::Truncated)?;
+        let question = Question {
+            name,
+            qtype: Type::from(NetworkEndian::read_u16(&fixed[0..2])),
+            qclass: NetworkEndian::read_u16(&fixed[2..4]),
+        };
+        Ok((question, end + 4))
+    }
+}
+
+impl<'a> Iterator for QuestionIter<'a> {
+    type Item = Result<Question<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        match self.parse() {
+            Ok((question, next)) => {
+                self.remaining -= 1;
+                self.offset = next;
+                Some(Ok(question))
+            }
+            Err(err) => {
+                // the position of the next question is unknown
+                self.remaining = 0;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Error;
+    use super::{
+        Message,
+        Name,
+        Opcode,
+        Rcode,
+        Type,
+        CLASS_IN,
+    };
+
+    // A? example.com, as sent by dig
+    static QUERY_BYTES: [u8; 29] = [
+        0x1a, 0x2b, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x07, 0x65, 0x78, 0x61,
+        0x6d, 0x70, 0x6c, 0x65, 0x03, 0x63, 0x6f, 0x6d,
+        0x00, 0x00, 0x01, 0x00, 0x01,
+    ];
+
+    #[test]
+    fn test_query() {
+        let message = Message::new_checked(&QUERY_BYTES[..]).unwrap();
+        assert_eq!(message.id(), 0x1a2b);
+        assert!(!message.qr());
+        assert_eq!(message.opcode(), Opcode::Query);
+        assert!(message.recursion_desired());
+        assert_eq!(message.rcode(), Rcode::NoError);
+        assert_eq!(message.question_count(), 1);
+        assert_eq!(message.answer_count(), 0);
+        assert_eq!(message.authority_count(), 0);
+        assert_eq!(message.additional_count(), 0);
+
+        let mut questions = message.questions();
+        let question = questions.next().unwrap().unwrap();
+        assert_eq!(question.name.to_string(), "example.com");
+        assert_eq!(question.qtype, Type::A);
+        assert_eq!(question.qclass, CLASS_IN);
+        assert!(questions.next().is_none());
+    }
+
+    #[test]
+    fn test_compressed_name() {
+        // a second question "www" + pointer to "example.com" at offset 12
+        let mut bytes = QUERY_BYTES.to_vec();
+        bytes[5] = 2;
+        bytes.extend_from_slice(&[
+            0x03, 0x77, 0x77, 0x77, 0xc0, 0x0c, 0x00, 0x1c, 0x00, 0x01,
+        ]);
+        let message = Message::new_checked(&bytes[..]).unwrap();
+        let names: Vec<_> = message.questions()
+            .map(|question| question.unwrap())
+            .map(|question| (question.name.to_string(), question.qtype))
+            .collect();
+        assert_eq!(names, [
+            ("example.com".to_string(), Type::A),
+            ("www.example.com".to_string(), Type::Aaaa),
+        ]);
+
+        assert_eq!(Name::parse(&[0x00], 0).unwrap().0.to_string(), ".");
+    }
+
+    #[test]
+    fn test_malformed_name() {
+        // the name is a pointer to itself
+        let mut bytes = QUERY_BYTES;
+        bytes[12] = 0xc0;
+        bytes[13] = 0x0c;
+        let message = Message::new_checked(&bytes[..]).unwrap();
+        let mut questions = message.questions();
+        assert_eq!(questions.next().unwrap().err(), Some(Error::Malformed));
+        assert!(questions.next().is_none());
+
+        // two pointers to each other
+        let bytes = [0xc0, 0x02, 0xc0, 0x00];
+        assert_eq!(Name::parse(&bytes, 0).err(), Some(Error::Malformed));
+
+        let message = Message::new_checked(&QUERY_BYTES[..20]).unwrap();
+        assert_eq!(message.questions().next().unwrap().err(), Some(Error::Truncated));
+
+        assert_eq!(Message::new_checked(&QUERY_BYTES[..11]).err(), Some(Error::Truncated));
+    }
+}
@@ -3,20 +3,42 @@ use crate::{
     Result,
     Error,
 };
+use crate::bytes;
 
 use byteorder::{
     NetworkEndian,
     ByteOrder,
 };
+use core::fmt;
+use core::str::FromStr;
+use core::convert::TryFrom;
+use super::ip::ipv4::Address as IPv4Address;
+use super::ip::ipv6::Address as IPv6Address;
+use super::ip::ipv4::Packet as IPv4Packet;
+use super::ip::Protocol;
+use super::icmp::icmpv4::Packet as ICMPv4Packet;
+use super::ip::ipv6::Packet as IPv6Packet;
+use super::arp;
+use crate::hexdump::HexDump;
+#[cfg(feature = "alloc")]
+use alloc::{
+    vec,
+    vec::Vec,
+};
 
-#[repr(u16)]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EtherType {
-    IPv4 = 0x0800,
-    ARP  = 0x0806,
-    IPv6 = 0x86DD,
-    ECTP = 0x9000,
-    Unsupported = 0xFFFF,
+    IPv4,
+    ARP,
+    WakeOnLan,
+    VlanTagged,
+    IPv6,
+    PppoeDiscovery,
+    PppoeSession,
+    Lldp,
+    ECTP,
+    // keeps the raw value of any other type
+    Unknown(u16),
 }
 
 impl From<u16> for EtherType {
@@ -24,9 +46,14 @@ impl From<u16> for EtherType {
         match val {
             0x0800 => Self::IPv4,
             0x0806 => Self::ARP,
+            0x0842 => Self::WakeOnLan,
+            0x8100 => Self::VlanTagged,
             0x86DD => Self::IPv6,
+            0x8863 => Self::PppoeDiscovery,
+            0x8864 => Self::PppoeSession,
+            0x88CC => Self::Lldp,
             0x9000 => Self::ECTP,
-            _ => Self::Unsupported,
+            _ => Self::Unknown(val),
         }
     }
 }
@@ -36,25 +63,78 @@ impl From<EtherType> for u16 {
         match ether_type {
             EtherType::IPv4 => 0x0800,
             EtherType::ARP  => 0x0806,
+            EtherType::WakeOnLan => 0x0842,
+            EtherType::VlanTagged => 0x8100,
             EtherType::IPv6 => 0x86DD,
+            EtherType::PppoeDiscovery => 0x8863,
+            EtherType::PppoeSession => 0x8864,
+            EtherType::Lldp => 0x88CC,
             EtherType::ECTP => 0x9000,
-            EtherType::Unsupported => 0xFFFF
+            EtherType::Unknown(val) => val,
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+impl fmt::Display for EtherType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            EtherType::IPv4 => "IPv4",
+            EtherType::ARP => "ARP",
+            EtherType::WakeOnLan => "Wake-on-LAN",
+            EtherType::VlanTagged => "802.1Q",
+            EtherType::IPv6 => "IPv6",
+            EtherType::PppoeDiscovery => "PPPoE Discovery",
+            EtherType::PppoeSession => "PPPoE Session",
+            EtherType::Lldp => "LLDP",
+            EtherType::ECTP => "ECTP",
+            EtherType::Unknown(_) => "Unknown",
+        };
+        write!(f, "{} ({:#06x})", name, u16::from(*self))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for EtherType {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_u16((*self).into())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for EtherType {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let raw: u16 = serde::Deserialize::deserialize(deserializer)?;
+        Ok(raw.into())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Address(pub [u8;6]);
 
 impl Address {
     pub const BROADCAST: Address = Address([0xFF; 6]);
+    pub const STP_GROUP: Address = Address([0x01, 0x80, 0xc2, 0x00, 0x00, 0x00]);
 
+    // Panics unless `data` is exactly 6 bytes long; see `try_from_bytes`.
     pub fn from_bytes(data: &[u8]) -> Self {
         let mut addr = [0; 6];
         addr.copy_from_slice(&data);
         Address(addr)
     }
 
+    pub fn try_from_bytes(data: &[u8]) -> Result<Self> {
+        if data.len() != 6 {
+            return Err(Error::Malformed);
+        }
+        Ok(Self::from_bytes(data))
+    }
+
     pub fn as_bytes(&self) -> &[u8] {
         &self.0
     } 
@@ -74,6 +154,123 @@ impl Address {
     pub fn is_local(&self) -> bool {
         self.0[0] & 0x02 != 0
     }
+
+    // The group address an IPv4 multicast address is sent to (RFC 1112),
+    // 01:00:5e followed by the low 23 bits of the IP address.
+    pub fn from_multicast_ipv4(addr: &IPv4Address) -> Address {
+        let bytes = addr.as_bytes();
+        Address([0x01, 0x00, 0x5e, bytes[1] & 0x7F, bytes[2], bytes[3]])
+    }
+
+    // The group address an IPv6 multicast address is sent to (RFC 2464),
+    // 33:33 followed by the low 32 bits of the IP address.
+    pub fn from_multicast_ipv6(addr: &IPv6Address) -> Address {
+        let bytes = addr.as_bytes();
+        Address([0x33, 0x33, bytes[12], bytes[13], bytes[14], bytes[15]])
+    }
+
+    pub fn is_ipv4_multicast(&self) -> bool {
+        self.0[..3] == [0x01, 0x00, 0x5e] && self.0[3] & 0x80 == 0
+    }
+
+    pub fn is_ipv6_multicast(&self) -> bool {
+        self.0[..2] == [0x33, 0x33]
+    }
+
+    // The group address spanning tree BPDUs are sent to (IEEE 802.1D).
+    pub fn is_stp_group(&self) -> bool {
+        *self == Self::STP_GROUP
+    }
+
+    // A locally administered unicast address derived from `seed`;
+    // the same seed always gives the same address.
+    pub fn generate_local(seed: u64) -> Address {
+        // one round of SplitMix64 spreads nearby seeds apart
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        let mut addr = [0; 6];
+        addr.copy_from_slice(&z.to_be_bytes()[..6]);
+        addr[0] = (addr[0] & !0x01) | 0x02;
+        Address(addr)
+    }
+}
+
+impl TryFrom<&[u8]> for Address {
+    type Error = Error;
+
+    fn try_from(data: &[u8]) -> Result<Self> {
+        Self::try_from_bytes(data)
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bytes = self.0;
+        write!(f, "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+               bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5])
+    }
+}
+
+impl FromStr for Address {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Address> {
+        let mut bytes = [0; 6];
+        let mut parts = s.split(':');
+        for byte in bytes.iter_mut() {
+            let part = parts.next().ok_or(Error::Malformed)?;
+            if part.len() != 2 || !part.bytes().all(|c| c.is_ascii_hexdigit()) {
+                return Err(Error::Malformed);
+            }
+            *byte = u8::from_str_radix(part, 16).map_err(|_| Error::Malformed)?;
+        }
+        if parts.next().is_some() {
+            return Err(Error::Malformed);
+        }
+        Ok(Address(bytes))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Address {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Address {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Address;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a MAC address like 02:00:00:00:00:01")
+            }
+
+            fn visit_str<E>(self, value: &str) -> core::result::Result<Address, E>
+            where
+                E: serde::de::Error,
+            {
+                value.parse().map_err(|_| {
+                    E::invalid_value(serde::de::Unexpected::Str(value), &self)
+                })
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
 }
 
 mod field {
@@ -89,6 +286,18 @@ mod field {
 }
 
 pub const HEADER_LEN: usize = field::PAYLOAD.start;
+// minimum frame length on the wire, FCS excluded
+pub const MIN_FRAME_LEN: usize = 60;
+// the payload of a minimum-length frame, padding included
+pub const MIN_PAYLOAD_LEN: usize = MIN_FRAME_LEN - HEADER_LEN;
+// the type/length field is a length up to MAX_802_3_LEN and
+// an EtherType from MIN_ETHER_TYPE
+pub const MAX_802_3_LEN: u16 = 1500;
+pub const MIN_ETHER_TYPE: u16 = 0x0600;
+// an 802.1Q tag: TPID (the VlanTagged EtherType) is in the header,
+// followed by the 2-byte TCI and the inner EtherType
+pub const VLAN_TAG_LEN: usize = 4;
+pub const FCS_LEN: usize = 4;
 
 pub struct Frame<T: AsRef<[u8]>> {
     buffer: T
@@ -105,6 +314,16 @@ impl<T: AsRef<[u8]>> Frame<T> {
         Ok(packet)
     }
 
+    // Like new_checked, but also rejects runts. Use it on receive; on
+    // transmit, short frames are padded instead (see pad_to_minimum).
+    pub fn new_checked_strict(buffer: T) -> Result<Frame<T>> {
+        let packet = Self::new_checked(buffer)?;
+        if packet.is_runt() {
+            return Err(Error::Truncated);
+        }
+        Ok(packet)
+    }
+
     pub fn check_len(&self) -> Result<()> {
         let len = self.buffer.as_ref().len();
         if len < HEADER_LEN {
@@ -114,6 +333,12 @@ impl<T: AsRef<[u8]>> Frame<T> {
         }
     }
 
+    // A received frame shorter than the minimum frame length, FCS
+    // excluded. Such frames are usually collision fragments.
+    pub fn is_runt(&self) -> bool {
+        self.buffer.as_ref().len() < MIN_FRAME_LEN
+    }
+
     pub fn into_inner(self) -> T {
         self.buffer
     }
@@ -126,25 +351,135 @@ impl<T: AsRef<[u8]>> Frame<T> {
         HEADER_LEN + payload_len
     }
 
+    pub fn frame_len_with_padding(payload_len: usize) -> usize {
+        Self::frame_len(payload_len).max(MIN_FRAME_LEN)
+    }
+
     pub fn dst_addr(&self) -> Address {
         let data = self.buffer.as_ref();
-        Address::from_bytes(&data[field::DESTINATION])
+        Address::from_bytes(bytes::checked(bytes::get_slice(data, field::DESTINATION)))
     }
 
     pub fn src_addr(&self) -> Address {
         let data = self.buffer.as_ref();
-        Address::from_bytes(&data[field::SOURCE])
+        Address::from_bytes(bytes::checked(bytes::get_slice(data, field::SOURCE)))
     }
 
     pub fn ether_type(&self) -> EtherType {
+        self.ether_type_raw().into()
+    }
+
+    pub fn ether_type_raw(&self) -> u16 {
         let data = self.buffer.as_ref();
-        let raw = NetworkEndian::read_u16(&data[field::ETHERTYPE]);
-        raw.into()
+        bytes::checked(bytes::get_u16(data, field::ETHERTYPE))
+    }
+
+    // Values from 1536 up are EtherTypes; an IEEE 802.3 frame carries
+    // the payload length there instead, at most 1500.
+    pub fn is_ethernet_ii(&self) -> bool {
+        self.ether_type_raw() >= MIN_ETHER_TYPE
+    }
+
+    pub fn length_field(&self) -> Option<u16> {
+        match self.ether_type_raw() {
+            len if len <= MAX_802_3_LEN => Some(len),
+            _ => None,
+        }
+    }
+
+    // The length of the frame as described by its contents: the 802.3
+    // length field, or the length of an IPv4, IPv6 or ARP payload. It can
+    // be shorter than the buffer, e.g. one built in an oversized scratch
+    // buffer or padded to the minimum length. Other frames, and frames
+    // whose length can't be read, span the whole buffer.
+    pub fn total_len(&self) -> usize {
+        let buffer_len = self.buffer.as_ref().len();
+        let payload = self.payload();
+        let payload_len = match self.length_field() {
+            Some(len) => Some(len as usize),
+            None => match self.ether_type() {
+                EtherType::IPv4 => IPv4Packet::new_unchecked(payload)
+                    .total_len_checked()
+                    .ok()
+                    .map(|len| len as usize),
+                EtherType::IPv6 if payload.len() >= IPv6Packet::<&[u8]>::header_len() => {
+                    let packet = IPv6Packet::new_unchecked(payload);
+                    Some(IPv6Packet::<&[u8]>::header_len() + packet.payload_len() as usize)
+                }
+                EtherType::ARP => Some(arp::PACKET_LEN),
+                _ => None,
+            }
+        };
+        match payload_len {
+            Some(len) => (HEADER_LEN + len).min(buffer_len),
+            None => buffer_len,
+        }
     }
 
     pub fn payload(&self) -> &[u8] {
         let data = self.buffer.as_ref();
-        &data[field::PAYLOAD]
+        bytes::checked(bytes::get_slice(data, field::PAYLOAD))
+    }
+
+    // The EtherType of the encapsulated protocol, looking past an
+    // 802.1Q tag if there is one.
+    pub fn inner_ether_type(&self) -> EtherType {
+        let data = self.buffer.as_ref();
+        match self.ether_type() {
+            EtherType::VlanTagged if data.len() >= HEADER_LEN + VLAN_TAG_LEN => {
+                bytes::checked(bytes::get_u16(data, HEADER_LEN + 2..HEADER_LEN + 4)).into()
+            }
+            ether_type => ether_type,
+        }
+    }
+
+    // The payload of the encapsulated protocol, without an 802.1Q tag
+    // and, if `has_fcs` is set, without the trailing frame check sequence.
+    pub fn inner_payload(&self, has_fcs: bool) -> &[u8] {
+        let data = self.buffer.as_ref();
+        let start = match self.ether_type() {
+            EtherType::VlanTagged => HEADER_LEN + VLAN_TAG_LEN,
+            _ => HEADER_LEN,
+        };
+        let end = if has_fcs {
+            data.len().saturating_sub(FCS_LEN)
+        } else {
+            data.len()
+        };
+        if start > end {
+            return &[];
+        }
+        bytes::checked(bytes::get_slice(data, start..end))
+    }
+}
+
+impl<'a> Frame<&'a [u8]> {
+    // The frame occupying the first `len` bytes of a larger buffer.
+    pub fn new_checked_len(buffer: &'a [u8], len: usize) -> Result<Frame<&'a [u8]>> {
+        if buffer.len() < len {
+            return Err(Error::Truncated);
+        }
+        Frame::new_checked(&buffer[..len])
+    }
+
+    // Parse an ICMP message carried in an IPv4 packet in this frame.
+    // Returns Error::Unrecognized for other EtherTypes or protocols, and
+    // Error::Fragmented for a fragment, which holds only part of a message.
+    pub fn icmp_over_ipv4(&self) -> Result<ICMPv4Packet<&'a [u8]>> {
+        self.check_len()?;
+        if self.ether_type() != EtherType::IPv4 {
+            return Err(Error::Unrecognized);
+        }
+        let packet = IPv4Packet::new_checked(&self.buffer[field::PAYLOAD])?;
+        if packet.protocol() != Protocol::ICMP {
+            return Err(Error::Unrecognized);
+        }
+        if packet.more_frags() || packet.frag_offset() != 0 {
+            return Err(Error::Fragmented);
+        }
+        let header_len = packet.header_len() as usize;
+        let total_len = packet.total_len() as usize;
+        ICMPv4Packet::new_checked(&packet.into_inner()[header_len..total_len])
     }
 }
 
@@ -173,8 +508,571 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Frame<T> {
     }
 }
 
+#[cfg(feature = "alloc")]
+impl<T: AsRef<[u8]>> Frame<T> {
+    // Copy the frame into an owned buffer.
+    pub fn into_owned(self) -> Frame<Vec<u8>> {
+        Frame::new_unchecked(self.buffer.as_ref().to_vec())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Frame<Vec<u8>> {
+    // Allocate a frame with room for the header and the payload,
+    // padded to the minimum frame length.
+    pub fn builder(payload_len: usize) -> Frame<Vec<u8>> {
+        Frame::new_unchecked(vec![0; Self::frame_len_with_padding(payload_len)])
+    }
+
+    // Zero-fill the payload of a runt frame up to the minimum frame length.
+    pub fn pad_to_minimum(&mut self) {
+        if self.buffer.len() < MIN_FRAME_LEN {
+            self.buffer.resize(MIN_FRAME_LEN, 0);
+        }
+    }
+}
+
+impl<T: AsRef<[u8]>> Frame<T> {
+    pub fn hexdump(&self) -> impl fmt::Display + '_ {
+        HexDump(self.buffer.as_ref())
+    }
+}
+
+// A header field, or the payload length, that differs between two
+// frames, holding the values of self and of the other frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldDiff {
+    DstAddr(Address, Address),
+    SrcAddr(Address, Address),
+    EtherType(EtherType, EtherType),
+    PayloadLen(usize, usize),
+}
+
+impl fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldDiff::DstAddr(a, b) => write!(f, "dst: {} != {}", a, b),
+            FieldDiff::SrcAddr(a, b) => write!(f, "src: {} != {}", a, b),
+            FieldDiff::EtherType(a, b) => write!(f, "ethertype: {} != {}", a, b),
+            FieldDiff::PayloadLen(a, b) => write!(f, "payload length: {} != {}", a, b),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: AsRef<[u8]>> Frame<T> {
+    // The fields that differ from another frame, for assertion messages
+    // more helpful than a byte slice mismatch. The payload itself is only
+    // compared by length.
+    pub fn diff<U: AsRef<[u8]>>(&self, other: &Frame<U>) -> Vec<FieldDiff> {
+        let mut diffs = Vec::new();
+        if self.dst_addr() != other.dst_addr() {
+            diffs.push(FieldDiff::DstAddr(self.dst_addr(), other.dst_addr()));
+        }
+        if self.src_addr() != other.src_addr() {
+            diffs.push(FieldDiff::SrcAddr(self.src_addr(), other.src_addr()));
+        }
+        if self.ether_type_raw() != other.ether_type_raw() {
+            diffs.push(FieldDiff::EtherType(self.ether_type(), other.ether_type()));
+        }
+        if self.payload().len() != other.payload().len() {
+            diffs.push(FieldDiff::PayloadLen(self.payload().len(), other.payload().len()));
+        }
+        diffs
+    }
+}
+
 impl<T: AsRef<[u8]>> AsRef<[u8]> for Frame<T> {
     fn as_ref(&self) -> &[u8] {
         self.buffer.as_ref()
     }
 }
+
+// A parsed Ethernet II header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Repr {
+    pub src_addr: Address,
+    pub dst_addr: Address,
+    pub ethertype: EtherType,
+}
+
+impl Repr {
+    pub fn parse(frame: &Frame<&[u8]>) -> Result<Repr> {
+        frame.check_len()?;
+        Ok(Repr {
+            src_addr: frame.src_addr(),
+            dst_addr: frame.dst_addr(),
+            ethertype: frame.ether_type(),
+        })
+    }
+
+    // the length of the header; the payload follows it
+    pub fn buffer_len(&self) -> usize {
+        HEADER_LEN
+    }
+
+    pub fn emit<T: AsRef<[u8]> + AsMut<[u8]>>(&self, frame: &mut Frame<T>) {
+        frame.set_src_addr(self.src_addr);
+        frame.set_dst_addr(self.dst_addr);
+        frame.set_ether_type(self.ethertype);
+    }
+}
+
+// How FrameReader finds where each frame ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameLength {
+    // every frame has the same length
+    Fixed(usize),
+    // every frame is preceded by its length as a big-endian u16
+    Prefixed,
+}
+
+// Yields the frames of a buffer holding several of them back to back,
+// e.g. a replayed capture or a ring buffer.
+pub struct FrameReader<'a> {
+    data: &'a [u8],
+    length: FrameLength,
+}
+
+impl<'a> FrameReader<'a> {
+    pub fn new(data: &'a [u8], length: FrameLength) -> FrameReader<'a> {
+        FrameReader { data, length }
+    }
+}
+
+impl<'a> Iterator for FrameReader<'a> {
+    type Item = Result<Frame<&'a [u8]>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let (start, len) = match self.length {
+            FrameLength::Fixed(len) => (0, len),
+            FrameLength::Prefixed if self.data.len() >= 2 => {
+                (2, NetworkEndian::read_u16(self.data) as usize)
+            }
+            FrameLength::Prefixed => (2, 0),
+        };
+        if self.data.len() < start + len {
+            self.data = &[];
+            return Some(Err(Error::Truncated));
+        }
+        let frame = Frame::new_checked(&self.data[start..start + len]);
+        // a runt frame, e.g. from a Fixed(0) length, ends the iteration
+        self.data = match frame {
+            Ok(_) => &self.data[start + len..],
+            Err(_) => &[],
+        };
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Error;
+    use super::{
+        EtherType,
+        Frame,
+        FrameReader,
+        FrameLength,
+        Repr,
+        Address,
+        FieldDiff,
+        IPv4Address,
+        IPv6Address,
+        MIN_FRAME_LEN,
+        MIN_PAYLOAD_LEN,
+    };
+
+    #[test]
+    fn test_ether_type() {
+        let known = [
+            (0x0800, EtherType::IPv4),
+            (0x0806, EtherType::ARP),
+            (0x0842, EtherType::WakeOnLan),
+            (0x8100, EtherType::VlanTagged),
+            (0x86DD, EtherType::IPv6),
+            (0x8863, EtherType::PppoeDiscovery),
+            (0x8864, EtherType::PppoeSession),
+            (0x88CC, EtherType::Lldp),
+            (0x9000, EtherType::ECTP),
+        ];
+        for &(raw, ether_type) in known.iter() {
+            assert_eq!(EtherType::from(raw), ether_type);
+            assert_eq!(u16::from(ether_type), raw);
+        }
+
+        assert_eq!(EtherType::from(0x88B5), EtherType::Unknown(0x88B5));
+        assert_eq!(u16::from(EtherType::from(0x88B5)), 0x88B5);
+        assert_eq!(u16::from(EtherType::from(0xFFFF)), 0xFFFF);
+    }
+
+    #[test]
+    fn test_ether_type_raw() {
+        let mut bytes = [0; 14];
+        bytes[12..14].copy_from_slice(&[0x88, 0xb5]);
+        let frame = Frame::new_unchecked(&bytes[..]);
+        assert_eq!(frame.ether_type_raw(), 0x88b5);
+        assert_eq!(frame.ether_type(), EtherType::Unknown(0x88b5));
+    }
+
+    #[test]
+    fn test_generate_local() {
+        for seed in 0..64 {
+            let addr = Address::generate_local(seed);
+            assert!(addr.is_unicast(), "{}", addr);
+            assert!(addr.is_local(), "{}", addr);
+            assert_eq!(Address::generate_local(seed), addr);
+        }
+        assert_ne!(Address::generate_local(1), Address::generate_local(2));
+        assert!(Address::generate_local(u64::MAX).is_local());
+    }
+
+    #[test]
+    fn test_multicast_mapping() {
+        let addr = Address::from_multicast_ipv4(&IPv4Address([224, 0, 0, 1]));
+        assert_eq!(addr, Address([0x01, 0x00, 0x5e, 0x00, 0x00, 0x01]));
+        assert!(addr.is_multicast() && addr.is_ipv4_multicast());
+
+        // the high bit of the second octet is dropped
+        let addr = Address::from_multicast_ipv4(&IPv4Address([239, 255, 255, 250]));
+        assert_eq!(addr, Address([0x01, 0x00, 0x5e, 0x7f, 0xff, 0xfa]));
+
+        let addr = Address::from_multicast_ipv6(&IPv6Address::LINK_LOCAL_ALL_NODES);
+        assert_eq!(addr, Address([0x33, 0x33, 0x00, 0x00, 0x00, 0x01]));
+        assert!(addr.is_multicast() && addr.is_ipv6_multicast());
+        assert!(!addr.is_ipv4_multicast());
+
+        let solicited = IPv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0x1234, 0x5678)
+            .solicited_node_multicast();
+        let addr = Address::from_multicast_ipv6(&solicited);
+        assert_eq!(addr, Address([0x33, 0x33, 0xff, 0x34, 0x56, 0x78]));
+
+        assert!(!Address::BROADCAST.is_ipv4_multicast());
+        assert!(!Address::BROADCAST.is_ipv6_multicast());
+    }
+
+    #[test]
+    fn test_ether_type_display() {
+        assert_eq!(format!("{}", EtherType::IPv4), "IPv4 (0x0800)");
+        assert_eq!(format!("{}", EtherType::IPv6), "IPv6 (0x86dd)");
+        assert_eq!(format!("{}", EtherType::Unknown(0x88b5)), "Unknown (0x88b5)");
+        assert_eq!(format!("{}", EtherType::Unknown(0x0001)), "Unknown (0x0001)");
+    }
+
+    #[test]
+    fn test_address_str() {
+        let addr: Address = "02:fc:00:00:00:01".parse().unwrap();
+        assert_eq!(addr, Address([0x02, 0xfc, 0, 0, 0, 0x01]));
+        assert_eq!(format!("{}", addr), "02:fc:00:00:00:01");
+        assert_eq!("FF:FF:FF:FF:FF:FF".parse(), Ok(Address::BROADCAST));
+
+        assert!("02:fc:00:00:00".parse::<Address>().is_err());
+        assert!("02:fc:00:00:00:01:02".parse::<Address>().is_err());
+        assert!("02:fc:00:00:00:1".parse::<Address>().is_err());
+        assert!("02:fc:00:00:00:+1".parse::<Address>().is_err());
+        assert!("02:fc:00:00:00:gg".parse::<Address>().is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let addr = Address([0x02, 0xfc, 0, 0, 0, 0x01]);
+        let json = serde_json::to_string(&addr).unwrap();
+        assert_eq!(json, "\"02:fc:00:00:00:01\"");
+        assert_eq!(serde_json::from_str::<Address>(&json).unwrap(), addr);
+        assert!(serde_json::from_str::<Address>("\"02:fc\"").is_err());
+
+        for &ether_type in [EtherType::IPv4, EtherType::Unknown(0x88B5)].iter() {
+            let json = serde_json::to_string(&ether_type).unwrap();
+            assert_eq!(json, u16::from(ether_type).to_string());
+            assert_eq!(serde_json::from_str::<EtherType>(&json).unwrap(), ether_type);
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_padding() {
+        assert_eq!(Frame::<&[u8]>::frame_len_with_padding(4), 60);
+        assert_eq!(Frame::<&[u8]>::frame_len_with_padding(46), 60);
+        assert_eq!(Frame::<&[u8]>::frame_len_with_padding(100), 114);
+
+        let mut frame = Frame::new_unchecked(vec![0xAA; 14 + 4]);
+        frame.pad_to_minimum();
+        assert_eq!(frame.as_ref().len(), 60);
+        assert_eq!(&frame.payload()[..4], &[0xAA; 4]);
+        assert!(frame.payload()[4..].iter().all(|&b| b == 0));
+
+        let mut frame = Frame::builder(4);
+        frame.payload_mut()[..4].copy_from_slice(&[1, 2, 3, 4]);
+        frame.pad_to_minimum();
+        assert_eq!(frame.as_ref().len(), 60);
+
+        let mut frame = Frame::new_unchecked(vec![0; 100]);
+        frame.pad_to_minimum();
+        assert_eq!(frame.as_ref().len(), 100);
+    }
+
+    #[test]
+    fn test_new_checked_len() {
+        let bytes = [0; 64];
+        let frame = Frame::new_checked_len(&bytes[..], 42).unwrap();
+        assert_eq!(frame.as_ref().len(), 42);
+        assert_eq!(frame.payload().len(), 28);
+        assert_eq!(Frame::new_checked_len(&bytes[..], 65).err(), Some(Error::Truncated));
+        assert_eq!(Frame::new_checked_len(&bytes[..], 13).err(), Some(Error::Truncated));
+    }
+
+    #[test]
+    fn test_type_length() {
+        let mut bytes = [0; 60];
+        bytes[12..14].copy_from_slice(&[0x00, 46]);
+        let frame = Frame::new_checked(&bytes[..]).unwrap();
+        assert!(!frame.is_ethernet_ii());
+        assert_eq!(frame.length_field(), Some(46));
+
+        bytes[12..14].copy_from_slice(&[0x08, 0x00]);
+        let frame = Frame::new_checked(&bytes[..]).unwrap();
+        assert!(frame.is_ethernet_ii());
+        assert_eq!(frame.length_field(), None);
+        assert_eq!(frame.ether_type(), EtherType::IPv4);
+
+        // neither a length nor an EtherType
+        bytes[12..14].copy_from_slice(&1501u16.to_be_bytes());
+        let frame = Frame::new_checked(&bytes[..]).unwrap();
+        assert!(!frame.is_ethernet_ii());
+        assert_eq!(frame.length_field(), None);
+    }
+
+    #[test]
+    fn test_repr() {
+        let bytes = [
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02, 0xfc,
+            0x00, 0x00, 0x00, 0x01, 0x08, 0x06,
+        ];
+        let frame = Frame::new_checked(&bytes[..]).unwrap();
+        let repr = Repr::parse(&frame).unwrap();
+        assert_eq!(repr, Repr {
+            src_addr: Address([0x02, 0xfc, 0, 0, 0, 0x01]),
+            dst_addr: Address::BROADCAST,
+            ethertype: EtherType::ARP,
+        });
+        assert_eq!(repr.buffer_len(), 14);
+
+        let mut emitted = [0; 14];
+        repr.emit(&mut Frame::new_unchecked(&mut emitted[..]));
+        assert_eq!(emitted, bytes);
+
+        let frame = Frame::new_unchecked(&bytes[..13]);
+        assert_eq!(Repr::parse(&frame), Err(Error::Truncated));
+    }
+
+    #[test]
+    fn test_inner_payload() {
+        let mut untagged = vec![0; 14];
+        untagged[12..14].copy_from_slice(&[0x08, 0x00]);
+        untagged.extend_from_slice(b"payload");
+        let frame = Frame::new_checked(&untagged[..]).unwrap();
+        assert_eq!(frame.inner_ether_type(), EtherType::IPv4);
+        assert_eq!(frame.inner_payload(false), b"payload");
+
+        let mut tagged = vec![0; 14];
+        tagged[12..14].copy_from_slice(&[0x81, 0x00]);
+        tagged.extend_from_slice(&[0x00, 0x64, 0x86, 0xdd]);
+        tagged.extend_from_slice(b"payload");
+        let frame = Frame::new_checked(&tagged[..]).unwrap();
+        assert_eq!(frame.ether_type(), EtherType::VlanTagged);
+        assert_eq!(frame.inner_ether_type(), EtherType::IPv6);
+        assert_eq!(frame.inner_payload(false), b"payload");
+
+        tagged.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        let frame = Frame::new_checked(&tagged[..]).unwrap();
+        assert_eq!(frame.inner_payload(true), b"payload");
+
+        untagged.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        let frame = Frame::new_checked(&untagged[..]).unwrap();
+        assert_eq!(frame.inner_payload(true), b"payload");
+
+        // a tagged frame too short for its tag
+        let frame = Frame::new_checked(&tagged[..16]).unwrap();
+        assert_eq!(frame.inner_ether_type(), EtherType::VlanTagged);
+        assert!(frame.inner_payload(true).is_empty());
+    }
+
+    #[test]
+    fn test_frame_reader() {
+        let mut bytes = vec![];
+        for (i, &len) in [14usize, 20, 60].iter().enumerate() {
+            bytes.extend_from_slice(&(len as u16).to_be_bytes());
+            let mut frame = vec![0; len];
+            frame[..6].copy_from_slice(&[0xff; 6]);
+            frame[11] = i as u8;
+            frame[12..14].copy_from_slice(&[0x08, 0x06]);
+            bytes.extend_from_slice(&frame);
+        }
+
+        let mut reader = FrameReader::new(&bytes, FrameLength::Prefixed);
+        for (i, &len) in [14usize, 20, 60].iter().enumerate() {
+            let frame = reader.next().unwrap().unwrap();
+            assert_eq!(frame.as_ref().len(), len);
+            assert_eq!(frame.src_addr(), Address([0, 0, 0, 0, 0, i as u8]));
+            assert_eq!(frame.ether_type(), EtherType::ARP);
+        }
+        assert!(reader.next().is_none());
+
+        // a partial trailing frame
+        let mut reader = FrameReader::new(&bytes[..bytes.len() - 1], FrameLength::Prefixed);
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_ok());
+        assert_eq!(reader.next().unwrap().err(), Some(Error::Truncated));
+        assert!(reader.next().is_none());
+
+        let reader = FrameReader::new(&bytes[2..2 + 14], FrameLength::Fixed(14));
+        assert_eq!(reader.count(), 1);
+        let mut reader = FrameReader::new(&bytes[2..2 + 20], FrameLength::Fixed(14));
+        assert!(reader.next().unwrap().is_ok());
+        assert_eq!(reader.next().unwrap().err(), Some(Error::Truncated));
+
+        let mut reader = FrameReader::new(&bytes, FrameLength::Fixed(0));
+        assert_eq!(reader.next().unwrap().err(), Some(Error::Truncated));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_address_try_from_bytes() {
+        use core::convert::TryFrom;
+
+        let bytes = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+        assert_eq!(Address::try_from_bytes(&bytes), Ok(Address(bytes)));
+        assert_eq!(Address::try_from(&bytes[..]), Ok(Address(bytes)));
+        assert_eq!(Address::try_from_bytes(&bytes[..5]), Err(Error::Malformed));
+        assert_eq!(Address::try_from(&[0; 7][..]), Err(Error::Malformed));
+    }
+
+    #[test]
+    fn test_runt() {
+        let bytes = [0u8; 40];
+        let frame = Frame::new_checked(&bytes[..]).unwrap();
+        assert!(frame.is_runt());
+        assert_eq!(Frame::new_checked_strict(&bytes[..]).err(), Some(Error::Truncated));
+        assert_eq!(Frame::new_checked_strict(&bytes[..10]).err(), Some(Error::Truncated));
+
+        let bytes = [0u8; MIN_FRAME_LEN];
+        let frame = Frame::new_checked_strict(&bytes[..]).unwrap();
+        assert!(!frame.is_runt());
+        assert_eq!(frame.payload().len(), MIN_PAYLOAD_LEN);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_into_owned() {
+        let mut bytes = vec![0; 14 + 4];
+        let mut frame = Frame::new_unchecked(&mut bytes[..]);
+        frame.set_dst_addr(Address::BROADCAST);
+        frame.set_ether_type(EtherType::ARP);
+        frame.payload_mut().copy_from_slice(&[1, 2, 3, 4]);
+
+        let frame = Frame::new_checked(&bytes[..]).unwrap().into_owned();
+        drop(bytes);
+        assert_eq!(frame.dst_addr(), Address::BROADCAST);
+        assert_eq!(frame.ether_type(), EtherType::ARP);
+        assert_eq!(frame.payload(), &[1, 2, 3, 4]);
+    }
+
+    // echo request 10.0.0.2 -> 10.0.0.1, ident 0x1234
+    static ICMP_FRAME: [u8; 50] = [
+        0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x02, 0x00,
+        0x00, 0x00, 0x00, 0x02, 0x08, 0x00, 0x45, 0x00,
+        0x00, 0x24, 0xbe, 0xef, 0x40, 0x00, 0x40, 0x01,
+        0x67, 0xe7, 0x0a, 0x00, 0x00, 0x02, 0x0a, 0x00,
+        0x00, 0x01, 0x08, 0x00, 0xd4, 0xb5, 0x12, 0x34,
+        0x00, 0x01, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46,
+        0x47, 0x48,
+    ];
+
+    #[test]
+    fn test_icmp_over_ipv4() {
+        let frame = Frame::new_checked(&ICMP_FRAME[..]).unwrap();
+        let packet = frame.icmp_over_ipv4().unwrap();
+        assert!(packet.verify_checksum());
+        assert_eq!(packet.echo_ident(), 0x1234);
+        assert_eq!(packet.data(), b"ABCDEFGH");
+
+        let mut bytes = ICMP_FRAME;
+        bytes[12..14].copy_from_slice(&[0x86, 0xdd]);
+        let frame = Frame::new_checked(&bytes[..]).unwrap();
+        assert_eq!(frame.icmp_over_ipv4().err(), Some(Error::Unrecognized));
+
+        // UDP rather than ICMP
+        let mut bytes = ICMP_FRAME;
+        bytes[23] = 0x11;
+        let frame = Frame::new_checked(&bytes[..]).unwrap();
+        assert_eq!(frame.icmp_over_ipv4().err(), Some(Error::Unrecognized));
+
+        // more fragments
+        let mut bytes = ICMP_FRAME;
+        bytes[20] = 0x20;
+        let frame = Frame::new_checked(&bytes[..]).unwrap();
+        assert_eq!(frame.icmp_over_ipv4().err(), Some(Error::Fragmented));
+
+        let frame = Frame::new_checked(&ICMP_FRAME[..40]).unwrap();
+        assert_eq!(frame.icmp_over_ipv4().err(), Some(Error::Truncated));
+    }
+
+    #[test]
+    fn test_total_len() {
+        let frame = Frame::new_checked(&ICMP_FRAME[..]).unwrap();
+        assert_eq!(frame.total_len(), ICMP_FRAME.len());
+
+        // the same frame in an oversized buffer
+        let mut bytes = [0xee; 100];
+        bytes[..ICMP_FRAME.len()].copy_from_slice(&ICMP_FRAME);
+        let frame = Frame::new_checked(&bytes[..]).unwrap();
+        assert_eq!(frame.total_len(), ICMP_FRAME.len());
+
+        // an 802.3 frame with 4 bytes of payload
+        let mut bytes = [0; 60];
+        bytes[12..14].copy_from_slice(&[0x00, 0x04]);
+        assert_eq!(Frame::new_checked(&bytes[..]).unwrap().total_len(), 18);
+
+        // unknown payload length
+        let mut bytes = [0; 60];
+        bytes[12..14].copy_from_slice(&[0x88, 0xb5]);
+        assert_eq!(Frame::new_checked(&bytes[..]).unwrap().total_len(), 60);
+    }
+
+    #[test]
+    fn test_hexdump() {
+        let frame = Frame::new_checked(&ICMP_FRAME[..]).unwrap();
+        let dump = frame.hexdump().to_string();
+        let lines: Vec<&str> = dump.lines().collect();
+        assert!(lines[0].starts_with("00000000  "));
+        assert!(lines[1].starts_with("00000010  "));
+        assert_eq!(lines.len(), ICMP_FRAME.len().div_ceil(16));
+
+        let bytes = [0; 128];
+        let frame = Frame::new_checked(&bytes[..]).unwrap();
+        assert_eq!(frame.hexdump().to_string().lines().count(), 8);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_diff() {
+        let mut bytes = [0; 60];
+        let mut frame = Frame::new_unchecked(&mut bytes[..]);
+        frame.set_dst_addr(Address::BROADCAST);
+        frame.set_src_addr(Address([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]));
+        frame.set_ether_type(EtherType::IPv4);
+
+        let mut other = bytes;
+        Frame::new_unchecked(&mut other[..]).set_ether_type(EtherType::ARP);
+
+        let frame = Frame::new_checked(&bytes[..]).unwrap();
+        let other = Frame::new_checked(&other[..]).unwrap();
+        assert!(frame.diff(&frame).is_empty());
+        let diffs = frame.diff(&other);
+        assert_eq!(diffs, vec![FieldDiff::EtherType(EtherType::IPv4, EtherType::ARP)]);
+        assert_eq!(diffs[0].to_string(), format!("ethertype: {} != {}", EtherType::IPv4, EtherType::ARP));
+    }
+}
@@ -0,0 +1,263 @@
+// 0                   1                   2                   3
+// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |C| |K|S| Reserved0       | Ver |         Protocol Type         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |      Checksum (optional)      |       Reserved1 (Optional)    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                         Key (optional)                        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                 Sequence Number (Optional)                    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+#![allow(unused)]
+use byteorder::{
+    ByteOrder,
+    NetworkEndian,
+};
+use crate::{
+    Result,
+    Error,
+};
+use crate::bytes;
+use super::ethernet::EtherType;
+use crate::checksum;
+
+mod field {
+    use crate::Field;
+
+    pub const FLAGS_VER: Field = 0..2;
+    pub const PROTOCOL:  Field = 2..4;
+
+    pub const FLG_CHECKSUM: u16 = 0x8000;
+    pub const FLG_KEY:      u16 = 0x2000;
+    pub const FLG_SEQUENCE: u16 = 0x1000;
+    pub const VERSION_MASK: u16 = 0x0007;
+}
+
+pub const MIN_HEADER_LEN: usize = field::PROTOCOL.end;
+
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T
+}
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    pub fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < MIN_HEADER_LEN || len < self.header_len() {
+            Err(Error::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    pub fn flags_version(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::FLAGS_VER))
+    }
+
+    pub fn checksum_present(&self) -> bool {
+        self.flags_version() & field::FLG_CHECKSUM != 0
+    }
+
+    pub fn key_present(&self) -> bool {
+        self.flags_version() & field::FLG_KEY != 0
+    }
+
+    pub fn sequence_present(&self) -> bool {
+        self.flags_version() & field::FLG_SEQUENCE != 0
+    }
+
+    pub fn version(&self) -> u8 {
+        (self.flags_version() & field::VERSION_MASK) as u8
+    }
+
+    pub fn protocol_type(&self) -> EtherType {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::PROTOCOL)).into()
+    }
+
+    // the optional fields follow each other in flag order
+    fn key_offset(&self) -> usize {
+        MIN_HEADER_LEN + if self.checksum_present() { 4 } else { 0 }
+    }
+
+    fn sequence_offset(&self) -> usize {
+        self.key_offset() + if self.key_present() { 4 } else { 0 }
+    }
+
+    pub fn header_len(&self) -> usize {
+        self.sequence_offset() + if self.sequence_present() { 4 } else { 0 }
+    }
+
+    pub fn checksum(&self) -> Option<u16> {
+        if !self.checksum_present() {
+            return None;
+        }
+        let data = self.buffer.as_ref();
+        Some(bytes::checked(bytes::get_u16(data, MIN_HEADER_LEN..MIN_HEADER_LEN + 2)))
+    }
+
+    pub fn key(&self) -> Option<u32> {
+        if !self.key_present() {
+            return None;
+        }
+        let offset = self.key_offset();
+        let data = self.buffer.as_ref();
+        Some(bytes::checked(bytes::get_u32(data, offset..offset + 4)))
+    }
+
+    pub fn sequence(&self) -> Option<u32> {
+        if !self.sequence_present() {
+            return None;
+        }
+        let offset = self.sequence_offset();
+        let data = self.buffer.as_ref();
+        Some(bytes::checked(bytes::get_u32(data, offset..offset + 4)))
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_slice(data, self.header_len()..))
+    }
+
+    // A packet without the checksum field always verifies.
+    pub fn verify_checksum(&self) -> bool {
+        if !self.checksum_present() {
+            return true;
+        }
+        let data = self.buffer.as_ref();
+        checksum::data(data) == !0
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    // Setting the flags moves the optional fields, so set them
+    // before filling in the fields themselves.
+    pub fn set_flags_version(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::FLAGS_VER], value);
+    }
+
+    pub fn set_protocol_type(&mut self, ether_type: EtherType) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::PROTOCOL], ether_type.into());
+    }
+
+    pub fn set_key(&mut self, key: u32) {
+        let offset = self.key_offset();
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[offset..offset + 4], key);
+    }
+
+    pub fn set_sequence(&mut self, sequence: u32) {
+        let offset = self.sequence_offset();
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[offset..offset + 4], sequence);
+    }
+
+    // Fill the checksum field, which must be present, over the
+    // header and payload.
+    pub fn fill_checksum(&mut self) {
+        let data = self.buffer.as_mut();
+        data[MIN_HEADER_LEN..MIN_HEADER_LEN + 4].copy_from_slice(&[0; 4]);
+        let checksum = !checksum::data(data);
+        NetworkEndian::write_u16(&mut data[MIN_HEADER_LEN..MIN_HEADER_LEN + 2], checksum);
+    }
+
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let header_len = self.header_len();
+        let data = self.buffer.as_mut();
+        &mut data[header_len..]
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Error;
+    use crate::protocol::ethernet::EtherType;
+    use crate::protocol::ip::Protocol;
+    use crate::protocol::ip::ipv4::{
+        Address as IPv4Address,
+        Packet as IPv4Packet,
+    };
+    use super::Packet;
+
+    // 192.0.2.1 -> 198.51.100.1, key 42, carrying
+    // 10.1.0.1 -> 10.2.0.1
+    static PACKET_BYTES: [u8; 48] = [
+        0x45, 0x00, 0x00, 0x30, 0x00, 0x00, 0x40, 0x00,
+        0x40, 0x2f, 0x4e, 0x69, 0xc0, 0x00, 0x02, 0x01,
+        0xc6, 0x33, 0x64, 0x01, 0x20, 0x00, 0x08, 0x00,
+        0x00, 0x00, 0x00, 0x2a, 0x45, 0x00, 0x00, 0x14,
+        0x00, 0x00, 0x40, 0x00, 0x40, 0xfd, 0x25, 0xe9,
+        0x0a, 0x01, 0x00, 0x01, 0x0a, 0x02, 0x00, 0x01,
+    ];
+
+    #[test]
+    fn test_deconstruct() {
+        let outer = IPv4Packet::new_checked(&PACKET_BYTES[..]).unwrap();
+        assert!(outer.verify_checksum());
+        assert_eq!(outer.protocol(), Protocol::Gre);
+
+        let packet = Packet::new_checked(outer.payload()).unwrap();
+        assert_eq!(packet.version(), 0);
+        assert!(!packet.checksum_present());
+        assert!(packet.key_present());
+        assert!(!packet.sequence_present());
+        assert_eq!(packet.protocol_type(), EtherType::IPv4);
+        assert_eq!(packet.header_len(), 8);
+        assert_eq!(packet.checksum(), None);
+        assert_eq!(packet.key(), Some(42));
+        assert_eq!(packet.sequence(), None);
+        assert!(packet.verify_checksum());
+
+        let inner = IPv4Packet::new_checked(packet.payload()).unwrap();
+        assert!(inner.verify_checksum());
+        assert_eq!(inner.src_addr(), IPv4Address([10, 1, 0, 1]));
+        assert_eq!(inner.dst_addr(), IPv4Address([10, 2, 0, 1]));
+    }
+
+    #[test]
+    fn test_optional_fields() {
+        let mut bytes = [0; 16 + 4];
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        packet.set_flags_version(0xB000);
+        packet.set_protocol_type(EtherType::IPv6);
+        packet.set_key(0xdeadbeef);
+        packet.set_sequence(7);
+        packet.payload_mut().copy_from_slice(b"data");
+        packet.fill_checksum();
+
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert_eq!(packet.header_len(), 16);
+        assert_eq!(packet.key(), Some(0xdeadbeef));
+        assert_eq!(packet.sequence(), Some(7));
+        assert!(packet.checksum().is_some());
+        assert!(packet.verify_checksum());
+        assert_eq!(packet.payload(), b"data");
+
+        assert_eq!(Packet::new_checked(&bytes[..15]).err(), Some(Error::Truncated));
+        assert_eq!(Packet::new_checked(&bytes[..3]).err(), Some(Error::Truncated));
+    }
+}
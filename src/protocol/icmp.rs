@@ -1,2 +1,2 @@
-mod icmpv4;
-mod icmpv6;
+pub mod icmpv4;
+pub mod icmpv6;
@@ -28,22 +28,47 @@ use crate::{
     Result,
     Error,
 };
+use crate::bytes;
 use crate::checksum;
+use crate::device::ChecksumCapabilities;
+use crate::protocol::ip::Protocol;
+use crate::protocol::ip::ipv4::{
+    Address as IPv4Address,
+    Packet as IPv4Packet,
+};
+use crate::protocol::ethernet::{
+    Address as MacAddress,
+    EtherType,
+    Frame,
+};
+#[cfg(feature = "alloc")]
+use alloc::{
+    vec,
+    vec::Vec,
+};
 
 // just...
 #[repr(u8)]
 pub enum Message {
-    EchoReply   = 0,
-    EchoRequest = 8,
-    Unsupported = 0xFF,
+    EchoReply      = 0,
+    DstUnreachable = 3,
+    Redirect       = 5,
+    EchoRequest    = 8,
+    TimeExceeded   = 11,
+    ParamProblem   = 12,
+    Unsupported    = 0xFF,
 }
 
 impl From<u8> for Message {
     fn from(val: u8) -> Self {
         match val {
-            0 => Self::EchoReply,
-            8 => Self::EchoRequest,
-            _ => Self::Unsupported
+            0  => Self::EchoReply,
+            3  => Self::DstUnreachable,
+            5  => Self::Redirect,
+            8  => Self::EchoRequest,
+            11 => Self::TimeExceeded,
+            12 => Self::ParamProblem,
+            _  => Self::Unsupported
         }
     }
 }
@@ -52,7 +77,11 @@ impl From<Message> for u8 {
     fn from(msg: Message) -> Self {
         match msg {
             Message::EchoReply => 0,
+            Message::DstUnreachable => 3,
+            Message::Redirect => 5,
             Message::EchoRequest => 8,
+            Message::TimeExceeded => 11,
+            Message::ParamProblem => 12,
             Message::Unsupported => 0xFF,
         }
     }
@@ -69,16 +98,22 @@ mod field {
     pub const ECHO_IDENT: Field = 4..6;
     pub const ECHO_SEQNO: Field = 6..8;
 
+    pub const NEXT_MTU: Field = 6..8;
+
+    pub const GATEWAY: Field = 4..8;
+
     pub const HEADER_END: usize = 8;
 }
 
 pub struct Packet<T: AsRef<[u8]>> {
-    buffer: T
+    buffer: T,
+    // ICMP has no length field; see set_message_len
+    message_len: Option<usize>,
 }
 
 impl<T: AsRef<[u8]>> Packet<T> {
     pub fn new_unchecked(buffer: T) -> Packet<T> {
-        Packet { buffer }
+        Packet { buffer, message_len: None }
     }
 
     pub fn new_checked(buffer: T) -> Result<Packet<T>> {
@@ -88,7 +123,7 @@ impl<T: AsRef<[u8]>> Packet<T> {
     }
 
     pub fn check_len(&self) -> Result<()> {
-        let len = self.buffer.as_ref().len();
+        let len = self.message_len();
         if len < field::HEADER_END {
             Err(Error::Truncated)
         } else {
@@ -100,42 +135,101 @@ impl<T: AsRef<[u8]>> Packet<T> {
         self.buffer
     }
 
+    // The length of the message, header and data. It is the whole buffer
+    // unless set from the payload length of the enclosing IP header, e.g.
+    // for a message built in an oversized buffer.
+    pub fn message_len(&self) -> usize {
+        let len = self.buffer.as_ref().len();
+        self.message_len.map_or(len, |message_len| message_len.min(len))
+    }
+
+    // A length shorter than the header is refused and leaves the packet
+    // as it was, so that data() stays in bounds.
+    pub fn set_message_len(&mut self, len: usize) -> Result<()> {
+        let previous = self.message_len.replace(len);
+        let result = self.check_len();
+        if result.is_err() {
+            self.message_len = previous;
+        }
+        result
+    }
+
     pub fn msg_type(&self) -> Message {
         let data = self.buffer.as_ref();
-        data[field::TYPE].into()
+        bytes::checked(bytes::get_u8(data, field::TYPE)).into()
     }
 
     pub fn msg_code(&self) -> u8 {
         let data = self.buffer.as_ref();
-        data[field::CODE]
+        bytes::checked(bytes::get_u8(data, field::CODE))
     }
 
     pub fn checksum(&self) -> u16 {
         let data = self.buffer.as_ref();
-        NetworkEndian::read_u16(&data[field::CHECKSUM])
+        bytes::checked(bytes::get_u16(data, field::CHECKSUM))
     }
 
     pub fn echo_ident(&self) -> u16 {
         let data = self.buffer.as_ref();
-        NetworkEndian::read_u16(&data[field::ECHO_IDENT])
+        bytes::checked(bytes::get_u16(data, field::ECHO_IDENT))
     }
 
     pub fn echo_seq_no(&self) -> u16 {
         let data = self.buffer.as_ref();
-        NetworkEndian::read_u16(&data[field::ECHO_SEQNO])
+        bytes::checked(bytes::get_u16(data, field::ECHO_SEQNO))
+    }
+
+    // The next-hop MTU of a "fragmentation needed" message (RFC 1191).
+    pub fn next_hop_mtu(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::NEXT_MTU))
+    }
+
+    // The gateway a Redirect message points to (RFC 792). The field is
+    // unused in other messages; see redirect_gateway_checked.
+    pub fn redirect_gateway(&self) -> IPv4Address {
+        let data = self.buffer.as_ref();
+        IPv4Address::from_bytes(bytes::checked(bytes::get_slice(data, field::GATEWAY)))
+    }
+
+    pub fn redirect_gateway_checked(&self) -> Result<IPv4Address> {
+        if !matches!(self.msg_type(), Message::Redirect) {
+            return Err(Error::Illegal);
+        }
+        let data = self.buffer.as_ref();
+        bytes::get_slice(data, field::GATEWAY).map(IPv4Address::from_bytes)
     }
 
     pub fn header_len(&self) -> usize {
         match self.msg_type() {
             Message::EchoRequest => field::ECHO_SEQNO.end,
             Message::EchoReply   => field::ECHO_SEQNO.end,
+            // error messages carry a 4-byte unused (or gateway) field,
+            // followed by the original datagram
+            Message::DstUnreachable => field::UNUSED.end,
+            Message::Redirect       => field::UNUSED.end,
+            Message::TimeExceeded   => field::UNUSED.end,
+            Message::ParamProblem   => field::UNUSED.end,
             _ => field::UNUSED.end
         }
     }
 
+    // Internet Header + 64 bits of Original Data Datagram
+    // for the error messages, or the echo data otherwise.
+    pub fn data(&self) -> &[u8] {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_slice(data, self.header_len()..self.message_len()))
+    }
+
+    // ICMPv4 has no pseudo-header, the checksum covers the message only;
+    // compare icmpv6::Packet::verify_checksum.
     pub fn verify_checksum(&self) -> bool {
         let data = self.buffer.as_ref();
-        checksum::data(data) == !0
+        checksum::data(bytes::checked(bytes::get_slice(data, ..self.message_len()))) == !0
+    }
+
+    pub fn verify_checksum_with(&self, caps: &ChecksumCapabilities) -> bool {
+        !caps.icmp.rx() || self.verify_checksum()
     }
 }
 
@@ -147,7 +241,7 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
 
     pub fn set_msg_code(&mut self, code: u8) {
         let data = self.buffer.as_mut();
-        data[field::CODE] == code;
+        data[field::CODE] = code;
     }
 
     pub fn set_checksum(&mut self, checksum: u16) {
@@ -165,14 +259,56 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
         NetworkEndian::write_u16(&mut data[field::ECHO_SEQNO], number)
     }
 
+    pub fn set_next_hop_mtu(&mut self, mtu: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::NEXT_MTU], mtu)
+    }
+
+    pub fn set_redirect_gateway(&mut self, addr: IPv4Address) {
+        let data = self.buffer.as_mut();
+        data[field::GATEWAY].copy_from_slice(addr.as_bytes());
+    }
+
     pub fn fill_checksum(&mut self) {
         self.set_checksum(0);
         let checksum = {
             let data = self.buffer.as_ref();
-            !checksum::data(data)
+            !checksum::data(&data[..self.message_len()])
         };
         self.set_checksum(checksum)
     }
+
+    pub fn fill_checksum_with(&mut self, caps: &ChecksumCapabilities) {
+        if caps.icmp.tx() {
+            self.fill_checksum();
+        }
+    }
+
+    // Turn an echo request into its reply in place, patching the
+    // checksum for the changed type instead of recomputing it.
+    pub fn make_echo_reply(&mut self) -> Result<()> {
+        if !matches!(self.msg_type(), Message::EchoRequest) {
+            return Err(Error::Illegal);
+        }
+        let old_word = {
+            let data = self.buffer.as_ref();
+            NetworkEndian::read_u16(&data[field::TYPE..field::CODE + 1])
+        };
+        self.set_msg_type(Message::EchoReply);
+        let new_word = {
+            let data = self.buffer.as_ref();
+            NetworkEndian::read_u16(&data[field::TYPE..field::CODE + 1])
+        };
+        let checksum = checksum::update(self.checksum(), old_word, new_word);
+        self.set_checksum(checksum);
+        Ok(())
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        let range = self.header_len()..self.message_len();
+        let data = self.buffer.as_mut();
+        &mut data[range]
+    }
 }
 
 impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
@@ -181,12 +317,342 @@ impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
     }
 }
 
-impl<'a, T: AsRef<[u8]> + AsMut<[u8]> + ?Sized> Packet<&'a mut T> {
-    pub fn data_mut(&mut self) -> &mut [u8] {
-        let range = self.header_len()..;
-        let data = self.buffer.as_mut();
-        &mut data[range]
+#[cfg(feature = "alloc")]
+impl<T: AsRef<[u8]>> Packet<T> {
+    // Copy the message into an owned buffer, up to message_len.
+    pub fn into_owned(self) -> Packet<Vec<u8>> {
+        Packet::new_unchecked(self.buffer.as_ref()[..self.message_len()].to_vec())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Packet<Vec<u8>> {
+    // Allocate a packet with room for the 8-byte header and the data.
+    pub fn builder(data_len: usize) -> Packet<Vec<u8>> {
+        Packet::new_unchecked(vec![0; field::HEADER_END + data_len])
+    }
+}
+
+// A parsed ICMPv4 message; error messages keep the quoted datagram
+// as their data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repr<'a> {
+    EchoRequest {
+        ident: u16,
+        seq_no: u16,
+        data: &'a [u8],
+    },
+    EchoReply {
+        ident: u16,
+        seq_no: u16,
+        data: &'a [u8],
+    },
+    DstUnreachable {
+        code: u8,
+        data: &'a [u8],
+    },
+    TimeExceeded {
+        code: u8,
+        data: &'a [u8],
+    },
+}
+
+impl<'a> Repr<'a> {
+    pub fn parse(packet: &'a Packet<&[u8]>) -> Result<Repr<'a>> {
+        packet.check_len()?;
+        if !packet.verify_checksum() {
+            return Err(Error::Checksum);
+        }
+        let (code, data) = (packet.msg_code(), packet.data());
+        match packet.msg_type() {
+            Message::EchoRequest if code == 0 => Ok(Repr::EchoRequest {
+                ident: packet.echo_ident(),
+                seq_no: packet.echo_seq_no(),
+                data,
+            }),
+            Message::EchoReply if code == 0 => Ok(Repr::EchoReply {
+                ident: packet.echo_ident(),
+                seq_no: packet.echo_seq_no(),
+                data,
+            }),
+            Message::DstUnreachable => Ok(Repr::DstUnreachable { code, data }),
+            Message::TimeExceeded => Ok(Repr::TimeExceeded { code, data }),
+            _ => Err(Error::Unrecognized),
+        }
+    }
+
+    pub fn buffer_len(&self) -> usize {
+        match *self {
+            Repr::EchoRequest { data, .. } |
+            Repr::EchoReply { data, .. } |
+            Repr::DstUnreachable { data, .. } |
+            Repr::TimeExceeded { data, .. } => field::HEADER_END + data.len(),
+        }
+    }
+
+    // Write the message into a packet of exactly buffer_len() bytes
+    // and fill the checksum.
+    pub fn emit<T: AsRef<[u8]> + AsMut<[u8]>>(&self, packet: &mut Packet<T>) {
+        let (msg_type, code, echo, data) = match *self {
+            Repr::EchoRequest { ident, seq_no, data } =>
+                (Message::EchoRequest, 0, Some((ident, seq_no)), data),
+            Repr::EchoReply { ident, seq_no, data } =>
+                (Message::EchoReply, 0, Some((ident, seq_no)), data),
+            Repr::DstUnreachable { code, data } =>
+                (Message::DstUnreachable, code, None, data),
+            Repr::TimeExceeded { code, data } =>
+                (Message::TimeExceeded, code, None, data),
+        };
+        packet.set_msg_type(msg_type);
+        packet.set_msg_code(code);
+        match echo {
+            Some((ident, seq_no)) => {
+                packet.set_echo_ident(ident);
+                packet.set_echo_seq_no(seq_no);
+            }
+            None => {
+                let data = packet.buffer.as_mut();
+                data[field::UNUSED].copy_from_slice(&[0; 4]);
+            }
+        }
+        packet.data_mut().copy_from_slice(data);
+        packet.fill_checksum();
+    }
+}
+
+// The fields shared by echo requests and replies, for callers that only
+// deal with pings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EchoRepr<'a> {
+    pub ident: u16,
+    pub seq_no: u16,
+    pub data: &'a [u8],
+}
+
+impl<'a> EchoRepr<'a> {
+    // Error::Malformed for anything but an echo request or reply.
+    pub fn parse(packet: &'a Packet<&[u8]>) -> Result<EchoRepr<'a>> {
+        match Repr::parse(packet) {
+            Ok(Repr::EchoRequest { ident, seq_no, data }) |
+            Ok(Repr::EchoReply { ident, seq_no, data }) => Ok(EchoRepr { ident, seq_no, data }),
+            Ok(_) | Err(Error::Unrecognized) => Err(Error::Malformed),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn request(&self) -> Repr<'a> {
+        Repr::EchoRequest { ident: self.ident, seq_no: self.seq_no, data: self.data }
+    }
+
+    pub fn reply(&self) -> Repr<'a> {
+        Repr::EchoReply { ident: self.ident, seq_no: self.seq_no, data: self.data }
+    }
+
+    pub fn buffer_len(&self) -> usize {
+        field::HEADER_END + self.data.len()
+    }
+
+    // Like Repr::emit, with msg_type choosing between request and reply;
+    // Error::Illegal for any other type.
+    pub fn emit<T: AsRef<[u8]> + AsMut<[u8]>>(
+        &self,
+        msg_type: Message,
+        packet: &mut Packet<T>,
+    ) -> Result<()> {
+        match msg_type {
+            Message::EchoRequest => self.request().emit(packet),
+            Message::EchoReply => self.reply().emit(packet),
+            _ => return Err(Error::Illegal),
+        }
+        Ok(())
+    }
+}
+
+// Writes an echo request into a caller-provided buffer:
+//
+//     let len = EchoBuilder::new().ident(1).seq_no(2).payload(b"ping")
+//         .build_into(&mut buffer)?;
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EchoBuilder<'a> {
+    ident: u16,
+    seq_no: u16,
+    payload: &'a [u8],
+}
+
+impl<'a> EchoBuilder<'a> {
+    pub fn new() -> EchoBuilder<'a> {
+        EchoBuilder::default()
+    }
+
+    pub fn ident(mut self, ident: u16) -> Self {
+        self.ident = ident;
+        self
+    }
+
+    pub fn seq_no(mut self, seq_no: u16) -> Self {
+        self.seq_no = seq_no;
+        self
+    }
+
+    pub fn payload(mut self, payload: &'a [u8]) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    // Write the message and return its length, or Error::Exhausted
+    // if it does not fit.
+    pub fn build_into(&self, buffer: &mut [u8]) -> Result<usize> {
+        let len = field::ECHO_SEQNO.end + self.payload.len();
+        if buffer.len() < len {
+            return Err(Error::Exhausted);
+        }
+        let mut packet = Packet::new_unchecked(&mut buffer[..len]);
+        packet.set_msg_type(Message::EchoRequest);
+        packet.set_msg_code(0);
+        packet.set_echo_ident(self.ident);
+        packet.set_echo_seq_no(self.seq_no);
+        packet.data_mut().copy_from_slice(self.payload);
+        packet.fill_checksum();
+        Ok(len)
+    }
+}
+
+// Build a complete IPv4 datagram carrying an echo request, with
+// all lengths and checksums filled in. Returns Error::Exhausted if the
+// payload does not fit in one datagram.
+#[cfg(feature = "alloc")]
+pub fn build_echo_request(
+    src: IPv4Address,
+    dst: IPv4Address,
+    ident: u16,
+    seq: u16,
+    payload: &[u8]
+) -> Result<Vec<u8>> {
+    let mut packet = Packet::builder(payload.len());
+    packet.set_msg_type(Message::EchoRequest);
+    packet.set_msg_code(0);
+    packet.set_echo_ident(ident);
+    packet.set_echo_seq_no(seq);
+    packet.data_mut().copy_from_slice(payload);
+    packet.fill_checksum();
+
+    let mut ipv4_packet = IPv4Packet::builder(packet.as_ref().len())?;
+    ipv4_packet.set_hop_limit(64);
+    ipv4_packet.set_protocol(Protocol::ICMP);
+    ipv4_packet.set_src_addr(src);
+    ipv4_packet.set_dst_addr(dst);
+    ipv4_packet.payload_mut().copy_from_slice(packet.as_ref());
+    ipv4_packet.fill_checksum();
+    Ok(ipv4_packet.into_inner())
+}
+
+// Write a complete Ethernet frame carrying an echo request into `buf`,
+// padded to the minimum frame length, and return its length or
+// Error::Exhausted if it does not fit. Like build_echo_request, but
+// without allocating.
+#[allow(clippy::too_many_arguments)]
+pub fn build_echo_request_into(
+    buf: &mut [u8],
+    src_mac: MacAddress,
+    dst_mac: MacAddress,
+    src_ip: IPv4Address,
+    dst_ip: IPv4Address,
+    ident: u16,
+    seq: u16,
+    payload: &[u8]
+) -> Result<usize> {
+    const IPV4_HEADER_LEN: usize = 20;
+
+    let ip_len = IPV4_HEADER_LEN + field::ECHO_SEQNO.end + payload.len();
+    let frame_len = Frame::<&[u8]>::frame_len_with_padding(ip_len);
+    if buf.len() < frame_len || ip_len > u16::MAX as usize {
+        return Err(Error::Exhausted);
+    }
+    let buf = &mut buf[..frame_len];
+    for byte in buf.iter_mut() {
+        *byte = 0;
+    }
+
+    let mut frame = Frame::new_unchecked(&mut buf[..]);
+    frame.set_dst_addr(dst_mac);
+    frame.set_src_addr(src_mac);
+    frame.set_ether_type(EtherType::IPv4);
+
+    let mut ipv4_packet = IPv4Packet::new_unchecked(&mut frame.payload_mut()[..ip_len]);
+    ipv4_packet.set_version(4);
+    ipv4_packet.set_header_len(IPV4_HEADER_LEN as u8);
+    ipv4_packet.set_total_len(ip_len as u16);
+    ipv4_packet.set_hop_limit(64);
+    ipv4_packet.set_protocol(Protocol::ICMP);
+    ipv4_packet.set_src_addr(src_ip);
+    ipv4_packet.set_dst_addr(dst_ip);
+    EchoBuilder::new()
+        .ident(ident)
+        .seq_no(seq)
+        .payload(payload)
+        .build_into(ipv4_packet.payload_mut())?;
+    ipv4_packet.fill_checksum();
+    Ok(frame_len)
+}
+
+// Build a Destination Unreachable message quoting the original
+// IP header and the first 64 bits of its payload (RFC 792).
+#[cfg(feature = "alloc")]
+pub fn build_dest_unreachable(code: u8, original: &IPv4Packet<&[u8]>) -> Vec<u8> {
+    build_error(Message::DstUnreachable, code, original)
+}
+
+#[cfg(feature = "alloc")]
+pub fn build_time_exceeded(code: u8, original: &IPv4Packet<&[u8]>) -> Vec<u8> {
+    build_error(Message::TimeExceeded, code, original)
+}
+
+// Build a Destination Unreachable "fragmentation needed and DF set"
+// message carrying the MTU of the next hop, for path MTU discovery.
+#[cfg(feature = "alloc")]
+pub fn build_frag_needed(original: &IPv4Packet<&[u8]>, next_mtu: u16) -> Vec<u8> {
+    let mut packet = Packet::new_unchecked(build_error(Message::DstUnreachable, 4, original));
+    packet.set_next_hop_mtu(next_mtu);
+    packet.fill_checksum();
+    packet.into_inner()
+}
+
+#[cfg(feature = "alloc")]
+fn build_error(msg_type: Message, code: u8, original: &IPv4Packet<&[u8]>) -> Vec<u8> {
+    // the header is at most 60 bytes long, so quoting can't run out of room
+    let mut quoted = [0; 60 + 8];
+    let len = original.quote(&mut quoted).unwrap_or(0);
+
+    let mut packet = Packet::builder(len);
+    packet.set_msg_type(msg_type);
+    packet.set_msg_code(code);
+    packet.data_mut().copy_from_slice(&quoted[..len]);
+    packet.fill_checksum();
+    packet.into_inner()
+}
+
+// Parse an IPv4 datagram carrying an echo reply into its
+// (ident, seq, payload).
+pub fn parse_echo_reply(datagram: &[u8]) -> Result<(u16, u16, &[u8])> {
+    let ipv4_packet = IPv4Packet::new_checked(datagram)?;
+    if !ipv4_packet.verify_checksum() {
+        return Err(Error::Checksum);
+    }
+    if ipv4_packet.protocol() != Protocol::ICMP {
+        return Err(Error::Unrecognized);
+    }
+    let header_len = ipv4_packet.header_len() as usize;
+    let total_len = ipv4_packet.total_len() as usize;
+    let packet = Packet::new_checked(&datagram[header_len..total_len])?;
+    if !packet.verify_checksum() {
+        return Err(Error::Checksum);
+    }
+    if !matches!(packet.msg_type(), Message::EchoReply) {
+        return Err(Error::Unrecognized);
     }
+    let (ident, seq) = (packet.echo_ident(), packet.echo_seq_no());
+    let data = packet.into_inner();
+    Ok((ident, seq, &data[field::ECHO_SEQNO.end..]))
 }
 
 #[cfg(test)]
@@ -246,4 +712,467 @@ mod test {
 
         send_raw_socket(frame.as_ref());
     }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_builders() {
+        let mut packet = ICMPPacket::builder(4);
+        packet.set_msg_type(Message::EchoRequest);
+        packet.set_msg_code(0);
+        packet.set_echo_ident(0x1234);
+        packet.set_echo_seq_no(0xabcd);
+        packet.data_mut().copy_from_slice(b"ABCD");
+        packet.fill_checksum();
+
+        let mut ipv4_packet = IPv4Packet::builder(packet.as_ref().len()).unwrap();
+        ipv4_packet.set_hop_limit(0x40);
+        ipv4_packet.set_protocol(IPv4Protocal::ICMP);
+        ipv4_packet.set_src_addr(IPv4Address([10, 10, 10, 2]));
+        ipv4_packet.set_dst_addr(IPv4Address([10, 10, 10, 1]));
+        ipv4_packet.payload_mut().copy_from_slice(packet.as_ref());
+        ipv4_packet.fill_checksum();
+        assert_eq!(ipv4_packet.version(), 4);
+        assert_eq!(ipv4_packet.header_len(), 20);
+        assert_eq!(ipv4_packet.total_len(), 20 + 12);
+        assert!(ipv4_packet.verify_checksum());
+
+        let mut frame = Frame::builder(ipv4_packet.as_ref().len());
+        frame.set_dst_addr(ethernet::Address::BROADCAST);
+        frame.set_src_addr(ethernet::Address([0x02, 0, 0, 0, 0, 0x01]));
+        frame.set_ether_type(EtherType::IPv4);
+        let ip_len = ipv4_packet.as_ref().len();
+        frame.payload_mut()[..ip_len].copy_from_slice(ipv4_packet.as_ref());
+        // short frames are padded to the 60-byte minimum
+        assert_eq!(frame.as_ref().len(), 60);
+
+        let frame = Frame::new_checked(frame.into_inner()).unwrap();
+        let ipv4_packet = IPv4Packet::new_checked(frame.payload()).unwrap();
+        assert_eq!(ipv4_packet.total_len() as usize, 20 + 12);
+        let packet = ICMPPacket::new_checked(&frame.payload()[20..]).unwrap();
+        assert!(packet.verify_checksum());
+        assert_eq!(packet.echo_ident(), 0x1234);
+    }
+
+    #[test]
+    fn test_time_exceeded() {
+        // original IPv4 header + 64 bits of its payload
+        let original = [
+            0x45, 0x00, 0x00, 0x20, 0x00, 0x00, 0x40, 0x00,
+            0x01, 0x11, 0x00, 0x00, 0x0a, 0x0a, 0x0a, 0x02,
+            0x0a, 0x0a, 0x0a, 0x01, 0x30, 0x39, 0x00, 0x35,
+            0x00, 0x0c, 0x00, 0x00,
+        ];
+
+        let mut bytes = vec![0; 8 + original.len()];
+        let mut packet = ICMPPacket::new_unchecked(&mut bytes);
+        packet.set_msg_type(Message::TimeExceeded);
+        packet.set_msg_code(0);
+        packet.data_mut().copy_from_slice(&original);
+        packet.fill_checksum();
+
+        assert_eq!(packet.as_ref()[0], 11);
+        assert_eq!(packet.msg_code(), 0);
+        assert_eq!(packet.header_len(), 8);
+        assert_eq!(packet.data(), &original[..]);
+        assert!(packet.verify_checksum());
+
+        packet.set_msg_code(1);
+        assert!(!packet.verify_checksum());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_echo_helpers() {
+        use crate::Error;
+        use super::{
+            build_echo_request,
+            parse_echo_reply,
+        };
+
+        let src = IPv4Address([10, 10, 10, 2]);
+        let dst = IPv4Address([10, 10, 10, 1]);
+        let mut datagram = build_echo_request(src, dst, 0x1234, 7, b"ping").unwrap();
+        assert_eq!(datagram.len(), 20 + 8 + 4);
+        let ipv4_packet = IPv4Packet::new_checked(&datagram[..]).unwrap();
+        assert!(ipv4_packet.verify_checksum());
+        assert_eq!(ipv4_packet.src_addr(), src);
+        assert_eq!(ipv4_packet.dst_addr(), dst);
+
+        // not a reply yet
+        assert_eq!(parse_echo_reply(&datagram).err(), Some(Error::Unrecognized));
+
+        let mut packet = ICMPPacket::new_unchecked(&mut datagram[20..]);
+        packet.set_msg_type(Message::EchoReply);
+        packet.fill_checksum();
+        assert_eq!(parse_echo_reply(&datagram), Ok((0x1234, 7, &b"ping"[..])));
+
+        datagram[30] ^= 0xff;
+        assert_eq!(parse_echo_reply(&datagram).err(), Some(Error::Checksum));
+        assert_eq!(parse_echo_reply(&datagram[..19]).err(), Some(Error::Truncated));
+
+        // the echo message must fit in one datagram
+        let payload = vec![0; 65535 - 20 - 8];
+        assert!(build_echo_request(src, dst, 0x1234, 8, &payload).is_ok());
+        let payload = vec![0; 65535 - 20 - 7];
+        assert_eq!(build_echo_request(src, dst, 0x1234, 8, &payload), Err(Error::Exhausted));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_dest_unreachable() {
+        use super::{
+            build_echo_request,
+            build_dest_unreachable,
+            build_time_exceeded,
+        };
+
+        let datagram = build_echo_request(
+            IPv4Address([10, 10, 10, 2]),
+            IPv4Address([10, 10, 10, 1]),
+            0x1234, 1, &[0x5a; 20],
+        ).unwrap();
+        let original = IPv4Packet::new_checked(&datagram[..]).unwrap();
+
+        let bytes = build_dest_unreachable(3, &original);
+        assert_eq!(bytes.len(), 8 + 20 + 8);
+        let packet = ICMPPacket::new_checked(&bytes[..]).unwrap();
+        assert!(matches!(packet.msg_type(), Message::DstUnreachable));
+        assert_eq!(packet.msg_code(), 3);
+        assert_eq!(&bytes[4..8], &[0; 4]);
+        assert_eq!(packet.data(), &datagram[..28]);
+        assert!(packet.verify_checksum());
+
+        let bytes = build_time_exceeded(0, &original);
+        let packet = ICMPPacket::new_checked(&bytes[..]).unwrap();
+        assert!(matches!(packet.msg_type(), Message::TimeExceeded));
+        assert!(packet.verify_checksum());
+
+        // a datagram with only 64 bits of payload is quoted whole
+        let datagram = build_echo_request(
+            IPv4Address([10, 10, 10, 2]),
+            IPv4Address([10, 10, 10, 1]),
+            0x1234, 1, &[],
+        ).unwrap();
+        let original = IPv4Packet::new_checked(&datagram[..]).unwrap();
+        let bytes = build_dest_unreachable(1, &original);
+        assert_eq!(bytes.len(), 8 + 20 + 8);
+        assert_eq!(&bytes[8..], &datagram[..]);
+    }
+
+    #[test]
+    fn test_make_echo_reply() {
+        use crate::Error;
+
+        let mut bytes = [0; 12];
+        let mut packet = ICMPPacket::new_unchecked(&mut bytes[..]);
+        packet.set_msg_type(Message::EchoRequest);
+        packet.set_msg_code(0);
+        packet.set_echo_ident(0x1234);
+        packet.set_echo_seq_no(0xabcd);
+        packet.data_mut().copy_from_slice(b"ABCD");
+        packet.fill_checksum();
+
+        packet.make_echo_reply().unwrap();
+        assert!(matches!(packet.msg_type(), Message::EchoReply));
+        assert!(packet.verify_checksum());
+        assert_eq!(packet.echo_ident(), 0x1234);
+        assert_eq!(packet.echo_seq_no(), 0xabcd);
+        assert_eq!(packet.data(), b"ABCD");
+
+        let patched = packet.checksum();
+        packet.fill_checksum();
+        assert_eq!(packet.checksum(), patched);
+
+        assert_eq!(packet.make_echo_reply(), Err(Error::Illegal));
+    }
+
+    #[test]
+    fn test_echo_builder() {
+        use crate::Error;
+        use super::EchoBuilder;
+
+        let mut buffer = [0xff; 64];
+        let len = EchoBuilder::new()
+            .ident(0x1234)
+            .seq_no(0xabcd)
+            .payload(b"ABCD")
+            .build_into(&mut buffer)
+            .unwrap();
+        assert_eq!(len, 12);
+
+        let packet = ICMPPacket::new_checked(&buffer[..len]).unwrap();
+        assert!(matches!(packet.msg_type(), Message::EchoRequest));
+        assert_eq!(packet.msg_code(), 0);
+        assert_eq!(packet.echo_ident(), 0x1234);
+        assert_eq!(packet.echo_seq_no(), 0xabcd);
+        assert_eq!(packet.data(), b"ABCD");
+        assert!(packet.verify_checksum());
+
+        let builder = EchoBuilder::new().payload(b"ABCD");
+        assert_eq!(builder.build_into(&mut buffer[..11]), Err(Error::Exhausted));
+        assert_eq!(builder.build_into(&mut buffer[..12]), Ok(12));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_frag_needed() {
+        use crate::protocol::ip::ipv4::needs_fragmentation;
+        use super::{
+            build_echo_request,
+            build_frag_needed,
+        };
+
+        let mut datagram = build_echo_request(
+            IPv4Address([10, 10, 10, 2]),
+            IPv4Address([10, 10, 10, 1]),
+            0x1234, 1, &[0; 1000],
+        ).unwrap();
+        let original = IPv4Packet::new_checked(&datagram[..]).unwrap();
+        assert!(!needs_fragmentation(&original, 576));
+
+        let mut packet = IPv4Packet::new_unchecked(&mut datagram[..]);
+        packet.set_dont_frag(true);
+        packet.fill_checksum();
+        let original = IPv4Packet::new_checked(&datagram[..]).unwrap();
+        assert!(needs_fragmentation(&original, 576));
+        assert!(!needs_fragmentation(&original, 1500));
+
+        let bytes = build_frag_needed(&original, 576);
+        let packet = ICMPPacket::new_checked(&bytes[..]).unwrap();
+        assert!(matches!(packet.msg_type(), Message::DstUnreachable));
+        assert_eq!(packet.msg_code(), 4);
+        assert_eq!(packet.next_hop_mtu(), 576);
+        assert_eq!(&bytes[4..6], &[0, 0]);
+        assert_eq!(packet.data(), &datagram[..28]);
+        assert!(packet.verify_checksum());
+    }
+
+    #[test]
+    fn test_repr() {
+        use crate::Error;
+        use super::Repr;
+
+        let quoted = [
+            0x45, 0x00, 0x00, 0x20, 0x00, 0x00, 0x40, 0x00,
+            0x01, 0x11, 0x00, 0x00, 0x0a, 0x0a, 0x0a, 0x02,
+            0x0a, 0x0a, 0x0a, 0x01, 0x30, 0x39, 0x00, 0x35,
+            0x00, 0x0c, 0x00, 0x00,
+        ];
+        let reprs = [
+            Repr::EchoRequest { ident: 0x1234, seq_no: 1, data: b"ABCD" },
+            Repr::EchoReply { ident: 0x1234, seq_no: 2, data: b"" },
+            Repr::DstUnreachable { code: 3, data: &quoted },
+            Repr::TimeExceeded { code: 0, data: &quoted },
+        ];
+        for repr in reprs.iter() {
+            let mut bytes = vec![0xa5; repr.buffer_len()];
+            repr.emit(&mut ICMPPacket::new_unchecked(&mut bytes[..]));
+
+            let packet = ICMPPacket::new_checked(&bytes[..]).unwrap();
+            assert!(packet.verify_checksum());
+            let parsed = Repr::parse(&packet).unwrap();
+            assert_eq!(&parsed, repr);
+
+            let mut emitted = vec![0; parsed.buffer_len()];
+            parsed.emit(&mut ICMPPacket::new_unchecked(&mut emitted[..]));
+            assert_eq!(emitted, bytes);
+        }
+
+        let mut bytes = [0; 8];
+        reprs[1].emit(&mut ICMPPacket::new_unchecked(&mut bytes[..]));
+        bytes[7] ^= 1;
+        let packet = ICMPPacket::new_checked(&bytes[..]).unwrap();
+        assert_eq!(Repr::parse(&packet), Err(Error::Checksum));
+
+        let mut packet = ICMPPacket::new_unchecked(&mut bytes[..]);
+        packet.set_msg_type(Message::Redirect);
+        packet.fill_checksum();
+        let packet = ICMPPacket::new_checked(&bytes[..]).unwrap();
+        assert_eq!(Repr::parse(&packet), Err(Error::Unrecognized));
+    }
+
+    #[test]
+    fn test_echo_repr() {
+        use crate::Error;
+        use super::EchoRepr;
+
+        // echo request sent by Linux ping
+        let captured = [
+            0x08, 0x00, 0xb7, 0xa6, 0x0d, 0x64, 0x00, 0x01,
+            0x5c, 0x3e, 0x8a, 0x65, 0x00, 0x00, 0x00, 0x00,
+            0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17,
+        ];
+        let packet = ICMPPacket::new_checked(&captured[..]).unwrap();
+        let echo = EchoRepr::parse(&packet).unwrap();
+        assert_eq!(echo.ident, 0x0d64);
+        assert_eq!(echo.seq_no, 1);
+        assert_eq!(echo.data, &captured[8..]);
+
+        let mut bytes = vec![0; echo.buffer_len()];
+        echo.emit(Message::EchoRequest, &mut ICMPPacket::new_unchecked(&mut bytes[..])).unwrap();
+        assert_eq!(&bytes[..], &captured[..]);
+        assert_eq!(ICMPPacket::new_checked(&bytes[..]).unwrap().checksum(), 0xb7a6);
+
+        let mut packet = ICMPPacket::new_unchecked(&mut bytes[..]);
+        assert_eq!(echo.emit(Message::Redirect, &mut packet), Err(Error::Illegal));
+        packet.set_msg_type(Message::TimeExceeded);
+        packet.fill_checksum();
+        let packet = ICMPPacket::new_checked(&bytes[..]).unwrap();
+        assert_eq!(EchoRepr::parse(&packet), Err(Error::Malformed));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_into_owned() {
+        use super::EchoBuilder;
+
+        let mut bytes = vec![0; 8 + 4];
+        let len = EchoBuilder::new()
+            .ident(0x1234)
+            .seq_no(7)
+            .payload(b"ABCD")
+            .build_into(&mut bytes)
+            .unwrap();
+        let packet = ICMPPacket::new_checked(&bytes[..len]).unwrap().into_owned();
+        drop(bytes);
+        assert!(packet.verify_checksum());
+        assert_eq!(packet.echo_ident(), 0x1234);
+        assert_eq!(packet.echo_seq_no(), 7);
+        assert_eq!(packet.data(), b"ABCD");
+    }
+
+    #[test]
+    fn test_redirect_gateway() {
+        use crate::Error;
+
+        // original IPv4 header + 64 bits of its payload
+        let original = [
+            0x45, 0x00, 0x00, 0x20, 0x00, 0x00, 0x40, 0x00,
+            0x40, 0x11, 0x00, 0x00, 0x0a, 0x0a, 0x0a, 0x02,
+            0xc0, 0xa8, 0x01, 0x01, 0x30, 0x39, 0x00, 0x35,
+            0x00, 0x0c, 0x00, 0x00,
+        ];
+
+        let mut bytes = vec![0; 8 + original.len()];
+        let mut packet = ICMPPacket::new_unchecked(&mut bytes);
+        packet.set_msg_type(Message::Redirect);
+        // redirect datagrams for the host
+        packet.set_msg_code(1);
+        packet.set_redirect_gateway(IPv4Address([10, 10, 10, 254]));
+        packet.data_mut().copy_from_slice(&original);
+        packet.fill_checksum();
+
+        let packet = ICMPPacket::new_checked(&bytes[..]).unwrap();
+        assert!(packet.verify_checksum());
+        assert_eq!(&packet.as_ref()[4..8], &[10, 10, 10, 254]);
+        assert_eq!(packet.redirect_gateway(), IPv4Address([10, 10, 10, 254]));
+        assert_eq!(packet.redirect_gateway_checked(), Ok(IPv4Address([10, 10, 10, 254])));
+        assert_eq!(packet.data(), &original[..]);
+
+        let mut bytes = [0; 8];
+        let mut packet = ICMPPacket::new_unchecked(&mut bytes[..]);
+        packet.set_msg_type(Message::TimeExceeded);
+        assert_eq!(packet.redirect_gateway_checked(), Err(Error::Illegal));
+    }
+
+    #[test]
+    fn test_message_len() {
+        use crate::Error;
+        use super::EchoBuilder;
+
+        let mut message = [0; 12];
+        let len = EchoBuilder::new()
+            .ident(0x1234)
+            .seq_no(1)
+            .payload(b"ABCD")
+            .build_into(&mut message)
+            .unwrap();
+
+        // an IPv4 datagram in an oversized buffer with garbage after it
+        let mut bytes = [0xa5; 20 + 12 + 20];
+        let mut ipv4_packet = IPv4Packet::new_unchecked(&mut bytes[..]);
+        ipv4_packet.set_version(4);
+        ipv4_packet.set_header_len(20);
+        ipv4_packet.set_total_len((20 + len) as u16);
+        ipv4_packet.payload_mut().copy_from_slice(&message[..len]);
+
+        let ipv4_packet = IPv4Packet::new_checked(&bytes[..]).unwrap();
+        let message_len = ipv4_packet.payload().len();
+        let mut packet = ICMPPacket::new_unchecked(&bytes[20..]);
+        assert_eq!(packet.message_len(), 32);
+        assert!(!packet.verify_checksum());
+        packet.set_message_len(message_len).unwrap();
+        assert_eq!(packet.message_len(), 12);
+        assert!(packet.verify_checksum());
+        assert_eq!(packet.data(), b"ABCD");
+
+        let mut scratch = [0xa5; 64];
+        scratch[..len].copy_from_slice(&message[..len]);
+        let mut packet = ICMPPacket::new_unchecked(&mut scratch[..]);
+        packet.set_message_len(len).unwrap();
+        packet.set_echo_seq_no(2);
+        packet.fill_checksum();
+        assert!(packet.verify_checksum());
+        let packet = ICMPPacket::new_checked(&scratch[..len]).unwrap();
+        assert!(packet.verify_checksum());
+        assert_eq!(packet.echo_seq_no(), 2);
+
+        // the length can't grow past the buffer
+        let mut packet = ICMPPacket::new_unchecked(&message[..]);
+        packet.set_message_len(100).unwrap();
+        assert_eq!(packet.message_len(), 12);
+
+        // nor shrink into the header
+        assert_eq!(packet.set_message_len(4), Err(Error::Truncated));
+        assert_eq!(packet.message_len(), 12);
+        assert_eq!(packet.data(), b"ABCD");
+        assert!(packet.check_len().is_ok());
+    }
+
+    #[test]
+    fn test_build_echo_request_into() {
+        use crate::Error;
+        use super::build_echo_request_into;
+
+        let src_mac = ethernet::Address([0x02, 0, 0, 0, 0, 0x01]);
+        let dst_mac = ethernet::Address([0x02, 0, 0, 0, 0, 0x02]);
+        let src_ip = IPv4Address([10, 0, 0, 1]);
+        let dst_ip = IPv4Address([10, 0, 0, 2]);
+        let payload = [0x5a; 40];
+
+        let mut buf = [0xff; 128];
+        let len = build_echo_request_into(
+            &mut buf, src_mac, dst_mac, src_ip, dst_ip, 0x1234, 7, &payload
+        ).unwrap();
+        assert_eq!(len, 14 + 20 + 8 + 40);
+        assert!(buf[len..].iter().all(|&byte| byte == 0xff));
+
+        let frame = Frame::new_checked(&buf[..len]).unwrap();
+        assert_eq!(frame.src_addr(), src_mac);
+        assert_eq!(frame.dst_addr(), dst_mac);
+        let ipv4_packet = IPv4Packet::new_checked(frame.payload()).unwrap();
+        assert!(ipv4_packet.verify_checksum());
+        assert_eq!(ipv4_packet.src_addr(), src_ip);
+        assert_eq!(ipv4_packet.dst_addr(), dst_ip);
+        let packet = frame.icmp_over_ipv4().unwrap();
+        assert!(packet.verify_checksum());
+        assert!(matches!(packet.msg_type(), Message::EchoRequest));
+        assert_eq!(packet.echo_ident(), 0x1234);
+        assert_eq!(packet.echo_seq_no(), 7);
+        assert_eq!(packet.data(), &payload[..]);
+
+        // short frames are padded
+        let mut buf = [0xff; 128];
+        let len = build_echo_request_into(
+            &mut buf, src_mac, dst_mac, src_ip, dst_ip, 1, 1, b"ping"
+        ).unwrap();
+        assert_eq!(len, 60);
+        assert!(buf[14 + 32..60].iter().all(|&byte| byte == 0));
+
+        let mut buf = [0; 59];
+        assert_eq!(
+            build_echo_request_into(&mut buf, src_mac, dst_mac, src_ip, dst_ip, 1, 1, b"ping"),
+            Err(Error::Exhausted)
+        );
+    }
 }
@@ -1 +1,516 @@
+// 0                   1                   2                   3
+// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     Type      |     Code      |          Checksum             |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                                                               |
+// +                         Message Body                          +
+// |                                                               |
 
+// Echo Request or Echo Reply Message
+// 0                   1                   2                   3
+// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     Type      |     Code      |          Checksum             |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |           Identifier          |        Sequence Number        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     Data ...
+// +-+-+-+-+-
+
+// Neighbor Solicitation or Neighbor Advertisement Message
+// 0                   1                   2                   3
+// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     Type      |     Code      |          Checksum             |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |R|S|O|                     Reserved                            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                                                               |
+// +                                                               +
+// |                                                               |
+// +                       Target Address                          +
+// |                                                               |
+// +                                                               +
+// |                                                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |   Options ...
+// +-+-+-+-+-+-+-+-+-+-+-+-
+
+#![allow(unused)]
+use byteorder::{
+    NetworkEndian,
+    ByteOrder,
+};
+use crate::{
+    Result,
+    Error,
+};
+use crate::bytes;
+use crate::checksum;
+use crate::protocol::ip::Protocol;
+use crate::protocol::ip::ipv6::Address as IPv6Address;
+use crate::protocol::ethernet::Address as MacAddress;
+
+#[repr(u8)]
+pub enum Message {
+    DstUnreachable = 1,
+    PacketTooBig   = 2,
+    EchoRequest    = 128,
+    EchoReply      = 129,
+    // Neighbor Discovery (RFC 4861)
+    RouterSolicit   = 133,
+    RouterAdvert    = 134,
+    NeighborSolicit = 135,
+    NeighborAdvert  = 136,
+    Unsupported    = 0xFF,
+}
+
+impl From<u8> for Message {
+    fn from(val: u8) -> Self {
+        match val {
+            1   => Self::DstUnreachable,
+            2   => Self::PacketTooBig,
+            128 => Self::EchoRequest,
+            129 => Self::EchoReply,
+            133 => Self::RouterSolicit,
+            134 => Self::RouterAdvert,
+            135 => Self::NeighborSolicit,
+            136 => Self::NeighborAdvert,
+            _   => Self::Unsupported
+        }
+    }
+}
+
+impl From<Message> for u8 {
+    fn from(msg: Message) -> Self {
+        match msg {
+            Message::DstUnreachable => 1,
+            Message::PacketTooBig => 2,
+            Message::EchoRequest => 128,
+            Message::EchoReply => 129,
+            Message::RouterSolicit => 133,
+            Message::RouterAdvert => 134,
+            Message::NeighborSolicit => 135,
+            Message::NeighborAdvert => 136,
+            Message::Unsupported => 0xFF,
+        }
+    }
+}
+
+mod field {
+    use crate::Field;
+
+    pub const TYPE: usize = 0;
+    pub const CODE: usize = 1;
+    pub const CHECKSUM: Field = 2..4;
+    pub const UNUSED: Field = 4..8;
+    pub const MTU: Field = 4..8;
+
+    pub const ECHO_IDENT: Field = 4..6;
+    pub const ECHO_SEQNO: Field = 6..8;
+
+    pub const HEADER_END: usize = 8;
+
+    pub const RA_HOP_LIMIT:  usize = 4;
+    pub const RA_FLAGS:      usize = 5;
+    pub const RA_LIFETIME:   Field = 6..8;
+    pub const RA_REACHABLE:  Field = 8..12;
+    pub const RA_RETRANS:    Field = 12..16;
+
+    pub const NA_FLAGS:      usize = 4;
+    pub const TARGET_ADDR:   Field = 8..24;
+}
+
+pub mod flags {
+    pub const ROUTER:    u8 = 0x80;
+    pub const SOLICITED: u8 = 0x40;
+    pub const OVERRIDE:  u8 = 0x20;
+}
+
+// Unlike ICMPv4, the checksum covers an IPv6 pseudo-header (RFC 8200).
+fn pseudo_header(src_addr: &IPv6Address, dst_addr: &IPv6Address, length: u32) -> u16 {
+    checksum::pseudo_header_v6(&src_addr.0, &dst_addr.0, Protocol::ICMPv6.into(), length)
+}
+
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T
+}
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    pub fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    // Neighbor Discovery messages have a longer fixed part, which must be
+    // there too for their accessors and data() to be safe.
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < field::HEADER_END || len < self.header_len() {
+            Err(Error::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    pub fn msg_type(&self) -> Message {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u8(data, field::TYPE)).into()
+    }
+
+    pub fn msg_code(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u8(data, field::CODE))
+    }
+
+    pub fn checksum(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::CHECKSUM))
+    }
+
+    pub fn echo_ident(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::ECHO_IDENT))
+    }
+
+    pub fn echo_seq_no(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::ECHO_SEQNO))
+    }
+
+    // MTU of the next-hop link, for Packet Too Big
+    pub fn mtu(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u32(data, field::MTU))
+    }
+
+    pub fn header_len(&self) -> usize {
+        match self.msg_type() {
+            Message::RouterAdvert    => field::RA_RETRANS.end,
+            Message::NeighborSolicit => field::TARGET_ADDR.end,
+            Message::NeighborAdvert  => field::TARGET_ADDR.end,
+            _ => field::HEADER_END
+        }
+    }
+
+    pub fn cur_hop_limit(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u8(data, field::RA_HOP_LIMIT))
+    }
+
+    // lifetime of the default router, in seconds
+    pub fn router_lifetime(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::RA_LIFETIME))
+    }
+
+    // in milliseconds
+    pub fn reachable_time(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u32(data, field::RA_REACHABLE))
+    }
+
+    // in milliseconds
+    pub fn retrans_time(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u32(data, field::RA_RETRANS))
+    }
+
+    // R, S and O flags of a Neighbor Advertisement
+    pub fn neighbor_flags(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u8(data, field::NA_FLAGS)) & 0xE0
+    }
+
+    pub fn target_addr(&self) -> IPv6Address {
+        let data = self.buffer.as_ref();
+        IPv6Address::from_bytes(bytes::checked(bytes::get_slice(data, field::TARGET_ADDR)))
+    }
+
+    // options of a Neighbor Discovery message
+    pub fn ndp_options(&self) -> NdpOptionsIter<'_> {
+        NdpOptionsIter::new(self.data())
+    }
+
+    pub fn data(&self) -> &[u8] {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_slice(data, self.header_len()..))
+    }
+
+    // The pseudo-header and the message are summed separately and then
+    // combined, so the message need not follow an IPv6 header in memory.
+    pub fn verify_checksum(&self, src: &IPv6Address, dst: &IPv6Address) -> bool {
+        let data = self.buffer.as_ref();
+        checksum::combine(&[
+            pseudo_header(src, dst, data.len() as u32),
+            checksum::data(data)
+        ]) == !0
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    pub fn set_msg_type(&mut self, msg_type: Message) {
+        let data = self.buffer.as_mut();
+        data[field::TYPE] = msg_type.into();
+    }
+
+    pub fn set_msg_code(&mut self, code: u8) {
+        let data = self.buffer.as_mut();
+        data[field::CODE] = code;
+    }
+
+    pub fn set_checksum(&mut self, checksum: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::CHECKSUM], checksum);
+    }
+
+    pub fn set_echo_ident(&mut self, ident: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::ECHO_IDENT], ident)
+    }
+
+    pub fn set_echo_seq_no(&mut self, number: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::ECHO_SEQNO], number)
+    }
+
+    pub fn set_mtu(&mut self, mtu: u32) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[field::MTU], mtu)
+    }
+
+    pub fn set_cur_hop_limit(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::RA_HOP_LIMIT] = value;
+    }
+
+    pub fn set_router_lifetime(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::RA_LIFETIME], value)
+    }
+
+    pub fn set_reachable_time(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[field::RA_REACHABLE], value)
+    }
+
+    pub fn set_retrans_time(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[field::RA_RETRANS], value)
+    }
+
+    pub fn set_neighbor_flags(&mut self, flags: u8) {
+        let data = self.buffer.as_mut();
+        data[field::NA_FLAGS] = flags & 0xE0;
+    }
+
+    pub fn set_target_addr(&mut self, addr: IPv6Address) {
+        let data = self.buffer.as_mut();
+        data[field::TARGET_ADDR].copy_from_slice(addr.as_bytes());
+    }
+
+    pub fn fill_checksum(&mut self, src: &IPv6Address, dst: &IPv6Address) {
+        self.set_checksum(0);
+        let checksum = {
+            let data = self.buffer.as_ref();
+            !checksum::combine(&[
+                pseudo_header(src, dst, data.len() as u32),
+                checksum::data(data)
+            ])
+        };
+        self.set_checksum(checksum)
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        let range = self.header_len()..;
+        let data = self.buffer.as_mut();
+        &mut data[range]
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+// 0                   1                   2                   3
+// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     Type      |    Length     |    Link-Layer Address ...
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+#[derive(Debug, PartialEq)]
+pub enum NdpOption<'a> {
+    SourceLinkLayerAddr(MacAddress),
+    TargetLinkLayerAddr(MacAddress),
+    Unknown(u8, &'a [u8]),
+}
+
+pub mod ndp_option {
+    pub const SOURCE_LL_ADDR: u8 = 1;
+    pub const TARGET_LL_ADDR: u8 = 2;
+}
+
+// The option length is counted in units of 8 octets, type and
+// length fields included.
+pub struct NdpOptionsIter<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> NdpOptionsIter<'a> {
+    pub fn new(data: &'a [u8]) -> NdpOptionsIter<'a> {
+        NdpOptionsIter { data }
+    }
+}
+
+impl<'a> Iterator for NdpOptionsIter<'a> {
+    type Item = Result<NdpOption<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let &kind = self.data.first()?;
+        let len = match self.data.get(1) {
+            Some(&len) => len as usize * 8,
+            None => 0,
+        };
+        if len == 0 || len > self.data.len() {
+            self.data = &[];
+            return Some(Err(Error::Malformed));
+        }
+        let value = &self.data[2..len];
+        self.data = &self.data[len..];
+
+        let option = match kind {
+            ndp_option::SOURCE_LL_ADDR =>
+                NdpOption::SourceLinkLayerAddr(MacAddress::from_bytes(&value[..6])),
+            ndp_option::TARGET_LL_ADDR =>
+                NdpOption::TargetLinkLayerAddr(MacAddress::from_bytes(&value[..6])),
+            _ => NdpOption::Unknown(kind, value),
+        };
+        Some(Ok(option))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::protocol::ip::ipv6::Address as IPv6Address;
+    use crate::protocol::ethernet::Address as MacAddress;
+    use super::{
+        Packet,
+        Message,
+        NdpOption,
+    };
+
+    static SRC_ADDR: IPv6Address = IPv6Address([
+        0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1
+    ]);
+    static DST_ADDR: IPv6Address = IPv6Address([
+        0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2
+    ]);
+
+    static ECHO_BYTES: [u8; 12] = [
+        0x80, 0x00, 0x40, 0x2c, 0x12, 0x34, 0xab, 0xcd,
+        0x41, 0x42, 0x43, 0x44,
+    ];
+
+    #[test]
+    fn test_echo() {
+        let mut bytes = vec![0; 12];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_msg_type(Message::EchoRequest);
+        packet.set_msg_code(0);
+        packet.set_echo_ident(0x1234);
+        packet.set_echo_seq_no(0xabcd);
+        packet.data_mut().copy_from_slice(b"ABCD");
+        packet.fill_checksum(&SRC_ADDR, &DST_ADDR);
+        assert_eq!(packet.as_ref(), &ECHO_BYTES[..]);
+
+        let packet = Packet::new_checked(&ECHO_BYTES[..]).unwrap();
+        assert_eq!(u8::from(packet.msg_type()), 128);
+        assert_eq!(packet.msg_code(), 0);
+        assert_eq!(packet.checksum(), 0x402c);
+        assert_eq!(packet.echo_ident(), 0x1234);
+        assert_eq!(packet.echo_seq_no(), 0xabcd);
+        assert_eq!(packet.data(), b"ABCD");
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        let packet = Packet::new_checked(&ECHO_BYTES[..]).unwrap();
+        assert!(packet.verify_checksum(&SRC_ADDR, &DST_ADDR));
+        // the pseudo-header is covered too
+        assert!(!packet.verify_checksum(&DST_ADDR, &DST_ADDR));
+
+        let mut bytes = ECHO_BYTES;
+        bytes[9] ^= 0x01;
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert!(!packet.verify_checksum(&SRC_ADDR, &DST_ADDR));
+    }
+
+    #[test]
+    fn test_packet_too_big() {
+        let mut bytes = vec![0; 8];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_msg_type(Message::PacketTooBig);
+        packet.set_mtu(1280);
+        assert_eq!(packet.as_ref(), &[2, 0, 0, 0, 0, 0, 0x05, 0x00]);
+        assert_eq!(packet.mtu(), 1280);
+    }
+
+    #[test]
+    fn test_neighbor_solicit() {
+        let mac = MacAddress([0x02, 0xfc, 0, 0, 0, 0x01]);
+        let mut bytes = vec![0; 24 + 8];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_msg_type(Message::NeighborSolicit);
+        packet.set_msg_code(0);
+        packet.set_target_addr(DST_ADDR);
+        packet.data_mut()[..2].copy_from_slice(&[1, 1]);
+        packet.data_mut()[2..].copy_from_slice(mac.as_bytes());
+        packet.fill_checksum(&SRC_ADDR, &DST_ADDR.solicited_node_multicast());
+
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert_eq!(packet.header_len(), 24);
+        assert_eq!(packet.target_addr(), DST_ADDR);
+        let mut options = packet.ndp_options();
+        assert_eq!(options.next(), Some(Ok(NdpOption::SourceLinkLayerAddr(mac))));
+        assert_eq!(options.next(), None);
+    }
+
+    #[test]
+    fn test_truncated_ndp() {
+        use crate::Error;
+
+        // a Neighbor Solicitation cut off in the target address
+        let mut bytes = [0; 10];
+        bytes[0] = Message::NeighborSolicit.into();
+        assert_eq!(Packet::new_checked(&bytes[..]).err(), Some(Error::Truncated));
+
+        // a Router Advertisement without its timers
+        let mut bytes = [0; 12];
+        bytes[0] = Message::RouterAdvert.into();
+        assert_eq!(Packet::new_checked(&bytes[..]).err(), Some(Error::Truncated));
+        let mut bytes = [0; 16];
+        bytes[0] = Message::RouterAdvert.into();
+        assert!(Packet::new_checked(&bytes[..]).is_ok());
+    }
+
+    #[test]
+    fn test_ndp_options_malformed() {
+        let mut options = super::NdpOptionsIter::new(&[2, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(options.next(), Some(Err(crate::Error::Malformed)));
+        assert_eq!(options.next(), None);
+
+        let mut options = super::NdpOptionsIter::new(&[2, 2, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(options.next(), Some(Err(crate::Error::Malformed)));
+    }
+}
@@ -0,0 +1,463 @@
+// IGMPv2 Message
+// 0                   1                   2                   3
+// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |      Type     | Max Resp Time |           Checksum            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                         Group Address                         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+// IGMPv3 Membership Report (RFC 3376)
+// 0                   1                   2                   3
+// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  Type = 0x22  |    Reserved   |           Checksum            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |           Reserved            |  Number of Group Records (M)  |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                      Group Record [1..M] ...
+// +-+-+-+-+-
+
+// Group Record
+// 0                   1                   2                   3
+// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  Record Type  |  Aux Data Len |     Number of Sources (N)     |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                       Multicast Address                       |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                   Source Address [1..N] ...
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                       Auxiliary Data ...
+// +-+-+-+-+-
+
+#![allow(unused)]
+use byteorder::{
+    ByteOrder,
+    NetworkEndian,
+};
+use crate::{
+    Result,
+    Error,
+};
+use crate::bytes;
+use super::ip::ipv4::Address as IPv4Address;
+use crate::checksum;
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    MembershipQuery    = 0x11,
+    MembershipReportV1 = 0x12,
+    MembershipReportV2 = 0x16,
+    LeaveGroup         = 0x17,
+    MembershipReportV3 = 0x22,
+    Unsupported        = 0xFF,
+}
+
+impl From<u8> for Message {
+    fn from(val: u8) -> Self {
+        match val {
+            0x11 => Self::MembershipQuery,
+            0x12 => Self::MembershipReportV1,
+            0x16 => Self::MembershipReportV2,
+            0x17 => Self::LeaveGroup,
+            0x22 => Self::MembershipReportV3,
+            _    => Self::Unsupported,
+        }
+    }
+}
+
+impl From<Message> for u8 {
+    fn from(msg: Message) -> Self {
+        match msg {
+            Message::MembershipQuery => 0x11,
+            Message::MembershipReportV1 => 0x12,
+            Message::MembershipReportV2 => 0x16,
+            Message::LeaveGroup => 0x17,
+            Message::MembershipReportV3 => 0x22,
+            Message::Unsupported => 0xFF,
+        }
+    }
+}
+
+mod field {
+    use crate::Field;
+
+    pub const TYPE:      usize = 0;
+    pub const MAX_RESP:  usize = 1;
+    pub const CHECKSUM:  Field = 2..4;
+    pub const GROUP:     Field = 4..8;
+}
+
+pub const HEADER_LEN: usize = field::GROUP.end;
+
+mod v3_field {
+    use crate::Field;
+
+    pub const CHECKSUM:    Field = 2..4;
+    pub const NUM_RECORDS: Field = 6..8;
+    pub const RECORDS:     usize = 8;
+
+    pub const RECORD_TYPE: usize = 0;
+    pub const AUX_LEN:     usize = 1;
+    pub const NUM_SOURCES: Field = 2..4;
+    pub const GROUP:       Field = 4..8;
+    pub const SOURCES:     usize = 8;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordType {
+    ModeIsInclude,
+    ModeIsExclude,
+    ChangeToInclude,
+    ChangeToExclude,
+    AllowNewSources,
+    BlockOldSources,
+    Unknown(u8),
+}
+
+impl From<u8> for RecordType {
+    fn from(val: u8) -> Self {
+        match val {
+            1 => Self::ModeIsInclude,
+            2 => Self::ModeIsExclude,
+            3 => Self::ChangeToInclude,
+            4 => Self::ChangeToExclude,
+            5 => Self::AllowNewSources,
+            6 => Self::BlockOldSources,
+            _ => Self::Unknown(val),
+        }
+    }
+}
+
+impl From<RecordType> for u8 {
+    fn from(record_type: RecordType) -> Self {
+        match record_type {
+            RecordType::ModeIsInclude => 1,
+            RecordType::ModeIsExclude => 2,
+            RecordType::ChangeToInclude => 3,
+            RecordType::ChangeToExclude => 4,
+            RecordType::AllowNewSources => 5,
+            RecordType::BlockOldSources => 6,
+            RecordType::Unknown(val) => val,
+        }
+    }
+}
+
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T
+}
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    pub fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < HEADER_LEN {
+            Err(Error::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    pub fn msg_type(&self) -> Message {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u8(data, field::TYPE)).into()
+    }
+
+    // in units of 1/10 second
+    pub fn max_resp_time(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u8(data, field::MAX_RESP))
+    }
+
+    pub fn checksum(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::CHECKSUM))
+    }
+
+    pub fn group_address(&self) -> IPv4Address {
+        let data = self.buffer.as_ref();
+        IPv4Address::from_bytes(bytes::checked(bytes::get_slice(data, field::GROUP)))
+    }
+
+    pub fn verify_checksum(&self) -> bool {
+        let data = self.buffer.as_ref();
+        checksum::data(bytes::checked(bytes::get_slice(data, ..HEADER_LEN))) == !0
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    pub fn set_msg_type(&mut self, msg_type: Message) {
+        let data = self.buffer.as_mut();
+        data[field::TYPE] = msg_type.into();
+    }
+
+    pub fn set_max_resp_time(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::MAX_RESP] = value;
+    }
+
+    pub fn set_checksum(&mut self, checksum: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::CHECKSUM], checksum);
+    }
+
+    pub fn set_group_address(&mut self, addr: IPv4Address) {
+        let data = self.buffer.as_mut();
+        data[field::GROUP].copy_from_slice(addr.as_bytes());
+    }
+
+    pub fn fill_checksum(&mut self) {
+        self.set_checksum(0);
+        let checksum = {
+            let data = self.buffer.as_ref();
+            !checksum::data(&data[..HEADER_LEN])
+        };
+        self.set_checksum(checksum)
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+pub struct V3Report<T: AsRef<[u8]>> {
+    buffer: T
+}
+
+impl<T: AsRef<[u8]>> V3Report<T> {
+    pub fn new_unchecked(buffer: T) -> V3Report<T> {
+        V3Report { buffer }
+    }
+
+    pub fn new_checked(buffer: T) -> Result<V3Report<T>> {
+        let report = Self::new_unchecked(buffer);
+        report.check_len()?;
+        Ok(report)
+    }
+
+    // Only the fixed part; the group records are checked as they are read.
+    pub fn check_len(&self) -> Result<()> {
+        if self.buffer.as_ref().len() < v3_field::RECORDS {
+            Err(Error::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    pub fn checksum(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, v3_field::CHECKSUM))
+    }
+
+    pub fn num_group_records(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, v3_field::NUM_RECORDS))
+    }
+
+    pub fn group_records(&self) -> GroupRecordIter<'_> {
+        let data = self.buffer.as_ref();
+        GroupRecordIter {
+            data: bytes::checked(bytes::get_slice(data, v3_field::RECORDS..)),
+            remaining: self.num_group_records(),
+        }
+    }
+
+    // Unlike v2, the checksum covers the whole message.
+    pub fn verify_checksum(&self) -> bool {
+        let data = self.buffer.as_ref();
+        checksum::data(data) == !0
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for V3Report<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+// A group record whose length was checked by GroupRecordIter.
+#[derive(Debug, Clone, Copy)]
+pub struct GroupRecord<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> GroupRecord<'a> {
+    pub fn record_type(&self) -> RecordType {
+        self.buffer[v3_field::RECORD_TYPE].into()
+    }
+
+    // in units of 32-bit words
+    pub fn aux_data_len(&self) -> u8 {
+        self.buffer[v3_field::AUX_LEN]
+    }
+
+    pub fn num_sources(&self) -> u16 {
+        NetworkEndian::read_u16(&self.buffer[v3_field::NUM_SOURCES])
+    }
+
+    pub fn multicast_addr(&self) -> IPv4Address {
+        IPv4Address::from_bytes(&self.buffer[v3_field::GROUP])
+    }
+
+    pub fn sources(&self) -> SourceIter<'a> {
+        let end = v3_field::SOURCES + self.num_sources() as usize * 4;
+        SourceIter { data: &self.buffer[v3_field::SOURCES..end] }
+    }
+
+    pub fn aux_data(&self) -> &'a [u8] {
+        let start = v3_field::SOURCES + self.num_sources() as usize * 4;
+        &self.buffer[start..]
+    }
+}
+
+pub struct GroupRecordIter<'a> {
+    data: &'a [u8],
+    remaining: u16,
+}
+
+impl<'a> Iterator for GroupRecordIter<'a> {
+    type Item = Result<GroupRecord<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let len = match self.data.get(..v3_field::SOURCES) {
+            Some(fixed) => {
+                let num_sources = NetworkEndian::read_u16(&fixed[v3_field::NUM_SOURCES]);
+                v3_field::SOURCES + num_sources as usize * 4 + fixed[v3_field::AUX_LEN] as usize * 4
+            }
+            None => usize::MAX,
+        };
+        if len > self.data.len() {
+            self.remaining = 0;
+            return Some(Err(Error::Truncated));
+        }
+        let (record, rest) = self.data.split_at(len);
+        self.data = rest;
+        self.remaining -= 1;
+        Some(Ok(GroupRecord { buffer: record }))
+    }
+}
+
+pub struct SourceIter<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for SourceIter<'a> {
+    type Item = IPv4Address;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let addr = self.data.get(..4)?;
+        self.data = &self.data[4..];
+        Some(IPv4Address::from_bytes(addr))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Error;
+    use crate::protocol::ip::ipv4::Address as IPv4Address;
+    use super::{
+        Packet,
+        Message,
+        V3Report,
+        RecordType,
+    };
+
+    // V2 report for 224.0.0.251
+    static REPORT_BYTES: [u8; 8] = [
+        0x16, 0x00, 0x09, 0x04, 0xe0, 0x00, 0x00, 0xfb,
+    ];
+
+    #[test]
+    fn test_construct() {
+        let mut bytes = [0xa5; 8];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_msg_type(Message::MembershipReportV2);
+        packet.set_max_resp_time(0);
+        packet.set_group_address(IPv4Address([224, 0, 0, 251]));
+        packet.fill_checksum();
+        assert_eq!(packet.as_ref(), &REPORT_BYTES[..]);
+    }
+
+    #[test]
+    fn test_deconstruct() {
+        let packet = Packet::new_checked(&REPORT_BYTES[..]).unwrap();
+        assert_eq!(packet.msg_type(), Message::MembershipReportV2);
+        assert_eq!(packet.max_resp_time(), 0);
+        assert_eq!(packet.checksum(), 0x0904);
+        assert_eq!(packet.group_address(), IPv4Address([224, 0, 0, 251]));
+        assert!(packet.verify_checksum());
+
+        let mut bytes = REPORT_BYTES;
+        bytes[7] = 0xfc;
+        assert!(!Packet::new_checked(&bytes[..]).unwrap().verify_checksum());
+        assert_eq!(Packet::new_checked(&bytes[..7]).err(), Some(Error::Truncated));
+    }
+
+    // V3 report: MODE_IS_INCLUDE 232.1.1.1 from 10.0.0.1 and 10.0.0.2,
+    // CHANGE_TO_EXCLUDE 239.1.1.1 with no sources
+    static V3_REPORT_BYTES: [u8; 32] = [
+        0x22, 0x00, 0xeb, 0xf2, 0x00, 0x00, 0x00, 0x02,
+        0x01, 0x00, 0x00, 0x02, 0xe8, 0x01, 0x01, 0x01,
+        0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02,
+        0x04, 0x00, 0x00, 0x00, 0xef, 0x01, 0x01, 0x01,
+    ];
+
+    #[test]
+    fn test_v3_report() {
+        let packet = Packet::new_checked(&V3_REPORT_BYTES[..]).unwrap();
+        assert_eq!(packet.msg_type(), Message::MembershipReportV3);
+
+        let report = V3Report::new_checked(&V3_REPORT_BYTES[..]).unwrap();
+        assert!(report.verify_checksum());
+        assert_eq!(report.num_group_records(), 2);
+
+        let mut records = report.group_records();
+        let record = records.next().unwrap().unwrap();
+        assert_eq!(record.record_type(), RecordType::ModeIsInclude);
+        assert_eq!(record.num_sources(), 2);
+        assert_eq!(record.multicast_addr(), IPv4Address([232, 1, 1, 1]));
+        let sources: Vec<_> = record.sources().collect();
+        assert_eq!(sources, [IPv4Address([10, 0, 0, 1]), IPv4Address([10, 0, 0, 2])]);
+        assert!(record.aux_data().is_empty());
+
+        let record = records.next().unwrap().unwrap();
+        assert_eq!(record.record_type(), RecordType::ChangeToExclude);
+        assert_eq!(record.multicast_addr(), IPv4Address([239, 1, 1, 1]));
+        assert_eq!(record.sources().count(), 0);
+        assert!(records.next().is_none());
+    }
+
+    #[test]
+    fn test_v3_report_truncated() {
+        let report = V3Report::new_checked(&V3_REPORT_BYTES[..20]).unwrap();
+        let mut records = report.group_records();
+        assert_eq!(records.next().unwrap().err(), Some(Error::Truncated));
+        assert!(records.next().is_none());
+
+        assert_eq!(V3Report::new_checked(&V3_REPORT_BYTES[..7]).err(), Some(Error::Truncated));
+    }
+}
@@ -1,39 +1,20 @@
 #![allow(unused)]
 
 pub mod ipv4;
-mod ipv6;
+pub mod ipv6;
+#[cfg(feature = "alloc")]
+pub mod reassembly;
+#[cfg(feature = "alloc")]
+pub mod fragmentation;
 
 use crate::{
     Result,
     Error,
 };
+use core::fmt;
+use core::str::FromStr;
 
-mod checksum {
-    use byteorder::{
-        ByteOrder, 
-        NetworkEndian,
-    };
-
-    // return checksum
-    pub fn result(data: &[u8]) -> u16 {
-        let mut accum = 0u32;
-        for i in (0..data.len()).step_by(2) {
-            if i == 10 { continue; }
-            if i + 1 > data.len() { break; }
-            accum += NetworkEndian::read_u16(
-                &data[i..]
-            ) as u32;
-        }
-
-        if data.len() % 2 != 0 {
-            let &val = data.last().unwrap();
-            accum += (val as u32) << 8;  
-        }
-        
-        !((accum >> 16) as u16 + accum as u16)
-    }
-}
-
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Version {
     IPv4,
     IPv6,
@@ -51,20 +32,153 @@ impl Version {
     }
 }
 
-#[repr(u8)]
+// An address of either IP version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IpAddress {
+    Ipv4(ipv4::Address),
+    Ipv6(ipv6::Address),
+}
+
+impl IpAddress {
+    pub fn version(&self) -> Version {
+        match self {
+            IpAddress::Ipv4(_) => Version::IPv4,
+            IpAddress::Ipv6(_) => Version::IPv6,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            IpAddress::Ipv4(addr) => addr.as_bytes(),
+            IpAddress::Ipv6(addr) => addr.as_bytes(),
+        }
+    }
+
+    pub fn is_unspecified(&self) -> bool {
+        match self {
+            IpAddress::Ipv4(addr) => addr.is_unspecified(),
+            IpAddress::Ipv6(addr) => addr.is_unspecified(),
+        }
+    }
+
+    pub fn is_multicast(&self) -> bool {
+        match self {
+            IpAddress::Ipv4(addr) => addr.is_multicast(),
+            IpAddress::Ipv6(addr) => addr.is_multicast(),
+        }
+    }
+}
+
+impl From<ipv4::Address> for IpAddress {
+    fn from(addr: ipv4::Address) -> Self {
+        IpAddress::Ipv4(addr)
+    }
+}
+
+impl From<ipv6::Address> for IpAddress {
+    fn from(addr: ipv6::Address) -> Self {
+        IpAddress::Ipv6(addr)
+    }
+}
+
+impl fmt::Display for IpAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IpAddress::Ipv4(addr) => write!(f, "{}", addr),
+            IpAddress::Ipv6(addr) => write!(f, "{}", addr),
+        }
+    }
+}
+
+impl FromStr for IpAddress {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<IpAddress> {
+        if s.contains(':') {
+            s.parse().map(IpAddress::Ipv6)
+        } else {
+            s.parse().map(IpAddress::Ipv4)
+        }
+    }
+}
+
+// An address and port pair, the key sockets are bound and looked up by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct IpEndpoint {
+    pub addr: IpAddress,
+    pub port: u16,
+}
+
+impl IpEndpoint {
+    pub fn new(addr: IpAddress, port: u16) -> IpEndpoint {
+        IpEndpoint { addr, port }
+    }
+
+    pub fn is_specified(&self) -> bool {
+        !self.addr.is_unspecified() && self.port != 0
+    }
+
+    // Whether a socket bound to this endpoint takes packets sent to
+    // `incoming`. An unspecified address matches any address of the same
+    // version and port 0 matches any port.
+    pub fn accepts(&self, incoming: &IpEndpoint) -> bool {
+        let addr_matches = if self.addr.is_unspecified() {
+            self.addr.version() == incoming.addr.version()
+        } else {
+            self.addr == incoming.addr
+        };
+        addr_matches && (self.port == 0 || self.port == incoming.port)
+    }
+}
+
+impl fmt::Display for IpEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.addr {
+            IpAddress::Ipv4(addr) => write!(f, "{}:{}", addr, self.port),
+            IpAddress::Ipv6(addr) => write!(f, "[{}]:{}", addr, self.port),
+        }
+    }
+}
+
+// "10.0.0.1:53" or "[fe80::1]:53"; IPv6 addresses must be bracketed.
+impl FromStr for IpEndpoint {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<IpEndpoint> {
+        let (addr, port) = if let Some(rest) = s.strip_prefix('[') {
+            let end = rest.find("]:").ok_or(Error::Malformed)?;
+            let addr = rest[..end].parse().map(IpAddress::Ipv6)?;
+            (addr, &rest[end + 2..])
+        } else {
+            let end = s.rfind(':').ok_or(Error::Malformed)?;
+            let addr = s[..end].parse().map(IpAddress::Ipv4)?;
+            (addr, &s[end + 1..])
+        };
+        // reject signs, which u16::from_str would accept
+        if !port.bytes().all(|c| c.is_ascii_digit()) {
+            return Err(Error::Malformed);
+        }
+        let port = port.parse().map_err(|_| Error::Malformed)?;
+        Ok(IpEndpoint { addr, port })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Protocol {
-    HopByHop  = 0x00,
-    ICMP      = 0x01,
-    IGMP      = 0x02,
-    TCP       = 0x06,
-    UDP       = 0x11,
-    IPv6Route = 0x2B,
-    IPv6Frag  = 0x2C,
-    ICMPv6    = 0x3A,
-    IPv6NoNxt = 0x3B,
-    IPv6Opts  = 0x3C,
-    Test = 0xFD,
-    Unsupported = 0xFF,
+    HopByHop,
+    ICMP,
+    IGMP,
+    TCP,
+    UDP,
+    Gre,
+    IPv6Route,
+    IPv6Frag,
+    ICMPv6,
+    IPv6NoNxt,
+    IPv6Opts,
+    Test,
+    // keeps the raw value of any other protocol
+    Other(u8),
 }
 
 impl From<u8> for Protocol {
@@ -75,12 +189,14 @@ impl From<u8> for Protocol {
             0x02 => Self::IGMP,
             0x06 => Self::TCP,
             0x11 => Self::UDP,
+            0x2F => Self::Gre,
             0x2B => Self::IPv6Route,
             0x2C => Self::IPv6Frag,
             0x3A => Self::ICMPv6,
             0x3B => Self::IPv6NoNxt,
             0x3C => Self::IPv6Opts,
-            _ => Self::Unsupported
+            0xFD => Self::Test,
+            _ => Self::Other(val),
         }
     }
 }
@@ -93,13 +209,165 @@ impl From<Protocol> for u8 {
             Protocol::IGMP => 0x02,
             Protocol::TCP => 0x06,
             Protocol::UDP => 0x11,
-            Protocol::IPv6Route => 0x11,
-            Protocol::IPv6Frag => 0x2B,
-            Protocol::ICMPv6 => 0x2C,
-            Protocol::IPv6NoNxt => 0x3A,
+            Protocol::Gre => 0x2F,
+            Protocol::IPv6Route => 0x2B,
+            Protocol::IPv6Frag => 0x2C,
+            Protocol::ICMPv6 => 0x3A,
+            Protocol::IPv6NoNxt => 0x3B,
             Protocol::IPv6Opts => 0x3C,
             Protocol::Test => 0xFD,
-            Protocol::Unsupported => 0xFF,
+            Protocol::Other(val) => val,
+        }
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Protocol::HopByHop => "IPv6-HopByHop",
+            Protocol::ICMP => "ICMP",
+            Protocol::IGMP => "IGMP",
+            Protocol::TCP => "TCP",
+            Protocol::UDP => "UDP",
+            Protocol::Gre => "GRE",
+            Protocol::IPv6Route => "IPv6-Route",
+            Protocol::IPv6Frag => "IPv6-Frag",
+            Protocol::ICMPv6 => "ICMPv6",
+            Protocol::IPv6NoNxt => "IPv6-NoNxt",
+            Protocol::IPv6Opts => "IPv6-Opts",
+            Protocol::Test => "Test",
+            Protocol::Other(_) => "Other",
+        };
+        write!(f, "{} ({})", name, u8::from(*self))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Protocol {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_u8((*self).into())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Protocol {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let raw: u8 = serde::Deserialize::deserialize(deserializer)?;
+        Ok(raw.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        Protocol,
+        IpAddress,
+        IpEndpoint,
+        Version,
+        ipv4,
+        ipv6,
+    };
+
+    #[test]
+    fn test_protocol() {
+        assert_eq!(Protocol::from(0x84), Protocol::Other(0x84));
+        assert_eq!(u8::from(Protocol::from(0x84)), 0x84);
+        assert_eq!(Protocol::from(0x11), Protocol::UDP);
+        assert_eq!(Protocol::from(0xFD), Protocol::Test);
+        for raw in 0..=255u8 {
+            assert_eq!(u8::from(Protocol::from(raw)), raw);
+        }
+    }
+
+    #[test]
+    fn test_protocol_display() {
+        assert_eq!(format!("{}", Protocol::TCP), "TCP (6)");
+        assert_eq!(format!("{}", Protocol::UDP), "UDP (17)");
+        assert_eq!(format!("{}", Protocol::Other(0x84)), "Other (132)");
+    }
+
+    #[test]
+    fn test_ip_address() {
+        let v4 = IpAddress::from(ipv4::Address([10, 0, 0, 1]));
+        assert_eq!(v4, IpAddress::Ipv4(ipv4::Address([10, 0, 0, 1])));
+        assert_eq!(v4.version(), Version::IPv4);
+        assert_eq!(v4.as_bytes(), &[10, 0, 0, 1]);
+        assert_eq!(v4.to_string(), "10.0.0.1");
+        assert!(!v4.is_unspecified());
+        assert!(!v4.is_multicast());
+        assert!(IpAddress::Ipv4(ipv4::Address::UNSPECIFIED).is_unspecified());
+        assert!(IpAddress::Ipv4(ipv4::Address::MULTICAST_ALL_SYSTEMS).is_multicast());
+
+        let v6 = IpAddress::from(ipv6::Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1));
+        assert_eq!(v6.version(), Version::IPv6);
+        assert_eq!(v6.as_bytes().len(), 16);
+        assert_eq!(v6.as_bytes()[..2], [0xfe, 0x80]);
+        assert_eq!(v6.to_string(), "fe80::1");
+        assert!(!v6.is_unspecified());
+        assert!(!v6.is_multicast());
+        assert!(IpAddress::Ipv6(ipv6::Address::UNSPECIFIED).is_unspecified());
+        assert!(IpAddress::Ipv6(ipv6::Address::LINK_LOCAL_ALL_NODES).is_multicast());
+    }
+
+    #[test]
+    fn test_ip_endpoint() {
+        use crate::Error;
+
+        let endpoints = [
+            (IpEndpoint::new(IpAddress::Ipv4(ipv4::Address([10, 0, 0, 1])), 53), "10.0.0.1:53"),
+            (IpEndpoint::new(IpAddress::Ipv6(ipv6::Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1)), 53), "[fe80::1]:53"),
+            (IpEndpoint::new(IpAddress::Ipv6(ipv6::Address::UNSPECIFIED), 0), "[::]:0"),
+        ];
+        for &(endpoint, text) in endpoints.iter() {
+            assert_eq!(endpoint.to_string(), text);
+            assert_eq!(text.parse::<IpEndpoint>(), Ok(endpoint));
+        }
+        assert!(endpoints[0].0.is_specified());
+        assert!(endpoints[1].0.is_specified());
+        assert!(!endpoints[2].0.is_specified());
+        assert!(!IpEndpoint::new(IpAddress::Ipv4(ipv4::Address::UNSPECIFIED), 53).is_specified());
+        assert!(!IpEndpoint::new(IpAddress::Ipv4(ipv4::Address([10, 0, 0, 1])), 0).is_specified());
+
+        for text in ["10.0.0.1", "10.0.0.1:", "10.0.0.1:+53", "10.0.0.1:65536",
+                     "fe80::1:53", "[fe80::1]", "[fe80::1]53", "[10.0.0.1]:53"].iter() {
+            assert_eq!(text.parse::<IpEndpoint>(), Err(Error::Malformed), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_ip_endpoint_accepts() {
+        let endpoint = |text: &str| text.parse::<IpEndpoint>().unwrap();
+
+        // wildcard address
+        assert!(endpoint("0.0.0.0:53").accepts(&endpoint("10.0.0.1:53")));
+        assert!(endpoint("0.0.0.0:53").accepts(&endpoint("192.168.1.1:53")));
+        assert!(!endpoint("0.0.0.0:53").accepts(&endpoint("10.0.0.1:54")));
+        assert!(!endpoint("0.0.0.0:53").accepts(&endpoint("[fe80::1]:53")));
+        assert!(endpoint("[::]:53").accepts(&endpoint("[fe80::1]:53")));
+
+        // wildcard port
+        assert!(endpoint("10.0.0.1:0").accepts(&endpoint("10.0.0.1:53")));
+        assert!(endpoint("10.0.0.1:0").accepts(&endpoint("10.0.0.1:5353")));
+        assert!(!endpoint("10.0.0.1:0").accepts(&endpoint("10.0.0.2:53")));
+
+        // exact match and full mismatch
+        assert!(endpoint("10.0.0.1:53").accepts(&endpoint("10.0.0.1:53")));
+        assert!(!endpoint("10.0.0.1:53").accepts(&endpoint("10.0.0.2:54")));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        for &protocol in [Protocol::ICMP, Protocol::UDP, Protocol::ICMPv6, Protocol::Other(0x84)].iter() {
+            let json = serde_json::to_string(&protocol).unwrap();
+            assert_eq!(json, u8::from(protocol).to_string());
+            assert_eq!(serde_json::from_str::<Protocol>(&json).unwrap(), protocol);
         }
     }
 }
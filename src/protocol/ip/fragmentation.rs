@@ -0,0 +1,282 @@
+#![allow(unused)]
+use alloc::vec::Vec;
+use crate::{
+    Result,
+    Error,
+};
+use super::ipv4::{
+    Packet,
+    OptionsIter,
+    option,
+};
+
+// Options with this bit in their type go into every fragment; the rest
+// only into the first one.
+const OPTION_COPIED: u8 = 0x80;
+
+// Where one fragment's payload lies in the original payload, in octets,
+// and whether more fragments follow it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FragmentMeta {
+    pub offset_octets: usize,
+    pub len: usize,
+    pub more: bool,
+}
+
+// The fragments of a payload of `total_payload` octets sent with a
+// `header_len` header over a link with the given MTU. Every fragment but
+// the last carries a multiple of 8 octets, as the fragment offset field
+// counts units of 8. A payload that fits gives a single fragment; an MTU
+// too small for 8 octets of payload gives none.
+#[derive(Debug, Clone)]
+pub struct FragmentPlan {
+    total_payload: usize,
+    chunk_len: usize,
+    offset: usize,
+    done: bool,
+}
+
+impl FragmentPlan {
+    pub fn new(total_payload: usize, mtu: usize, header_len: usize) -> FragmentPlan {
+        let room = mtu.saturating_sub(header_len);
+        let chunk_len = if total_payload <= room { total_payload.max(1) } else { room & !7 };
+        FragmentPlan {
+            total_payload,
+            chunk_len,
+            offset: 0,
+            done: chunk_len == 0,
+        }
+    }
+}
+
+impl Iterator for FragmentPlan {
+    type Item = FragmentMeta;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let len = self.chunk_len.min(self.total_payload - self.offset);
+        let meta = FragmentMeta {
+            offset_octets: self.offset,
+            len,
+            more: self.offset + len < self.total_payload,
+        };
+        self.offset += len;
+        self.done = !meta.more;
+        Some(meta)
+    }
+}
+
+// The header of fragments after the first: the original header with only
+// the options that have the copied bit set, padded to a multiple of 4
+// octets with End of Option List.
+fn later_header(header: &[u8], options: &[u8]) -> Result<Vec<u8>> {
+    let mut reduced = header[..header.len() - options.len()].to_vec();
+    let mut rest = options;
+    for opt in OptionsIter::new(options) {
+        let (kind, value) = opt?;
+        let len = match kind {
+            option::END_OF_LIST => break,
+            option::NO_OPERATION => 1,
+            _ => 2 + value.len(),
+        };
+        if kind & OPTION_COPIED != 0 {
+            reduced.extend_from_slice(&rest[..len]);
+        }
+        rest = &rest[len..];
+    }
+    let padded_len = (reduced.len() + 3) & !3;
+    reduced.resize(padded_len, option::END_OF_LIST);
+    Packet::new_unchecked(&mut reduced[..]).set_header_len(padded_len as u8);
+    Ok(reduced)
+}
+
+// Split a datagram that does not fit in `mtu` into fragments, passing each
+// one to `out`. The first fragment repeats the original header; later ones
+// keep only the options marked to be copied (RFC 791). Every fragment but
+// the last carries a multiple of 8 octets of payload. A datagram that
+// already fits is passed through unchanged.
+pub fn fragment<F>(packet: &Packet<&[u8]>, mtu: usize, out: &mut F) -> Result<()>
+where
+    F: FnMut(&[u8]),
+{
+    packet.check_len()?;
+    let header_len = packet.header_len() as usize;
+    let total_len = packet.total_len() as usize;
+
+    if total_len <= mtu {
+        out(&packet.as_ref()[..total_len]);
+        return Ok(());
+    }
+    if packet.dont_frag() {
+        return Err(Error::Illegal);
+    }
+
+    if mtu.saturating_sub(header_len) < 8 {
+        return Err(Error::Illegal);
+    }
+
+    // the datagram may itself be a fragment of a larger one
+    let base_offset = packet.frag_offset() as usize;
+    let more_frags = packet.more_frags();
+    let first_header = &packet.as_ref()[..header_len];
+    let later_header = later_header(first_header, packet.options())?;
+    let payload = &packet.as_ref()[header_len..total_len];
+
+    // sized for the first header, which is the longest
+    let mut buffer = Vec::with_capacity(mtu);
+    for meta in FragmentPlan::new(payload.len(), mtu, header_len) {
+        let header = if meta.offset_octets == 0 { first_header } else { &later_header[..] };
+        let chunk = &payload[meta.offset_octets..meta.offset_octets + meta.len];
+        buffer.clear();
+        buffer.extend_from_slice(header);
+        buffer.extend_from_slice(chunk);
+
+        let mut fragment = Packet::new_unchecked(&mut buffer[..]);
+        fragment.set_total_len((header.len() + chunk.len()) as u16);
+        fragment.set_frag_offset((base_offset + meta.offset_octets) as u16);
+        fragment.set_more_frags(more_frags || meta.more);
+        fragment.fill_checksum();
+        out(&buffer);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::vec::Vec;
+    use crate::Error;
+    use crate::dev::ipv4_datagram;
+    use crate::protocol::ip::ipv4::{
+        Packet,
+        OptionsIter,
+        option,
+    };
+    use crate::protocol::ip::reassembly::Reassembler;
+    use core::time::Duration;
+    use super::{
+        fragment,
+        FragmentPlan,
+        FragmentMeta,
+    };
+
+    fn datagram(payload_len: usize) -> Vec<u8> {
+        ipv4_datagram(0x4242, payload_len)
+    }
+
+    #[test]
+    fn test_fragment() {
+        let original = datagram(1500);
+        let packet = Packet::new_checked(&original[..]).unwrap();
+        let mut frags = Vec::new();
+        fragment(&packet, 576, &mut |bytes: &[u8]| frags.push(bytes.to_vec())).unwrap();
+        assert_eq!(frags.len(), 3);
+
+        let expected = [(0, 552, true), (552, 552, true), (1104, 396, false)];
+        for (bytes, &(offset, len, more)) in frags.iter().zip(expected.iter()) {
+            let frag = Packet::new_checked(&bytes[..]).unwrap();
+            assert!(bytes.len() <= 576);
+            assert!(frag.verify_checksum());
+            assert_eq!(frag.ident(), 0x4242);
+            assert_eq!(frag.frag_offset(), offset);
+            assert_eq!(frag.total_len() as usize, 20 + len);
+            assert_eq!(frag.more_frags(), more);
+        }
+
+        let mut reassembler = Reassembler::default();
+        let mut result = None;
+        for bytes in frags.iter().rev() {
+            let frag = Packet::new_checked(&bytes[..]).unwrap();
+            result = reassembler.accept(&frag, Duration::from_secs(0)).unwrap();
+        }
+        assert_eq!(result, Some(original));
+    }
+
+    #[test]
+    fn test_fragment_options() {
+        // Router Alert is copied into every fragment, Record Route is not
+        let options = [
+            option::ROUTER_ALERT, 4, 0, 0,
+            option::RECORD_ROUTE, 7, 4, 0, 0, 0, 0, option::END_OF_LIST,
+        ];
+        let plain = datagram(1000);
+        let mut original = plain[..20].to_vec();
+        original.extend_from_slice(&options);
+        original.extend_from_slice(&plain[20..]);
+        let mut packet = Packet::new_unchecked(&mut original[..]);
+        packet.set_header_len(32);
+        packet.set_total_len(32 + 1000);
+        packet.fill_checksum();
+
+        let packet = Packet::new_checked(&original[..]).unwrap();
+        let mut frags = Vec::new();
+        fragment(&packet, 576, &mut |bytes: &[u8]| frags.push(bytes.to_vec())).unwrap();
+        assert_eq!(frags.len(), 2);
+
+        let first = Packet::new_checked(&frags[0][..]).unwrap();
+        assert_eq!(first.header_len(), 32);
+        assert_eq!(first.options(), &options[..]);
+
+        let second = Packet::new_checked(&frags[1][..]).unwrap();
+        assert_eq!(second.header_len(), 24);
+        assert!(second.verify_checksum());
+        assert_eq!(second.total_len() as usize, 24 + 1000 - 544);
+        let kinds: Vec<_> = OptionsIter::new(second.options())
+            .map(|option| option.unwrap().0)
+            .collect();
+        assert_eq!(kinds, [option::ROUTER_ALERT]);
+
+        let mut reassembler = Reassembler::default();
+        for bytes in frags.iter() {
+            let frag = Packet::new_checked(&bytes[..]).unwrap();
+            if let Some(datagram) = reassembler.accept(&frag, Duration::from_secs(0)).unwrap() {
+                assert_eq!(datagram, original);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fits() {
+        let original = datagram(100);
+        let packet = Packet::new_checked(&original[..]).unwrap();
+        let mut frags = Vec::new();
+        fragment(&packet, 576, &mut |bytes: &[u8]| frags.push(bytes.to_vec())).unwrap();
+        assert_eq!(frags, vec![original.clone()]);
+    }
+
+    #[test]
+    fn test_dont_frag() {
+        let mut original = datagram(1500);
+        let mut packet = Packet::new_unchecked(&mut original[..]);
+        packet.set_dont_frag(true);
+        packet.fill_checksum();
+
+        let packet = Packet::new_checked(&original[..]).unwrap();
+        let mut count = 0;
+        assert_eq!(fragment(&packet, 576, &mut |_: &[u8]| count += 1), Err(Error::Illegal));
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_fragment_plan() {
+        let plan: Vec<_> = FragmentPlan::new(4000, 1500, 20).collect();
+        assert_eq!(plan, [
+            FragmentMeta { offset_octets: 0, len: 1480, more: true },
+            FragmentMeta { offset_octets: 1480, len: 1480, more: true },
+            FragmentMeta { offset_octets: 2960, len: 1040, more: false },
+        ]);
+
+        // 576 - 28 = 548 octets of room, rounded down to a multiple of 8
+        let plan: Vec<_> = FragmentPlan::new(1000, 576, 28).collect();
+        assert_eq!(plan[0], FragmentMeta { offset_octets: 0, len: 544, more: true });
+        assert_eq!(plan[1], FragmentMeta { offset_octets: 544, len: 456, more: false });
+
+        let plan: Vec<_> = FragmentPlan::new(100, 1500, 20).collect();
+        assert_eq!(plan, [FragmentMeta { offset_octets: 0, len: 100, more: false }]);
+        let plan: Vec<_> = FragmentPlan::new(0, 1500, 20).collect();
+        assert_eq!(plan, [FragmentMeta { offset_octets: 0, len: 0, more: false }]);
+
+        assert_eq!(FragmentPlan::new(100, 27, 20).count(), 0);
+    }
+}
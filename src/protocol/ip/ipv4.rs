@@ -22,29 +22,50 @@ use byteorder::{
 use crate::{
     Result,
     Error,
+    Field,
 };
 use super::Protocol;
+use crate::protocol::ethernet::Frame;
 use crate::checksum;
+use crate::device::ChecksumCapabilities;
+use crate::bytes;
+use crate::hexdump::HexDump;
+use core::fmt;
+use core::str::FromStr;
+use core::convert::TryFrom;
+#[cfg(feature = "alloc")]
+use alloc::{
+    vec,
+    vec::Vec,
+};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Address(pub [u8; 4]);
 
 impl Address {
     pub const UNSPECIFIED:           Address = Address([0x00; 4]);
     pub const BROADCAST:             Address = Address([0xFF; 4]);
-    pub const MUILTCAST_ALL_SYSTEMS: Address = Address([224, 0, 0, 1]);
-    pub const MUILICAST_ALL_ROUTERS: Address = Address([224, 0, 0, 2]);
+    pub const MULTICAST_ALL_SYSTEMS: Address = Address([224, 0, 0, 1]);
+    pub const MULTICAST_ALL_ROUTERS: Address = Address([224, 0, 0, 2]);
 
     pub fn new(a0: u8, a1: u8, a2: u8, a3: u8) -> Self {
         Address([a0, a1, a2, a3])
     }
 
+    // Panics unless `data` is exactly 4 bytes long; see `try_from_bytes`.
     pub fn from_bytes(data: &[u8]) -> Self {
         let mut bytes = [0; 4];
         bytes.copy_from_slice(data);
         Address(bytes)
     }
 
+    pub fn try_from_bytes(data: &[u8]) -> Result<Self> {
+        if data.len() != 4 {
+            return Err(Error::Malformed);
+        }
+        Ok(Self::from_bytes(data))
+    }
+
     pub fn as_bytes(&self) -> &[u8] {
         &self.0
     }
@@ -57,8 +78,14 @@ impl Address {
         *self == Self::UNSPECIFIED
     }
 
+    // 224.0.0.0/4
     pub fn is_multicast(&self) -> bool {
-        self.0[0] == 224
+        self.0[0] & 0xF0 == 0xE0
+    }
+
+    // 224.0.0.0/24, never forwarded by routers (RFC 5771)
+    pub fn is_local_network_multicast(&self) -> bool {
+        self.0[0..3] == [224, 0, 0]
     }
 
     pub fn is_link_local(&self) -> bool {
@@ -74,6 +101,292 @@ impl Address {
         !self.is_multicast() &&
         !self.is_unspecified()
     }
+
+    // RFC 1918
+    pub fn is_private(&self) -> bool {
+        match self.0 {
+            [10, ..] => true,
+            [172, b, ..] => b & 0xF0 == 16,
+            [192, 168, ..] => true,
+            _ => false,
+        }
+    }
+
+    // RFC 5737 TEST-NET-1, TEST-NET-2 and TEST-NET-3
+    pub fn is_documentation(&self) -> bool {
+        matches!(self.0, [192, 0, 2, _] | [198, 51, 100, _] | [203, 0, 113, _])
+    }
+
+    // RFC 6598 carrier-grade NAT space
+    pub fn is_shared(&self) -> bool {
+        self.0[0] == 100 && self.0[1] & 0xC0 == 64
+    }
+}
+
+impl TryFrom<&[u8]> for Address {
+    type Error = Error;
+
+    fn try_from(data: &[u8]) -> Result<Self> {
+        Self::try_from_bytes(data)
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bytes = self.0;
+        write!(f, "{}.{}.{}.{}", bytes[0], bytes[1], bytes[2], bytes[3])
+    }
+}
+
+impl FromStr for Address {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Address> {
+        let mut bytes = [0; 4];
+        let mut parts = s.split('.');
+        for byte in bytes.iter_mut() {
+            let part = parts.next().ok_or(Error::Malformed)?;
+            // reject signs, which u8::from_str would accept
+            if !part.bytes().all(|c| c.is_ascii_digit()) {
+                return Err(Error::Malformed);
+            }
+            *byte = part.parse().map_err(|_| Error::Malformed)?;
+        }
+        if parts.next().is_some() {
+            return Err(Error::Malformed);
+        }
+        Ok(Address(bytes))
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::net::Ipv4Addr> for Address {
+    fn from(addr: std::net::Ipv4Addr) -> Self {
+        Address(addr.octets())
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Address> for std::net::Ipv4Addr {
+    fn from(addr: Address) -> Self {
+        let bytes = addr.0;
+        std::net::Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3])
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Address {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Address {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Address;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an IPv4 address in dotted-quad form")
+            }
+
+            fn visit_str<E>(self, value: &str) -> core::result::Result<Address, E>
+            where
+                E: serde::de::Error,
+            {
+                value.parse().map_err(|_| {
+                    E::invalid_value(serde::de::Unexpected::Str(value), &self)
+                })
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
+
+// Explicit Congestion Notification codepoints (RFC 3168)
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ecn {
+    NotEct                = 0b00,
+    Ect1                  = 0b01,
+    Ect0                  = 0b10,
+    CongestionEncountered = 0b11,
+}
+
+impl From<u8> for Ecn {
+    fn from(val: u8) -> Self {
+        match val & 0x03 {
+            0b00 => Self::NotEct,
+            0b01 => Self::Ect1,
+            0b10 => Self::Ect0,
+            _    => Self::CongestionEncountered,
+        }
+    }
+}
+
+impl From<Ecn> for u8 {
+    fn from(ecn: Ecn) -> Self {
+        ecn as u8
+    }
+}
+
+// A 6-bit Differentiated Services codepoint (RFC 2474)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DiffServ(u8);
+
+impl DiffServ {
+    pub const CS0:  DiffServ = DiffServ(0);
+    pub const CS1:  DiffServ = DiffServ(8);
+    pub const CS2:  DiffServ = DiffServ(16);
+    pub const CS3:  DiffServ = DiffServ(24);
+    pub const CS4:  DiffServ = DiffServ(32);
+    pub const CS5:  DiffServ = DiffServ(40);
+    pub const CS6:  DiffServ = DiffServ(48);
+    pub const CS7:  DiffServ = DiffServ(56);
+    pub const AF11: DiffServ = DiffServ(10);
+    pub const AF12: DiffServ = DiffServ(12);
+    pub const AF13: DiffServ = DiffServ(14);
+    pub const AF21: DiffServ = DiffServ(18);
+    pub const AF22: DiffServ = DiffServ(20);
+    pub const AF23: DiffServ = DiffServ(22);
+    pub const AF31: DiffServ = DiffServ(26);
+    pub const AF32: DiffServ = DiffServ(28);
+    pub const AF33: DiffServ = DiffServ(30);
+    pub const AF41: DiffServ = DiffServ(34);
+    pub const AF42: DiffServ = DiffServ(36);
+    pub const AF43: DiffServ = DiffServ(38);
+    pub const EF:   DiffServ = DiffServ(46);
+
+    pub fn new(value: u8) -> Result<DiffServ> {
+        if value > 0x3F {
+            return Err(Error::Illegal);
+        }
+        Ok(DiffServ(value))
+    }
+
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+}
+
+// The per-hop behavior a DSCP selects: default forwarding (RFC 2474),
+// expedited forwarding (RFC 3246), assured forwarding with a class of
+// 1..=4 and a drop precedence of 1..=3 (RFC 2597), or a class selector
+// 1..=7 compatible with the old IP precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiffServClass {
+    Default,
+    ExpeditedForwarding,
+    AssuredForwarding(u8, u8),
+    ClassSelector(u8),
+    Unknown(u8),
+}
+
+impl DiffServClass {
+    pub fn from_dscp(dscp: u8) -> DiffServClass {
+        let dscp = dscp & 0x3F;
+        let (class, low) = (dscp >> 3, dscp & 0x07);
+        match (class, low) {
+            (0, 0) => Self::Default,
+            _ if dscp == DiffServ::EF.value() => Self::ExpeditedForwarding,
+            (_, 0) => Self::ClassSelector(class),
+            (1..=4, 2) | (1..=4, 4) | (1..=4, 6) => Self::AssuredForwarding(class, low >> 1),
+            _ => Self::Unknown(dscp),
+        }
+    }
+
+    pub fn to_dscp(self) -> u8 {
+        match self {
+            Self::Default => 0,
+            Self::ExpeditedForwarding => DiffServ::EF.value(),
+            Self::AssuredForwarding(class, drop) => ((class << 3) | (drop << 1)) & 0x3F,
+            Self::ClassSelector(class) => (class << 3) & 0x3F,
+            Self::Unknown(dscp) => dscp & 0x3F,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Cidr {
+    address: Address,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    pub fn new(address: Address, prefix_len: u8) -> Result<Cidr> {
+        if prefix_len > 32 {
+            return Err(Error::Illegal);
+        }
+        Ok(Cidr { address, prefix_len })
+    }
+
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    fn mask(&self) -> u32 {
+        // shifting a u32 by 32 overflows, so /0 is handled on its own
+        match self.prefix_len {
+            0 => 0,
+            len => !0u32 << (32 - len)
+        }
+    }
+
+    pub fn netmask(&self) -> Address {
+        let mut bytes = [0; 4];
+        NetworkEndian::write_u32(&mut bytes, self.mask());
+        Address(bytes)
+    }
+
+    pub fn network(&self) -> Address {
+        let raw = NetworkEndian::read_u32(self.address.as_bytes());
+        let mut bytes = [0; 4];
+        NetworkEndian::write_u32(&mut bytes, raw & self.mask());
+        Address(bytes)
+    }
+
+    pub fn broadcast(&self) -> Address {
+        let raw = NetworkEndian::read_u32(self.address.as_bytes());
+        let mut bytes = [0; 4];
+        NetworkEndian::write_u32(&mut bytes, raw | !self.mask());
+        Address(bytes)
+    }
+
+    pub fn contains_addr(&self, addr: &Address) -> bool {
+        let network = NetworkEndian::read_u32(self.address.as_bytes());
+        let raw = NetworkEndian::read_u32(addr.as_bytes());
+        (network ^ raw) & self.mask() == 0
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_len)
+    }
+}
+
+// Identifies the datagram a fragment belongs to; see Packet::fragment_key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FragmentKey(pub (Address, Address, u8, u16));
+
+impl<T: AsRef<[u8]>> From<&Packet<T>> for FragmentKey {
+    fn from(packet: &Packet<T>) -> Self {
+        FragmentKey(packet.fragment_key())
+    }
 }
 
 mod field {
@@ -89,6 +402,11 @@ mod field {
     pub const CHECKSUM: Field = 10..12;
     pub const SRC_ADDR: Field = 12..16;
     pub const DST_ADDR: Field = 16..20;
+
+    pub const FLG_RESERVED: u16 = 0x8000;
+    pub const FLG_DF:       u16 = 0x4000;
+    pub const FLG_MF:       u16 = 0x2000;
+    pub const OFF_MASK:     u16 = 0x1FFF;
 }
 
 pub struct Packet<T: AsRef<[u8]>> {
@@ -110,6 +428,8 @@ impl<T: AsRef<[u8]>> Packet<T> {
         let len = self.buffer.as_ref().len();
         if len < field::DST_ADDR.end {
             Err(Error::Truncated)
+        } else if self.version() != 4 || (self.header_len() as usize) < field::DST_ADDR.end {
+            Err(Error::Malformed)
         } else if len < self.header_len() as usize {
             Err(Error::Truncated)
         } else if self.header_len() as u16 > self.total_len() {
@@ -126,85 +446,236 @@ impl<T: AsRef<[u8]>> Packet<T> {
     }
 
     pub fn version(&self) -> u8 {
+        bytes::checked(self.version_checked())
+    }
+
+    pub fn version_checked(&self) -> Result<u8> {
         let data = self.buffer.as_ref();
-        data[field::VER_IHL] >> 4
+        bytes::get_u8(data, field::VER_IHL).map(|value| value >> 4)
     }
 
     // Internet Header Length is the length of the internet header in 32
     // bit words
     pub fn header_len(&self) -> u8 {
+        bytes::checked(self.header_len_checked())
+    }
+
+    pub fn header_len_checked(&self) -> Result<u8> {
         let data = self.buffer.as_ref();
-        (data[field::VER_IHL] & 0x0F) << 2
+        bytes::get_u8(data, field::VER_IHL).map(|value| (value & 0x0F) << 2)
     }
 
     pub fn dscp(&self) -> u8 {
         let data = self.buffer.as_ref();
-        data[field::DSCP_ECN] >> 2
+        bytes::checked(bytes::get_u8(data, field::DSCP_ECN)) >> 2
     }
 
     pub fn ecn(&self) -> u8 {
         let data = self.buffer.as_ref();
-        data[field::DSCP_ECN] & 0x03
+        bytes::checked(bytes::get_u8(data, field::DSCP_ECN)) & 0x03
+    }
+
+    pub fn ecn_typed(&self) -> Ecn {
+        self.ecn().into()
+    }
+
+    pub fn diff_serv(&self) -> DiffServ {
+        DiffServ(self.dscp())
+    }
+
+    pub fn diffserv_class(&self) -> DiffServClass {
+        DiffServClass::from_dscp(self.dscp())
     }
 
     pub fn total_len(&self) -> u16 {
+        bytes::checked(self.total_len_checked())
+    }
+
+    pub fn total_len_checked(&self) -> Result<u16> {
         let data = self.buffer.as_ref();
-        NetworkEndian::read_u16(&data[field::LENGTH])
+        bytes::get_u16(data, field::LENGTH)
     }
 
     pub fn ident(&self) -> u16 {
+        bytes::checked(self.ident_checked())
+    }
+
+    pub fn ident_checked(&self) -> Result<u16> {
         let data = self.buffer.as_ref();
-        NetworkEndian::read_u16(&data[field::IDENT])
+        bytes::get_u16(data, field::IDENT)
     }
 
-    pub fn dont_frag(&self) -> bool {
+    // The 3 flag bits and the 13-bit fragment offset as they are on the wire.
+    pub fn flags_and_offset_raw(&self) -> u16 {
+        bytes::checked(self.flags_and_offset_raw_checked())
+    }
+
+    pub fn flags_and_offset_raw_checked(&self) -> Result<u16> {
         let data = self.buffer.as_ref();
-        NetworkEndian::read_u16(&data[field::FLG_OFF]) & 0x4000 != 0
+        bytes::get_u16(data, field::FLG_OFF)
+    }
+
+    pub fn dont_frag(&self) -> bool {
+        self.flags_and_offset_raw() & field::FLG_DF != 0
     }
 
     pub fn more_frags(&self) -> bool {
-        let data = self.buffer.as_ref();
-        NetworkEndian::read_u16(&data[field::FLG_OFF]) & 0x2000 != 0
+        self.flags_and_offset_raw() & field::FLG_MF != 0
     }
 
     // The fragment offset is measured in units of 8 octets (64 bits).
     pub fn frag_offset(&self) -> u16 {
-        let data = self.buffer.as_ref();
-        // 0x0001_1111_1111_1111 = 0x1FFF
-        NetworkEndian::read_u16(&data[field::FLG_OFF]) << 3
+        (self.flags_and_offset_raw() & field::OFF_MASK) << 3
+    }
+
+    // (source, destination, protocol, identification): the fields that
+    // fragments of one datagram have in common (RFC 791).
+    pub fn fragment_key(&self) -> (Address, Address, u8, u16) {
+        (self.src_addr(), self.dst_addr(), self.protocol_raw(), self.ident())
     }
 
     pub fn hop_limit(&self) -> u8 {
+        bytes::checked(self.hop_limit_checked())
+    }
+
+    pub fn hop_limit_checked(&self) -> Result<u8> {
         let data = self.buffer.as_ref();
-        data[field::TTL]    
+        bytes::get_u8(data, field::TTL)
     }
 
     pub fn protocol(&self) -> Protocol {
+        bytes::checked(self.protocol_checked())
+    }
+
+    pub fn protocol_checked(&self) -> Result<Protocol> {
+        let data = self.buffer.as_ref();
+        bytes::get_u8(data, field::PROTOCOL).map(Protocol::from)
+    }
+
+    pub fn protocol_raw(&self) -> u8 {
         let data = self.buffer.as_ref();
-        data[field::PROTOCOL].into()
+        bytes::checked(bytes::get_u8(data, field::PROTOCOL))
     }
 
     pub fn checksum(&self) -> u16 {
+        bytes::checked(self.checksum_checked())
+    }
+
+    pub fn checksum_checked(&self) -> Result<u16> {
         let data = self.buffer.as_ref();
-        NetworkEndian::read_u16(&data[field::CHECKSUM])
+        bytes::get_u16(data, field::CHECKSUM)
     }
 
     pub fn src_addr(&self) -> Address {
+        bytes::checked(self.src_addr_checked())
+    }
+
+    pub fn src_addr_checked(&self) -> Result<Address> {
         let data = self.buffer.as_ref();
-        Address::from_bytes(&data[field::SRC_ADDR])
+        bytes::get_slice(data, field::SRC_ADDR).map(Address::from_bytes)
     }
 
     pub fn dst_addr(&self) -> Address {
+        bytes::checked(self.dst_addr_checked())
+    }
+
+    pub fn dst_addr_checked(&self) -> Result<Address> {
+        let data = self.buffer.as_ref();
+        bytes::get_slice(data, field::DST_ADDR).map(Address::from_bytes)
+    }
+
+    pub fn options(&self) -> &[u8] {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_slice(data, field::DST_ADDR.end..self.header_len() as usize))
+    }
+
+    // Whether the header carries an option this stack does not act on,
+    // e.g. a source route, which would be misrouted if ignored. A
+    // malformed option list counts as unsupported as well.
+    pub fn has_unsupported_options(&self) -> bool {
+        OptionsIter::new(self.options()).any(|option| match option {
+            Ok((kind, _)) => !matches!(
+                kind,
+                option::END_OF_LIST | option::NO_OPERATION | option::ROUTER_ALERT
+            ),
+            Err(_) => true,
+        })
+    }
+
+    // The first Timestamp option in the header, if any.
+    pub fn timestamp_option(&self) -> Result<Option<TimestampOption<'_>>> {
+        for option in OptionsIter::new(self.options()) {
+            let (kind, value) = option?;
+            if kind == option::TIMESTAMP {
+                return TimestampOption::new_checked(value).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    // header_len()..total_len(), clamped to the buffer so that
+    // inconsistent length fields never slice out of bounds
+    fn payload_range(&self) -> Field {
+        let len = self.buffer.as_ref().len();
+        let start = (self.header_len() as usize).min(len);
+        let end = (self.total_len() as usize).clamp(start, len);
+        start..end
+    }
+
+    // The payload as delimited by the header; for a packet that passed
+    // new_checked this is exactly header_len()..total_len().
+    pub fn payload(&self) -> &[u8] {
+        let range = self.payload_range();
         let data = self.buffer.as_ref();
-        Address::from_bytes(&data[field::DST_ADDR])
+        bytes::checked(bytes::get_slice(data, range))
+    }
+
+    // Copy the header and the first 8 bytes of the payload into `out`,
+    // the part of a datagram ICMP error messages quote (RFC 792).
+    // Returns the number of bytes copied, or Error::Exhausted if `out`
+    // is too small.
+    pub fn quote(&self, out: &mut [u8]) -> Result<usize> {
+        let range = self.payload_range();
+        let len = range.start + (range.end - range.start).min(8);
+        if out.len() < len {
+            return Err(Error::Exhausted);
+        }
+        let data = self.buffer.as_ref();
+        out[..len].copy_from_slice(bytes::checked(bytes::get_slice(data, ..len)));
+        Ok(len)
     }
 
     pub fn verify_checksum(&self) -> bool {
         let data = self.buffer.as_ref();
         checksum::data(
-            &data[..self.header_len() as usize]
+            bytes::checked(bytes::get_slice(data, ..self.header_len() as usize))
         ) == !0
     }
+
+    // Always true when the NIC verifies the checksum.
+    pub fn verify_checksum_with(&self, caps: &ChecksumCapabilities) -> bool {
+        !caps.ipv4.rx() || self.verify_checksum()
+    }
+}
+
+impl<'a> Packet<&'a [u8]> {
+    // The datagram carried by a frame, sliced by its total length since
+    // the payload of a short frame includes the Ethernet padding.
+    pub fn from_frame_payload(frame: &'a Frame<&[u8]>) -> Result<Packet<&'a [u8]>> {
+        Self::from_padded(frame.payload())
+    }
+
+    // Like from_frame_payload, for a frame payload already split off.
+    pub fn from_padded(payload: &'a [u8]) -> Result<Packet<&'a [u8]>> {
+        if payload.len() < field::DST_ADDR.end {
+            return Err(Error::Truncated);
+        }
+        let total_len = Packet::new_unchecked(payload).total_len() as usize;
+        if payload.len() < total_len {
+            return Err(Error::Truncated);
+        }
+        Packet::new_checked(&payload[..total_len.max(field::DST_ADDR.end)])
+    }
 }
 
 impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
@@ -220,6 +691,15 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
         data[field::VER_IHL] = new;
     }
 
+    // Set the IHL in 32-bit words, which must be between 5 and 15.
+    pub fn set_header_len_words(&mut self, ihl: u8) -> Result<()> {
+        if !(5..=15).contains(&ihl) {
+            return Err(Error::Illegal);
+        }
+        self.set_header_len(ihl << 2);
+        Ok(())
+    }
+
     pub fn set_dscp(&mut self, value: u8) {
         let data = self.buffer.as_mut();
         let new = (data[field::DSCP_ECN] & !0xFC) | (value << 2);
@@ -232,6 +712,18 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
         data[field::DSCP_ECN] = new;
     }
 
+    pub fn set_ecn_typed(&mut self, ecn: Ecn) {
+        self.set_ecn(ecn.into())
+    }
+
+    pub fn set_diff_serv(&mut self, dscp: DiffServ) {
+        self.set_dscp(dscp.value())
+    }
+
+    pub fn set_diffserv_class(&mut self, class: DiffServClass) {
+        self.set_dscp(class.to_dscp())
+    }
+
     pub fn set_total_len(&mut self, len: u16) {
         let data = self.buffer.as_mut();
         NetworkEndian::write_u16(&mut data[field::LENGTH], len);
@@ -242,33 +734,33 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
         NetworkEndian::write_u16(&mut data[field::IDENT], value);
     }
 
-    pub fn clear_flags(&mut self) {
+    pub fn set_flags_and_offset_raw(&mut self, value: u16) {
         let data = self.buffer.as_mut();
-        let raw = NetworkEndian::read_u16(&data[field::FLG_OFF]);
-        let raw = raw & !0xE000;
-        NetworkEndian::write_u16(&mut data[field::FLG_OFF], raw);
+        NetworkEndian::write_u16(&mut data[field::FLG_OFF], value);
+    }
+
+    pub fn clear_flags(&mut self) {
+        let raw = self.flags_and_offset_raw();
+        self.set_flags_and_offset_raw(raw & field::OFF_MASK);
     }
 
     pub fn set_dont_frag(&mut self, value: bool) {
-        let data = self.buffer.as_mut();
-        let raw = NetworkEndian::read_u16(&data[field::FLG_OFF]);
-        let raw = if value { raw | 0x4000 } else { raw & !0x4000 };
-        NetworkEndian::write_u16(&mut data[field::FLG_OFF], raw);
+        let raw = self.flags_and_offset_raw();
+        let raw = if value { raw | field::FLG_DF } else { raw & !field::FLG_DF };
+        self.set_flags_and_offset_raw(raw);
     }
 
     pub fn set_more_frags(&mut self, value: bool) {
-        let data = self.buffer.as_mut();
-        let raw = NetworkEndian::read_u16(&data[field::FLG_OFF]);
-        let raw = if value { raw | 0x2000 } else { raw & !0x2000 };
-        NetworkEndian::write_u16(&mut data[field::FLG_OFF], raw);
+        let raw = self.flags_and_offset_raw();
+        let raw = if value { raw | field::FLG_MF } else { raw & !field::FLG_MF };
+        self.set_flags_and_offset_raw(raw);
     }
 
     // The fragment offset is measured in units of 8 octets (64 bits).
     pub fn set_frag_offset(&mut self, value: u16) {
-        let data = self.buffer.as_mut();
-        let raw = NetworkEndian::read_u16(&data[field::FLG_OFF]);
-        let raw = (raw & 0xE000) | (value >> 3);
-        NetworkEndian::write_u16(&mut data[field::FLG_OFF], raw);
+        let raw = self.flags_and_offset_raw();
+        let raw = (raw & !field::OFF_MASK) | ((value >> 3) & field::OFF_MASK);
+        self.set_flags_and_offset_raw(raw);
     }
 
     pub fn set_hop_limit(&mut self, value: u8) {
@@ -276,6 +768,38 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
         data[field::TTL] = value;
     }
 
+    // Set the TTL and patch the header checksum incrementally,
+    // instead of recomputing it over the whole header.
+    pub fn set_hop_limit_update(&mut self, value: u8) {
+        // TTL shares its 16-bit word with the protocol field
+        let word = field::TTL..field::PROTOCOL + 1;
+        let data = self.buffer.as_mut();
+        let old_word = NetworkEndian::read_u16(&data[word.clone()]);
+        data[field::TTL] = value;
+        let new_word = NetworkEndian::read_u16(&data[word]);
+        let old_checksum = NetworkEndian::read_u16(&data[field::CHECKSUM]);
+        NetworkEndian::write_u16(
+            &mut data[field::CHECKSUM],
+            checksum::update(old_checksum, old_word, new_word)
+        );
+    }
+
+    // Decrement the TTL before forwarding, patching the checksum rather
+    // than recomputing it. This is the only header change a router makes;
+    // DSCP, ECN and the identification are kept. A packet arriving with a
+    // TTL of 0 or 1 must not be forwarded: Error::Illegal is returned and
+    // the packet is left untouched, and the caller should answer with an
+    // ICMP Time Exceeded message instead.
+    pub fn decrement_hop_limit(&mut self) -> Result<()> {
+        match self.hop_limit() {
+            0 | 1 => Err(Error::Illegal),
+            ttl => {
+                self.set_hop_limit_update(ttl - 1);
+                Ok(())
+            }
+        }
+    }
+
     pub fn set_protocol(&mut self, protocol: Protocol) {
         let data = self.buffer.as_mut();
         data[field::PROTOCOL] = protocol.into();
@@ -296,24 +820,1223 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
         data[field::DST_ADDR].copy_from_slice(addr.as_bytes());
     }
 
+    // Make the header consistent for `payload_len` bytes of payload:
+    // total length, reserved flag bit, and checksum, in that order.
+    // Error::Exhausted if the datagram would exceed 65535 octets.
+    pub fn finalize(&mut self, payload_len: usize) -> Result<()> {
+        let total_len = self.header_len() as usize + payload_len;
+        if total_len > u16::MAX as usize {
+            return Err(Error::Exhausted);
+        }
+        self.set_total_len(total_len as u16);
+        let raw = self.flags_and_offset_raw();
+        self.set_flags_and_offset_raw(raw & !field::FLG_RESERVED);
+        self.fill_checksum();
+        Ok(())
+    }
+
+    // Exchange the source and destination, e.g. to send a reply. The
+    // checksum sums both addresses either way, so it stays valid.
+    pub fn swap_addresses(&mut self) {
+        let data = self.buffer.as_mut();
+        let (src, dst) = data[field::SRC_ADDR.start..field::DST_ADDR.end].split_at_mut(4);
+        src.swap_with_slice(dst);
+    }
+
+    // Insert a Router Alert option (RFC 2113) after a 20-byte header,
+    // moving the payload up by 4 octets, and update the header length,
+    // total length and checksum. IGMP and RSVP messages need it.
+    pub fn set_router_alert(&mut self) -> Result<()> {
+        const OPTION: [u8; 4] = [option::ROUTER_ALERT, 4, 0, 0];
+
+        let header_len = field::DST_ADDR.end;
+        if self.header_len() as usize != header_len {
+            return Err(Error::Illegal);
+        }
+        let total_len = (self.total_len() as usize).max(header_len);
+        let data = self.buffer.as_mut();
+        if data.len() < total_len + OPTION.len() || total_len + OPTION.len() > u16::MAX as usize {
+            return Err(Error::Exhausted);
+        }
+        data.copy_within(header_len..total_len, header_len + OPTION.len());
+        data[header_len..header_len + OPTION.len()].copy_from_slice(&OPTION);
+
+        self.set_header_len((header_len + OPTION.len()) as u8);
+        self.set_total_len((total_len + OPTION.len()) as u16);
+        self.fill_checksum();
+        Ok(())
+    }
+
+    // Append a Timestamp option with as many zeroed slots as still fit
+    // in a 60-byte header, moving the payload up like set_router_alert.
+    // Existing options must not be terminated by End of Option List, or
+    // the new option would be hidden behind it.
+    pub fn add_timestamp_option(&mut self, flags: u8, overflow: u8) -> Result<()> {
+        const MAX_HEADER_LEN: usize = 60;
+        const PREFIX_LEN: usize = 4;
+
+        let slot_len = match flags {
+            option::timestamp::TIMESTAMPS_ONLY => 4,
+            option::timestamp::WITH_ADDRESSES | option::timestamp::PRESPECIFIED => 8,
+            _ => return Err(Error::Illegal),
+        };
+        let header_len = self.header_len() as usize;
+        if overflow > 0x0f || header_len < field::DST_ADDR.end {
+            return Err(Error::Illegal);
+        }
+        let terminated = OptionsIter::new(self.options()).any(|option| match option {
+            Ok((kind, _)) => kind == option::END_OF_LIST,
+            Err(_) => true,
+        });
+        if terminated {
+            return Err(Error::Illegal);
+        }
+
+        let slots = MAX_HEADER_LEN.saturating_sub(header_len + PREFIX_LEN) / slot_len;
+        if slots == 0 {
+            return Err(Error::Exhausted);
+        }
+        let option_len = PREFIX_LEN + slots * slot_len;
+        let total_len = (self.total_len() as usize).max(header_len);
+        let data = self.buffer.as_mut();
+        if data.len() < total_len + option_len || total_len + option_len > u16::MAX as usize {
+            return Err(Error::Exhausted);
+        }
+        data.copy_within(header_len..total_len, header_len + option_len);
+        let option = &mut data[header_len..header_len + option_len];
+        option[0] = option::TIMESTAMP;
+        option[1] = option_len as u8;
+        // the pointer is one-based from the option type
+        option[2] = PREFIX_LEN as u8 + 1;
+        option[3] = (overflow << 4) | flags;
+        for byte in option[PREFIX_LEN..].iter_mut() {
+            *byte = 0;
+        }
+
+        self.set_header_len((header_len + option_len) as u8);
+        self.set_total_len((total_len + option_len) as u16);
+        self.fill_checksum();
+        Ok(())
+    }
+
     pub fn fill_checksum(&mut self) {
         self.set_checksum(0);
         let checksum = {
             let data = self.buffer.as_ref();
-            !checksum::data(data)
+            !checksum::data(&data[..self.header_len() as usize])
         };
         self.set_checksum(checksum);
     }
+
+    // Leaves the checksum field as it is when the NIC fills it.
+    pub fn fill_checksum_with(&mut self, caps: &ChecksumCapabilities) {
+        if caps.ipv4.tx() {
+            self.fill_checksum();
+        }
+    }
     
+    // Clamped to the buffer like payload().
     pub fn payload_mut(&mut self) -> &mut [u8] {
-        let range = self.header_len() as usize..self.total_len() as usize;
+        let range = self.payload_range();
         let data = self.buffer.as_mut();
         &mut data[range]
     }
 } 
 
+#[cfg(feature = "alloc")]
+impl<T: AsRef<[u8]>> Packet<T> {
+    // Copy the packet into an owned buffer, e.g. to queue it past the
+    // lifetime of the receive buffer. Link-layer padding after total_len
+    // is left behind.
+    pub fn into_owned(self) -> Packet<Vec<u8>> {
+        let data = self.buffer.as_ref();
+        let len = (self.total_len() as usize).min(data.len());
+        Packet::new_unchecked(data[..len].to_vec())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Packet<Vec<u8>> {
+    // Allocate a packet with room for a 20-byte header and the payload,
+    // with version, header length and total length already filled in.
+    // Returns Error::Exhausted if the total length does not fit in 16 bits.
+    pub fn builder(payload_len: usize) -> Result<Packet<Vec<u8>>> {
+        let header_len = field::DST_ADDR.end;
+        let total_len = header_len + payload_len;
+        if total_len > u16::MAX as usize {
+            return Err(Error::Exhausted);
+        }
+        let mut packet = Packet::new_unchecked(vec![0; total_len]);
+        packet.set_version(4);
+        packet.set_header_len(header_len as u8);
+        packet.set_total_len(total_len as u16);
+        Ok(packet)
+    }
+}
+
+// A parsed IPv4 header, without the fields a host stack does not
+// need to keep. Options are ignored when parsing and never emitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Repr {
+    pub src_addr: Address,
+    pub dst_addr: Address,
+    pub protocol: Protocol,
+    pub payload_len: usize,
+    pub hop_limit: u8,
+}
+
+impl Repr {
+    pub fn parse(packet: &Packet<&[u8]>) -> Result<Repr> {
+        packet.check_len()?;
+        if !packet.verify_checksum() {
+            return Err(Error::Checksum);
+        }
+        if packet.more_frags() || packet.frag_offset() != 0 {
+            return Err(Error::Fragmented);
+        }
+        Ok(Repr {
+            src_addr: packet.src_addr(),
+            dst_addr: packet.dst_addr(),
+            protocol: packet.protocol(),
+            payload_len: packet.total_len() as usize - packet.header_len() as usize,
+            hop_limit: packet.hop_limit(),
+        })
+    }
+
+    pub fn header_len(&self) -> usize {
+        field::DST_ADDR.end
+    }
+
+    pub fn buffer_len(&self) -> usize {
+        self.header_len() + self.payload_len
+    }
+
+    // Write the header, with a zero ident and DF set, and fill the
+    // checksum; the payload is left to the caller.
+    pub fn emit<T: AsRef<[u8]> + AsMut<[u8]>>(&self, packet: &mut Packet<T>) {
+        packet.set_version(4);
+        packet.set_header_len(self.header_len() as u8);
+        packet.set_dscp(0);
+        packet.set_ecn(0);
+        packet.set_total_len(self.buffer_len() as u16);
+        packet.set_ident(0);
+        packet.set_flags_and_offset_raw(field::FLG_DF);
+        packet.set_hop_limit(self.hop_limit);
+        packet.set_protocol(self.protocol);
+        packet.set_src_addr(self.src_addr);
+        packet.set_dst_addr(self.dst_addr);
+        packet.fill_checksum();
+    }
+}
+
+// Whether a packet is too big for `mtu` and must not be fragmented,
+// which calls for an ICMP "fragmentation needed" back to the sender.
+pub fn needs_fragmentation(packet: &Packet<&[u8]>, mtu: usize) -> bool {
+    packet.total_len() as usize > mtu && packet.dont_frag()
+}
+
+// A problem found by Packet::validate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderIssue {
+    // the buffer cannot hold even a minimal header; nothing else is checked
+    Truncated,
+    BadVersion,
+    ShortIhl,
+    HeaderLenExceedsBuffer,
+    ChecksumMismatch,
+    TotalLenExceedsBuffer,
+    FragNotSupported,
+}
+
+#[cfg(feature = "alloc")]
+impl<T: AsRef<[u8]>> Packet<T> {
+    // Report every header problem at once, for diagnostics; check_len and
+    // verify_checksum stay the way to accept or drop a packet.
+    pub fn validate(&self) -> Vec<HeaderIssue> {
+        let mut issues = Vec::new();
+        let len = self.buffer.as_ref().len();
+        if len < field::DST_ADDR.end {
+            issues.push(HeaderIssue::Truncated);
+            return issues;
+        }
+        if self.version() != 4 {
+            issues.push(HeaderIssue::BadVersion);
+        }
+        let header_len = self.header_len() as usize;
+        if header_len < field::DST_ADDR.end {
+            issues.push(HeaderIssue::ShortIhl);
+        } else if header_len > len {
+            issues.push(HeaderIssue::HeaderLenExceedsBuffer);
+        } else if !self.verify_checksum() {
+            issues.push(HeaderIssue::ChecksumMismatch);
+        }
+        if self.total_len() as usize > len {
+            issues.push(HeaderIssue::TotalLenExceedsBuffer);
+        }
+        if self.more_frags() || self.frag_offset() != 0 {
+            issues.push(HeaderIssue::FragNotSupported);
+        }
+        issues
+    }
+}
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    pub fn hexdump(&self) -> impl fmt::Display + '_ {
+        HexDump(self.buffer.as_ref())
+    }
+}
+
 impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
     fn as_ref(&self) -> &[u8] {
         self.buffer.as_ref()
     }
 }
+
+pub mod option {
+    pub const END_OF_LIST:  u8 = 0;
+    pub const NO_OPERATION: u8 = 1;
+    pub const RECORD_ROUTE: u8 = 7;
+    pub const LOOSE_SOURCE_ROUTE:  u8 = 131;
+    pub const STRICT_SOURCE_ROUTE: u8 = 137;
+    pub const ROUTER_ALERT: u8 = 148;
+    pub const TIMESTAMP:    u8 = 68;
+
+    // Flags of the Timestamp option (RFC 791)
+    pub mod timestamp {
+        pub const TIMESTAMPS_ONLY: u8 = 0;
+        pub const WITH_ADDRESSES:  u8 = 1;
+        pub const PRESPECIFIED:    u8 = 3;
+    }
+}
+
+// Yields (type, data) for each option; data excludes the type and length
+// octets. End of Option List ends the iteration.
+pub struct OptionsIter<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> OptionsIter<'a> {
+    pub fn new(data: &'a [u8]) -> OptionsIter<'a> {
+        OptionsIter { data }
+    }
+}
+
+impl<'a> Iterator for OptionsIter<'a> {
+    type Item = Result<(u8, &'a [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let &kind = self.data.first()?;
+        match kind {
+            option::END_OF_LIST => {
+                self.data = &[];
+                Some(Ok((kind, &[])))
+            }
+            option::NO_OPERATION => {
+                self.data = &self.data[1..];
+                Some(Ok((kind, &[])))
+            }
+            _ => {
+                let len = match self.data.get(1) {
+                    Some(&len) => len as usize,
+                    None => 0,
+                };
+                if len < 2 || len > self.data.len() {
+                    self.data = &[];
+                    return Some(Err(Error::Malformed));
+                }
+                let value = &self.data[2..len];
+                self.data = &self.data[len..];
+                Some(Ok((kind, value)))
+            }
+        }
+    }
+}
+
+// The data of a Timestamp option as yielded by OptionsIter, i.e. starting
+// at the pointer octet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampOption<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> TimestampOption<'a> {
+    pub fn new_checked(data: &'a [u8]) -> Result<TimestampOption<'a>> {
+        if data.len() < 2 {
+            return Err(Error::Truncated);
+        }
+        let option = TimestampOption { data };
+        match option.flags() {
+            option::timestamp::TIMESTAMPS_ONLY
+            | option::timestamp::WITH_ADDRESSES
+            | option::timestamp::PRESPECIFIED => {}
+            _ => return Err(Error::Malformed),
+        }
+        if option.pointer() < 5 || !option.slots().len().is_multiple_of(option.slot_len()) {
+            return Err(Error::Malformed);
+        }
+        Ok(option)
+    }
+
+    // One-based offset of the next free slot from the option type.
+    pub fn pointer(&self) -> u8 {
+        self.data[0]
+    }
+
+    // Number of hops that could not record a timestamp for lack of room.
+    pub fn overflow(&self) -> u8 {
+        self.data[1] >> 4
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.data[1] & 0x0f
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.pointer() as usize > self.data.len() + 2
+    }
+
+    fn slot_len(&self) -> usize {
+        if self.flags() == option::timestamp::TIMESTAMPS_ONLY { 4 } else { 8 }
+    }
+
+    fn slots(&self) -> &'a [u8] {
+        &self.data[2..]
+    }
+
+    // (address, timestamp) for each slot before the pointer. The address
+    // is None for options that record timestamps only.
+    pub fn recorded(&self) -> impl Iterator<Item = (Option<Address>, u32)> + 'a {
+        let slots = self.slots();
+        let recorded = (self.pointer() as usize - 5).min(slots.len());
+        let slot_len = self.slot_len();
+        slots[..recorded].chunks_exact(slot_len).map(move |slot| {
+            if slot_len == 4 {
+                (None, NetworkEndian::read_u32(slot))
+            } else {
+                (Some(Address::from_bytes(&slot[..4])), NetworkEndian::read_u32(&slot[4..]))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        Address,
+        Cidr,
+        Ecn,
+        DiffServ,
+        DiffServClass,
+        FragmentKey,
+        Packet,
+        Repr,
+        OptionsIter,
+        option,
+    };
+    use crate::Error;
+    use crate::protocol::ethernet::Frame;
+    use crate::protocol::ip::Protocol;
+
+    #[test]
+    fn test_address_str() {
+        let addr: Address = "127.0.0.1".parse().unwrap();
+        assert_eq!(addr, Address([127, 0, 0, 1]));
+        assert_eq!(format!("{}", addr), "127.0.0.1");
+
+        let addr: Address = "255.255.255.255".parse().unwrap();
+        assert_eq!(addr, Address::BROADCAST);
+        assert_eq!(format!("{}", addr), "255.255.255.255");
+
+        assert!("".parse::<Address>().is_err());
+        assert!("1.2.3".parse::<Address>().is_err());
+        assert!("1.2.3.4.5".parse::<Address>().is_err());
+        assert!("1.2.3.256".parse::<Address>().is_err());
+        assert!("1.2.+3.4".parse::<Address>().is_err());
+        assert!("1..3.4".parse::<Address>().is_err());
+    }
+
+    #[test]
+    fn test_address_ranges() {
+        for addr in [[10, 1, 2, 3], [172, 16, 0, 1], [172, 31, 255, 255], [192, 168, 0, 1]].iter() {
+            let addr = Address(*addr);
+            assert!(addr.is_private(), "{}", addr);
+            assert!(!addr.is_documentation() && !addr.is_shared());
+        }
+        assert!(!Address([172, 15, 0, 1]).is_private());
+        assert!(!Address([172, 32, 0, 1]).is_private());
+
+        for addr in [[192, 0, 2, 1], [198, 51, 100, 7], [203, 0, 113, 255]].iter() {
+            let addr = Address(*addr);
+            assert!(addr.is_documentation(), "{}", addr);
+            assert!(!addr.is_private() && !addr.is_shared());
+        }
+
+        assert!(Address([100, 64, 0, 1]).is_shared());
+        assert!(Address([100, 127, 255, 254]).is_shared());
+        assert!(!Address([100, 128, 0, 1]).is_shared());
+        assert!(!Address([100, 63, 255, 255]).is_shared());
+
+        let public = Address([8, 8, 8, 8]);
+        assert!(!public.is_private());
+        assert!(!public.is_documentation());
+        assert!(!public.is_shared());
+    }
+
+    #[test]
+    fn test_cidr() {
+        let cidr = Cidr::new(Address([10, 0, 0, 7]), 24).unwrap();
+        assert_eq!(cidr.netmask(), Address([255, 255, 255, 0]));
+        assert_eq!(cidr.network(), Address([10, 0, 0, 0]));
+        assert_eq!(cidr.broadcast(), Address([10, 0, 0, 255]));
+        assert!(cidr.contains_addr(&Address([10, 0, 0, 0])));
+        assert!(cidr.contains_addr(&Address([10, 0, 0, 255])));
+        assert!(!cidr.contains_addr(&Address([10, 0, 1, 0])));
+        assert!(!cidr.contains_addr(&Address([9, 255, 255, 255])));
+
+        let cidr = Cidr::new(Address([10, 0, 0, 0]), 24).unwrap();
+        assert_eq!(format!("{}", cidr), "10.0.0.0/24");
+
+        assert_eq!(Cidr::new(Address([10, 0, 0, 0]), 33), Err(Error::Illegal));
+    }
+
+    #[test]
+    fn test_cidr_edges() {
+        let any = Cidr::new(Address([192, 168, 1, 1]), 0).unwrap();
+        assert_eq!(any.netmask(), Address::UNSPECIFIED);
+        assert_eq!(any.network(), Address::UNSPECIFIED);
+        assert_eq!(any.broadcast(), Address::BROADCAST);
+        assert!(any.contains_addr(&Address([1, 2, 3, 4])));
+        assert!(any.contains_addr(&Address::BROADCAST));
+
+        let point = Cidr::new(Address([192, 168, 1, 1]), 31).unwrap();
+        assert_eq!(point.netmask(), Address([255, 255, 255, 254]));
+        assert_eq!(point.network(), Address([192, 168, 1, 0]));
+        assert_eq!(point.broadcast(), Address([192, 168, 1, 1]));
+        assert!(point.contains_addr(&Address([192, 168, 1, 0])));
+        assert!(!point.contains_addr(&Address([192, 168, 1, 2])));
+
+        let host = Cidr::new(Address([192, 168, 1, 1]), 32).unwrap();
+        assert_eq!(host.netmask(), Address::BROADCAST);
+        assert_eq!(host.network(), Address([192, 168, 1, 1]));
+        assert_eq!(host.broadcast(), Address([192, 168, 1, 1]));
+        assert!(host.contains_addr(&Address([192, 168, 1, 1])));
+        assert!(!host.contains_addr(&Address([192, 168, 1, 0])));
+    }
+
+    // 192.168.0.1 -> 192.168.0.199, UDP
+    static HEADER_BYTES: [u8; 20] = [
+        0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00,
+        0x40, 0x11, 0xb8, 0x61, 0xc0, 0xa8, 0x00, 0x01,
+        0xc0, 0xa8, 0x00, 0xc7,
+    ];
+
+    #[test]
+    fn test_checksum() {
+        let packet = Packet::new_unchecked(&HEADER_BYTES[..]);
+        assert!(packet.verify_checksum());
+        assert_eq!(crate::checksum::data(&HEADER_BYTES), !0);
+
+        let mut bytes = HEADER_BYTES;
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        packet.fill_checksum();
+        assert_eq!(packet.checksum(), 0xb861);
+
+        packet.set_checksum(0);
+        assert_eq!(!crate::checksum::data(packet.as_ref()), 0xb861);
+        assert!(!packet.verify_checksum());
+    }
+
+    #[test]
+    fn test_protocol_raw() {
+        let mut bytes = HEADER_BYTES;
+        bytes[9] = 0x84;
+        let packet = Packet::new_unchecked(&bytes[..]);
+        assert_eq!(packet.protocol_raw(), 0x84);
+        assert_eq!(packet.protocol(), Protocol::Other(0x84));
+
+        let packet = Packet::new_unchecked(&HEADER_BYTES[..]);
+        assert_eq!(packet.protocol_raw(), 0x11);
+        assert_eq!(packet.protocol(), Protocol::UDP);
+    }
+
+    #[test]
+    fn test_ecn_dscp() {
+        let mut bytes = HEADER_BYTES;
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        let codepoints = [
+            (0b00, Ecn::NotEct),
+            (0b01, Ecn::Ect1),
+            (0b10, Ecn::Ect0),
+            (0b11, Ecn::CongestionEncountered),
+        ];
+        for &(raw, ecn) in codepoints.iter() {
+            assert_eq!(Ecn::from(raw), ecn);
+            assert_eq!(u8::from(ecn), raw);
+            packet.set_ecn_typed(ecn);
+            assert_eq!(packet.ecn(), raw);
+            assert_eq!(packet.ecn_typed(), ecn);
+        }
+
+        packet.set_diff_serv(DiffServ::EF);
+        assert_eq!(packet.dscp(), 46);
+        assert_eq!(packet.diff_serv(), DiffServ::EF);
+        assert_eq!(packet.ecn_typed(), Ecn::CongestionEncountered);
+        assert_eq!(packet.as_ref()[1], 0xbb);
+
+        assert_eq!(DiffServ::new(10), Ok(DiffServ::AF11));
+        assert_eq!(DiffServ::new(64), Err(Error::Illegal));
+    }
+
+    #[test]
+    fn test_diffserv_class() {
+        let classes = [
+            (0x00, DiffServClass::Default),
+            (0x2E, DiffServClass::ExpeditedForwarding),
+            (0x1A, DiffServClass::AssuredForwarding(3, 1)),
+            (0x26, DiffServClass::AssuredForwarding(4, 3)),
+            (0x30, DiffServClass::ClassSelector(6)),
+            (0x01, DiffServClass::Unknown(0x01)),
+        ];
+        for &(dscp, class) in classes.iter() {
+            assert_eq!(DiffServClass::from_dscp(dscp), class);
+            assert_eq!(class.to_dscp(), dscp);
+        }
+        assert_eq!(DiffServClass::from_dscp(DiffServ::AF31.value()), DiffServClass::AssuredForwarding(3, 1));
+
+        let mut bytes = HEADER_BYTES;
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        packet.set_ecn_typed(Ecn::Ect0);
+        packet.set_diffserv_class(DiffServClass::AssuredForwarding(3, 1));
+        assert_eq!(packet.dscp(), 0x1A);
+        assert_eq!(packet.diff_serv(), DiffServ::AF31);
+        assert_eq!(packet.ecn_typed(), Ecn::Ect0);
+
+        packet.set_diffserv_class(DiffServClass::ExpeditedForwarding);
+        assert_eq!(packet.as_ref()[1], 0xba);
+        assert_eq!(packet.diffserv_class(), DiffServClass::ExpeditedForwarding);
+    }
+
+    #[test]
+    fn test_fragment_key() {
+        let mut first = HEADER_BYTES;
+        let mut packet = Packet::new_unchecked(&mut first[..]);
+        packet.set_more_frags(true);
+        packet.set_frag_offset(0);
+        let mut second = HEADER_BYTES;
+        let mut packet = Packet::new_unchecked(&mut second[..]);
+        packet.set_more_frags(false);
+        packet.set_frag_offset(1480);
+        packet.set_total_len(40);
+        packet.set_hop_limit(1);
+
+        let first = Packet::new_unchecked(&first[..]);
+        let second = Packet::new_unchecked(&second[..]);
+        assert_eq!(
+            first.fragment_key(),
+            (first.src_addr(), first.dst_addr(), first.protocol_raw(), first.ident())
+        );
+        assert_eq!(first.fragment_key(), second.fragment_key());
+        assert_eq!(FragmentKey::from(&first), FragmentKey::from(&second));
+
+        let mut other = HEADER_BYTES;
+        let mut packet = Packet::new_unchecked(&mut other[..]);
+        packet.set_ident(first.ident().wrapping_add(1));
+        assert_ne!(FragmentKey::from(&packet), FragmentKey::from(&first));
+
+        let mut keys = std::collections::HashSet::new();
+        keys.insert(FragmentKey::from(&first));
+        assert!(keys.contains(&FragmentKey::from(&second)));
+        assert!(!keys.contains(&FragmentKey::from(&packet)));
+    }
+
+    #[test]
+    fn test_quote() {
+        let mut bytes = [0xa5; 20 + 12];
+        bytes[..20].copy_from_slice(&HEADER_BYTES);
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        packet.set_total_len(20 + 12);
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+
+        let mut out = [0; 64];
+        assert_eq!(packet.quote(&mut out), Ok(20 + 8));
+        assert_eq!(&out[..28], &bytes[..28]);
+        assert!(out[28..].iter().all(|&byte| byte == 0));
+
+        let mut out = [0; 27];
+        assert_eq!(packet.quote(&mut out), Err(Error::Exhausted));
+
+        // shorter payloads are quoted whole
+        let mut bytes = HEADER_BYTES;
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        packet.set_total_len(20);
+        let mut out = [0; 20];
+        assert_eq!(Packet::new_unchecked(&bytes[..]).quote(&mut out), Ok(20));
+    }
+
+    #[test]
+    fn test_hexdump() {
+        let packet = Packet::new_unchecked(&HEADER_BYTES[..]);
+        let dump = packet.hexdump().to_string();
+        assert!(dump.starts_with("00000000  45 "));
+        assert_eq!(dump.lines().count(), HEADER_BYTES.len().div_ceil(16));
+    }
+
+    #[test]
+    fn test_check_len() {
+        assert!(Packet::new_checked(&HEADER_BYTES[..]).is_err());
+
+        let mut bytes = HEADER_BYTES;
+        bytes[2..4].copy_from_slice(&[0x00, 0x14]);
+        assert!(Packet::new_checked(&bytes[..]).is_ok());
+        assert_eq!(Packet::new_checked(&bytes[..19]).err(), Some(Error::Truncated));
+
+        bytes[0] = 0x65;
+        assert_eq!(Packet::new_checked(&bytes[..]).err(), Some(Error::Malformed));
+
+        bytes[0] = 0x43;
+        assert_eq!(Packet::new_checked(&bytes[..]).err(), Some(Error::Malformed));
+
+        bytes[0] = 0x46;
+        assert_eq!(Packet::new_checked(&bytes[..]).err(), Some(Error::Truncated));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_validate() {
+        use super::HeaderIssue;
+
+        let mut bytes = HEADER_BYTES;
+        bytes[2..4].copy_from_slice(&[0x00, 0x14]);
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        packet.fill_checksum();
+        assert_eq!(packet.validate(), vec![]);
+
+        packet.set_version(6);
+        assert_eq!(packet.validate(), vec![
+            HeaderIssue::BadVersion,
+            HeaderIssue::ChecksumMismatch,
+        ]);
+
+        packet.set_version(4);
+        packet.set_more_frags(true);
+        packet.set_total_len(40);
+        packet.fill_checksum();
+        assert_eq!(packet.validate(), vec![
+            HeaderIssue::TotalLenExceedsBuffer,
+            HeaderIssue::FragNotSupported,
+        ]);
+
+        packet.set_header_len(12);
+        assert!(packet.validate().contains(&HeaderIssue::ShortIhl));
+        packet.set_header_len(24);
+        assert!(packet.validate().contains(&HeaderIssue::HeaderLenExceedsBuffer));
+
+        assert_eq!(Packet::new_unchecked(&bytes[..10]).validate(), vec![HeaderIssue::Truncated]);
+    }
+
+    #[test]
+    fn test_flags_and_offset() {
+        let mut bytes = HEADER_BYTES;
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        assert_eq!(packet.flags_and_offset_raw(), 0x4000);
+
+        packet.set_flags_and_offset_raw(0x20b9);
+        assert!(!packet.dont_frag());
+        assert!(packet.more_frags());
+        assert_eq!(packet.frag_offset(), 0xb9 * 8);
+
+        packet.set_flags_and_offset_raw(0x5fff);
+        assert!(packet.dont_frag());
+        assert!(!packet.more_frags());
+        assert_eq!(packet.frag_offset(), 0x1fff * 8);
+
+        packet.set_frag_offset(1480);
+        packet.set_more_frags(true);
+        packet.set_dont_frag(false);
+        assert_eq!(packet.flags_and_offset_raw(), 0x20b9);
+
+        packet.clear_flags();
+        assert_eq!(packet.flags_and_offset_raw(), 0x00b9);
+    }
+
+    #[test]
+    fn test_from_frame_payload() {
+        // echo request 10.0.0.2 -> 10.0.0.1 padded to 60 bytes
+        let mut bytes = [0; 60];
+        bytes[12..14].copy_from_slice(&[0x08, 0x00]);
+        bytes[14..50].copy_from_slice(&[
+            0x45, 0x00, 0x00, 0x24, 0xbe, 0xef, 0x40, 0x00,
+            0x40, 0x01, 0x67, 0xe7, 0x0a, 0x00, 0x00, 0x02,
+            0x0a, 0x00, 0x00, 0x01, 0x08, 0x00, 0xd4, 0xb5,
+            0x12, 0x34, 0x00, 0x01, 0x41, 0x42, 0x43, 0x44,
+            0x45, 0x46, 0x47, 0x48,
+        ]);
+        let frame = Frame::new_checked(&bytes[..]).unwrap();
+        assert_eq!(frame.payload().len(), 46);
+        let packet = Packet::from_frame_payload(&frame).unwrap();
+        assert_eq!(packet.as_ref().len(), packet.total_len() as usize);
+        assert_eq!(packet.total_len(), 36);
+        assert!(packet.verify_checksum());
+
+        let frame = Frame::new_checked(&bytes[..40]).unwrap();
+        assert_eq!(Packet::from_frame_payload(&frame).err(), Some(Error::Truncated));
+        let frame = Frame::new_checked(&bytes[..30]).unwrap();
+        assert_eq!(Packet::from_frame_payload(&frame).err(), Some(Error::Truncated));
+    }
+
+    #[test]
+    fn test_swap_addresses() {
+        let mut bytes = HEADER_BYTES;
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        let checksum = packet.checksum();
+        packet.swap_addresses();
+        assert_eq!(packet.src_addr(), Address([192, 168, 0, 199]));
+        assert_eq!(packet.dst_addr(), Address([192, 168, 0, 1]));
+        assert_eq!(packet.checksum(), checksum);
+        assert!(packet.verify_checksum());
+
+        packet.swap_addresses();
+        assert_eq!(packet.as_ref(), &HEADER_BYTES[..]);
+    }
+
+    #[test]
+    fn test_finalize() {
+        let mut bytes = [0; 24 + 8];
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        packet.set_version(4);
+        assert_eq!(packet.set_header_len_words(4), Err(Error::Illegal));
+        assert_eq!(packet.set_header_len_words(16), Err(Error::Illegal));
+        assert_eq!(packet.set_header_len_words(6), Ok(()));
+        assert_eq!(packet.header_len(), 24);
+
+        packet.set_flags_and_offset_raw(0xC000);
+        packet.set_hop_limit(64);
+        packet.set_protocol(Protocol::UDP);
+        packet.finalize(8).unwrap();
+        assert_eq!(packet.total_len(), 32);
+        assert_eq!(packet.flags_and_offset_raw(), 0x4000);
+        assert!(packet.verify_checksum());
+        assert_eq!(packet.payload().len(), 8);
+        assert!(Packet::new_checked(&bytes[..]).is_ok());
+
+        // the total length field can't describe more than 65535 octets
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        assert_eq!(packet.finalize(65535 - 24), Ok(()));
+        assert_eq!(packet.finalize(65535 - 23), Err(Error::Exhausted));
+        assert_eq!(packet.total_len(), 65535);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_builder_len() {
+        let packet = Packet::builder(65535 - 20).unwrap();
+        assert_eq!(packet.total_len(), 65535);
+        assert_eq!(packet.as_ref().len(), 65535);
+        assert_eq!(Packet::builder(65535 - 19).err(), Some(Error::Exhausted));
+    }
+
+    #[test]
+    fn test_repr() {
+        let mut bytes = vec![0; 0x73];
+        bytes[..20].copy_from_slice(&HEADER_BYTES);
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        let repr = Repr::parse(&packet).unwrap();
+        assert_eq!(repr, Repr {
+            src_addr: Address([192, 168, 0, 1]),
+            dst_addr: Address([192, 168, 0, 199]),
+            protocol: Protocol::UDP,
+            payload_len: 0x73 - 20,
+            hop_limit: 64,
+        });
+        assert_eq!(repr.buffer_len(), bytes.len());
+
+        let mut emitted = vec![0; repr.buffer_len()];
+        repr.emit(&mut Packet::new_unchecked(&mut emitted[..]));
+        assert_eq!(emitted, bytes);
+
+        bytes[10] ^= 0xff;
+        let packet = Packet::new_unchecked(&bytes[..]);
+        assert_eq!(Repr::parse(&packet), Err(Error::Checksum));
+
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        packet.set_more_frags(true);
+        packet.fill_checksum();
+        let packet = Packet::new_unchecked(&bytes[..]);
+        assert_eq!(Repr::parse(&packet), Err(Error::Fragmented));
+    }
+
+    #[test]
+    fn test_payload() {
+        // echo request 10.0.0.2 -> 10.0.0.1
+        let bytes = [
+            0x45, 0x00, 0x00, 0x24, 0xbe, 0xef, 0x40, 0x00,
+            0x40, 0x01, 0x67, 0xe7, 0x0a, 0x00, 0x00, 0x02,
+            0x0a, 0x00, 0x00, 0x01, 0x08, 0x00, 0xd4, 0xb5,
+            0x12, 0x34, 0x00, 0x01, 0x41, 0x42, 0x43, 0x44,
+            0x45, 0x46, 0x47, 0x48, 0x00, 0x00,
+        ];
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert_eq!(packet.protocol(), Protocol::ICMP);
+        assert_eq!(packet.payload().len(), 16);
+        assert_eq!(&packet.payload()[..2], &[0x08, 0x00]);
+        assert_eq!(&packet.payload()[8..], b"ABCDEFGH");
+    }
+
+    #[test]
+    fn test_payload_bounds() {
+        let mut bytes = [0; 24];
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        packet.set_version(4);
+        packet.set_header_len(20);
+        packet.set_total_len(1500);
+        assert!(Packet::new_checked(packet.as_ref()).is_err());
+        assert_eq!(packet.payload().len(), 4);
+        assert_eq!(packet.payload_mut().len(), 4);
+
+        // header length beyond the total length and the buffer
+        packet.set_header_len(60);
+        packet.set_total_len(22);
+        assert!(packet.payload().is_empty());
+        assert!(packet.payload_mut().is_empty());
+
+        packet.set_header_len(20);
+        packet.set_total_len(10);
+        assert!(packet.payload().is_empty());
+        assert!(packet.payload_mut().is_empty());
+    }
+
+    #[test]
+    fn test_hop_limit_update() {
+        let mut bytes = vec![0; 24];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_version(4);
+        packet.set_header_len(20);
+        packet.set_total_len(24);
+        packet.set_ident(0x1234);
+        packet.set_dont_frag(true);
+        packet.set_hop_limit(64);
+        packet.set_protocol(Protocol::UDP);
+        packet.set_src_addr(Address([192, 168, 1, 10]));
+        packet.set_dst_addr(Address([10, 0, 0, 1]));
+        packet.payload_mut().copy_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        packet.fill_checksum();
+        assert!(packet.verify_checksum());
+
+        for ttl in (0..64).rev() {
+            packet.set_hop_limit_update(ttl);
+            assert_eq!(packet.hop_limit(), ttl);
+            assert!(packet.verify_checksum());
+        }
+
+        let patched = packet.checksum();
+        packet.fill_checksum();
+        assert_eq!(packet.checksum(), patched);
+    }
+
+    #[test]
+    fn test_decrement_hop_limit() {
+        let mut bytes = vec![0; 20];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_version(4);
+        packet.set_header_len(20);
+        packet.set_total_len(20);
+        packet.set_hop_limit(64);
+        packet.set_protocol(Protocol::ICMP);
+        packet.set_src_addr(Address([10, 0, 0, 2]));
+        packet.set_dst_addr(Address([10, 0, 0, 1]));
+        packet.fill_checksum();
+
+        assert_eq!(packet.decrement_hop_limit(), Ok(()));
+        assert_eq!(packet.hop_limit(), 63);
+        assert!(packet.verify_checksum());
+
+        packet.set_hop_limit(1);
+        packet.fill_checksum();
+        assert_eq!(packet.decrement_hop_limit(), Err(Error::Illegal));
+        assert_eq!(packet.hop_limit(), 1);
+        assert!(packet.verify_checksum());
+
+        packet.set_hop_limit(0);
+        assert_eq!(packet.decrement_hop_limit(), Err(Error::Illegal));
+    }
+
+    #[test]
+    fn test_decrement_to_expiry() {
+        let mut bytes = HEADER_BYTES;
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        packet.set_diff_serv(DiffServ::AF41);
+        packet.set_ecn_typed(Ecn::Ect0);
+        packet.set_ident(0xbeef);
+        packet.fill_checksum();
+
+        for ttl in (1..64).rev() {
+            assert_eq!(packet.decrement_hop_limit(), Ok(()));
+            assert_eq!(packet.hop_limit(), ttl);
+            assert!(packet.verify_checksum());
+        }
+        assert_eq!(packet.decrement_hop_limit(), Err(Error::Illegal));
+        assert_eq!(packet.hop_limit(), 1);
+        assert!(packet.verify_checksum());
+
+        assert_eq!(packet.diff_serv(), DiffServ::AF41);
+        assert_eq!(packet.ecn_typed(), Ecn::Ect0);
+        assert_eq!(packet.ident(), 0xbeef);
+    }
+
+    #[test]
+    fn test_options() {
+        let mut bytes = vec![0; 28];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_version(4);
+        packet.set_header_len(28);
+        packet.set_total_len(28);
+        // Record Route with room for one address, then End of Option List
+        bytes[20..28].copy_from_slice(&[7, 7, 4, 0, 0, 0, 0, 0]);
+
+        let packet = Packet::new_checked(&bytes).unwrap();
+        assert_eq!(packet.options().len(), 8);
+
+        let mut iter = OptionsIter::new(packet.options());
+        let (kind, data) = iter.next().unwrap().unwrap();
+        assert_eq!(kind, option::RECORD_ROUTE);
+        assert_eq!(data.len(), 5);
+        assert_eq!(data[0], 4);
+        assert_eq!(iter.next(), Some(Ok((option::END_OF_LIST, &[][..]))));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn test_router_alert() {
+        let mut bytes = [0; 20 + 8 + 4];
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        packet.set_version(4);
+        packet.set_header_len(20);
+        packet.set_hop_limit(1);
+        packet.set_protocol(Protocol::IGMP);
+        packet.set_src_addr(Address([10, 0, 0, 1]));
+        packet.set_dst_addr(Address::MULTICAST_ALL_ROUTERS);
+        packet.finalize(8).unwrap();
+        packet.payload_mut().copy_from_slice(&[0x17, 0, 0, 0, 1, 2, 3, 4]);
+
+        packet.set_router_alert().unwrap();
+        assert_eq!(packet.set_router_alert(), Err(Error::Illegal));
+
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert_eq!(packet.header_len(), 24);
+        assert_eq!(packet.total_len(), 32);
+        assert!(packet.verify_checksum());
+        assert_eq!(packet.payload(), &[0x17, 0, 0, 0, 1, 2, 3, 4]);
+
+        let mut iter = OptionsIter::new(packet.options());
+        assert_eq!(iter.next(), Some(Ok((option::ROUTER_ALERT, &[0, 0][..]))));
+        assert_eq!(iter.next(), None);
+
+        // no room for the larger header
+        let mut bytes = [0; 20 + 8 + 3];
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        packet.set_version(4);
+        packet.set_header_len(20);
+        packet.finalize(8).unwrap();
+        assert_eq!(packet.set_router_alert(), Err(Error::Exhausted));
+
+        // no room in the total length field
+        let mut bytes = vec![0; 65535 + 4];
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        packet.set_version(4);
+        packet.set_header_len(20);
+        packet.finalize(65535 - 20).unwrap();
+        assert_eq!(packet.set_router_alert(), Err(Error::Exhausted));
+    }
+
+    #[test]
+    fn test_timestamp_option() {
+        let mut bytes = [0; 60 + 8];
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        packet.set_version(4);
+        packet.set_header_len(20);
+        packet.set_hop_limit(64);
+        packet.set_protocol(Protocol::ICMP);
+        packet.set_src_addr(Address([10, 0, 0, 1]));
+        packet.set_dst_addr(Address([10, 0, 0, 2]));
+        packet.finalize(8).unwrap();
+        packet.payload_mut().copy_from_slice(&[8, 0, 0, 0, 1, 2, 3, 4]);
+
+        assert_eq!(packet.add_timestamp_option(2, 0), Err(Error::Illegal));
+        packet.add_timestamp_option(option::timestamp::WITH_ADDRESSES, 0).unwrap();
+        // four address/timestamp pairs fill the header up to 60 octets
+        assert_eq!(packet.header_len(), 56);
+        assert_eq!(
+            packet.add_timestamp_option(option::timestamp::WITH_ADDRESSES, 0),
+            Err(Error::Exhausted)
+        );
+
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert_eq!(packet.total_len(), 64);
+        assert!(packet.verify_checksum());
+        assert_eq!(packet.payload(), &[8, 0, 0, 0, 1, 2, 3, 4]);
+        assert_eq!(&packet.options()[..4], &[option::TIMESTAMP, 36, 5, 0x01]);
+        assert!(packet.options()[4..].iter().all(|&b| b == 0));
+
+        let timestamps = packet.timestamp_option().unwrap().unwrap();
+        assert_eq!(timestamps.pointer(), 5);
+        assert_eq!(timestamps.flags(), option::timestamp::WITH_ADDRESSES);
+        assert_eq!(timestamps.overflow(), 0);
+        assert!(!timestamps.is_full());
+        assert_eq!(timestamps.recorded().count(), 0);
+
+        // one router recorded its address and timestamp
+        bytes[20 + 2] = 13;
+        bytes[24..32].copy_from_slice(&[10, 0, 0, 254, 0x00, 0x01, 0x02, 0x03]);
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        let timestamps = packet.timestamp_option().unwrap().unwrap();
+        let mut recorded = timestamps.recorded();
+        assert_eq!(recorded.next(), Some((Some(Address([10, 0, 0, 254])), 0x00010203)));
+        assert_eq!(recorded.next(), None);
+    }
+
+    #[test]
+    fn test_unsupported_options() {
+        fn with_options(options: &[u8]) -> Vec<u8> {
+            let header_len = 20 + options.len();
+            let mut bytes = vec![0; header_len];
+            let mut packet = Packet::new_unchecked(&mut bytes);
+            packet.set_version(4);
+            packet.set_header_len(header_len as u8);
+            packet.set_total_len(header_len as u16);
+            bytes[20..].copy_from_slice(options);
+            bytes
+        }
+
+        let bytes = with_options(&[]);
+        assert!(!Packet::new_checked(&bytes).unwrap().has_unsupported_options());
+
+        let bytes = with_options(&[1, 1, 1, 0]);
+        assert!(!Packet::new_checked(&bytes).unwrap().has_unsupported_options());
+
+        let bytes = with_options(&[148, 4, 0, 0]);
+        assert!(!Packet::new_checked(&bytes).unwrap().has_unsupported_options());
+
+        // Loose Source Route via 10.0.0.1, padded with NOPs
+        let bytes = with_options(&[131, 7, 4, 10, 0, 0, 1, 1]);
+        assert!(Packet::new_checked(&bytes).unwrap().has_unsupported_options());
+
+        let bytes = with_options(&[1, 137, 7, 4, 10, 0, 0, 1]);
+        assert!(Packet::new_checked(&bytes).unwrap().has_unsupported_options());
+
+        let bytes = with_options(&[1, 1, 148, 9]);
+        assert!(Packet::new_checked(&bytes).unwrap().has_unsupported_options());
+    }
+
+    #[test]
+    fn test_options_malformed() {
+        let mut iter = OptionsIter::new(&[1, 7, 9, 4, 0, 0, 0]);
+        assert_eq!(iter.next(), Some(Ok((option::NO_OPERATION, &[][..]))));
+        assert_eq!(iter.next(), Some(Err(crate::Error::Malformed)));
+        assert_eq!(iter.next(), None);
+
+        let mut iter = OptionsIter::new(&[7]);
+        assert_eq!(iter.next(), Some(Err(crate::Error::Malformed)));
+    }
+
+    #[test]
+    fn test_address_map() {
+        use std::collections::BTreeMap;
+        use crate::protocol::ethernet::Address as MacAddress;
+
+        let mut map = BTreeMap::new();
+        map.insert(Address([10, 0, 0, 2]), MacAddress([0x02, 0, 0, 0, 0, 0x02]));
+        map.insert(Address([10, 0, 0, 1]), MacAddress([0x02, 0, 0, 0, 0, 0x01]));
+        map.insert(Address([192, 168, 0, 1]), MacAddress::BROADCAST);
+
+        let addr = Address([10, 0, 0, 1]);
+        assert_eq!(map.get(&addr), Some(&MacAddress([0x02, 0, 0, 0, 0, 0x01])));
+        // still usable after the lookup since addresses are Copy
+        assert_eq!(map.get(&addr).copied(), Some(MacAddress([0x02, 0, 0, 0, 0, 0x01])));
+        assert_eq!(map.get(&Address([10, 0, 0, 3])), None);
+        assert_eq!(map.keys().next(), Some(&Address([10, 0, 0, 1])));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let addr = Address([10, 0, 0, 1]);
+        let json = serde_json::to_string(&addr).unwrap();
+        assert_eq!(json, "\"10.0.0.1\"");
+        assert_eq!(serde_json::from_str::<Address>(&json).unwrap(), addr);
+        assert!(serde_json::from_str::<Address>("\"10.0.0\"").is_err());
+        assert!(serde_json::from_str::<Address>("167772161").is_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_address_std() {
+        use std::net::Ipv4Addr;
+
+        let addr: Address = Ipv4Addr::LOCALHOST.into();
+        assert_eq!(addr, Address([127, 0, 0, 1]));
+        assert_eq!(Ipv4Addr::from(addr), Ipv4Addr::LOCALHOST);
+
+        let addr: Address = Ipv4Addr::BROADCAST.into();
+        assert_eq!(addr, Address::BROADCAST);
+        assert_eq!(Ipv4Addr::from(addr), Ipv4Addr::BROADCAST);
+    }
+
+    #[test]
+    fn test_checked_accessors() {
+        let bytes = [0x45, 0x00];
+        let packet = Packet::new_unchecked(&bytes[..]);
+        assert_eq!(packet.version_checked(), Ok(4));
+        assert_eq!(packet.header_len_checked(), Ok(20));
+        assert_eq!(packet.total_len_checked(), Err(Error::Truncated));
+        assert_eq!(packet.protocol_checked(), Err(Error::Truncated));
+        assert_eq!(packet.src_addr_checked(), Err(Error::Truncated));
+        assert_eq!(packet.dst_addr_checked(), Err(Error::Truncated));
+    }
+
+    #[test]
+    fn test_address_try_from_bytes() {
+        use core::convert::TryFrom;
+
+        assert_eq!(Address::try_from_bytes(&[10, 0, 0]), Err(Error::Malformed));
+        assert_eq!(Address::try_from_bytes(&[10, 0, 0, 1, 0]), Err(Error::Malformed));
+        assert_eq!(Address::try_from_bytes(&[10, 0, 0, 1]), Ok(Address([10, 0, 0, 1])));
+        assert_eq!(Address::try_from(&[192, 168, 0, 1][..]), Ok(Address([192, 168, 0, 1])));
+        assert_eq!(Address::try_from(&[][..]), Err(Error::Malformed));
+    }
+
+    #[test]
+    fn test_multicast() {
+        assert!(Address([224, 0, 0, 251]).is_multicast());
+        assert!(Address([239, 1, 1, 1]).is_multicast());
+        assert!(!Address([239, 1, 1, 1]).is_unicast());
+        assert!(!Address([223, 255, 255, 255]).is_multicast());
+        assert!(Address([223, 1, 1, 1]).is_unicast());
+        assert!(!Address([240, 0, 0, 1]).is_multicast());
+
+        assert!(Address::MULTICAST_ALL_SYSTEMS.is_local_network_multicast());
+        assert!(Address::MULTICAST_ALL_ROUTERS.is_local_network_multicast());
+        assert!(!Address([224, 0, 1, 1]).is_local_network_multicast());
+        assert!(!Address([239, 1, 1, 1]).is_local_network_multicast());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_into_owned() {
+        // a 24-byte datagram followed by link-layer padding
+        let mut bytes = vec![0; 24 + 10];
+        bytes[..20].copy_from_slice(&HEADER_BYTES);
+        bytes[20..24].copy_from_slice(&[1, 2, 3, 4]);
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        packet.set_total_len(24);
+        packet.fill_checksum();
+
+        let packet = Packet::new_checked(&bytes[..]).unwrap().into_owned();
+        drop(bytes);
+        assert_eq!(packet.as_ref().len(), 24);
+        assert!(packet.verify_checksum());
+        assert_eq!(packet.src_addr(), Address([192, 168, 0, 1]));
+        assert_eq!(packet.dst_addr(), Address([192, 168, 0, 199]));
+        assert_eq!(packet.protocol(), Protocol::UDP);
+        assert_eq!(packet.payload(), &[1, 2, 3, 4]);
+    }
+}
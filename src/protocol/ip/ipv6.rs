@@ -1 +1,645 @@
+// 0                   1                   2                   3
+// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |Version| Traffic Class |           Flow Label                  |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |         Payload Length        |  Next Header  |   Hop Limit   |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                                                               |
+// +                                                               +
+// |                                                               |
+// +                         Source Address                        +
+// |                                                               |
+// +                                                               +
+// |                                                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                                                               |
+// +                                                               +
+// |                                                               |
+// +                      Destination Address                      +
+// |                                                               |
+// +                                                               +
+// |                                                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
 
+#![allow(unused)]
+use byteorder::{
+    ByteOrder,
+    NetworkEndian,
+};
+use crate::{
+    Result,
+    Error,
+};
+use crate::bytes;
+use super::Protocol;
+use core::fmt;
+use core::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Address(pub [u8; 16]);
+
+impl Address {
+    pub const UNSPECIFIED:          Address = Address([0x00; 16]);
+    pub const LOOPBACK:             Address = Address([
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+    ]);
+    pub const LINK_LOCAL_ALL_NODES: Address = Address([
+        0xff, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+    ]);
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        a0: u16, a1: u16, a2: u16, a3: u16,
+        a4: u16, a5: u16, a6: u16, a7: u16,
+    ) -> Self {
+        let mut bytes = [0; 16];
+        let words = [a0, a1, a2, a3, a4, a5, a6, a7];
+        for (i, &word) in words.iter().enumerate() {
+            NetworkEndian::write_u16(&mut bytes[i * 2..], word);
+        }
+        Address(bytes)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Self {
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(data);
+        Address(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn is_unspecified(&self) -> bool {
+        *self == Self::UNSPECIFIED
+    }
+
+    pub fn is_loopback(&self) -> bool {
+        *self == Self::LOOPBACK
+    }
+
+    pub fn is_multicast(&self) -> bool {
+        self.0[0] == 0xff
+    }
+
+    // fe80::/10
+    pub fn is_link_local(&self) -> bool {
+        self.0[0] == 0xfe && self.0[1] & 0xc0 == 0x80
+    }
+
+    pub fn is_unicast(&self) -> bool {
+        !self.is_multicast() &&
+        !self.is_unspecified()
+    }
+
+    // ff02::1:ffXX:XXXX, built from the low 24 bits of the address (RFC 4291)
+    pub fn solicited_node_multicast(&self) -> Address {
+        let mut bytes = [
+            0xff, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x01, 0xff, 0x00, 0x00, 0x00,
+        ];
+        bytes[13..].copy_from_slice(&self.0[13..]);
+        Address(bytes)
+    }
+}
+
+// The canonical text form (RFC 5952): lowercase hex words without
+// leading zeros, the longest run of two or more zero words as "::".
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut words = [0u16; 8];
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = NetworkEndian::read_u16(&self.0[i * 2..]);
+        }
+
+        // (start, len) of the longest run of zeros, the first one on a tie
+        let mut longest = (0, 0);
+        let mut run = (0, 0);
+        for (i, &word) in words.iter().enumerate() {
+            if word != 0 {
+                run = (i + 1, 0);
+                continue;
+            }
+            run.1 += 1;
+            if run.1 > longest.1 {
+                longest = run;
+            }
+        }
+
+        if longest.1 < 2 {
+            return write_words(f, &words);
+        }
+        write_words(f, &words[..longest.0])?;
+        write!(f, "::")?;
+        write_words(f, &words[longest.0 + longest.1..])
+    }
+}
+
+// Accepts the hex-word forms of RFC 4291 with at most one "::"; the
+// dotted IPv4 suffix form is not supported.
+impl FromStr for Address {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Address> {
+        let mut words = [0u16; 8];
+        let mut halves = s.splitn(2, "::");
+        let head = halves.next().ok_or(Error::Malformed)?;
+        let head_len = parse_words(head, &mut words)?;
+        match halves.next() {
+            None if head_len == 8 => (),
+            None => return Err(Error::Malformed),
+            Some(tail) => {
+                let mut tail_words = [0u16; 8];
+                let tail_len = parse_words(tail, &mut tail_words)?;
+                if head_len + tail_len > 7 {
+                    return Err(Error::Malformed);
+                }
+                words[8 - tail_len..].copy_from_slice(&tail_words[..tail_len]);
+            }
+        }
+
+        let mut bytes = [0; 16];
+        for (i, &word) in words.iter().enumerate() {
+            NetworkEndian::write_u16(&mut bytes[i * 2..], word);
+        }
+        Ok(Address(bytes))
+    }
+}
+
+// Parse colon-separated hex words into `words`, returning their count.
+fn parse_words(s: &str, words: &mut [u16; 8]) -> Result<usize> {
+    if s.is_empty() {
+        return Ok(0);
+    }
+    let mut len = 0;
+    for part in s.split(':') {
+        if len == words.len() || part.is_empty() || part.len() > 4 ||
+           !part.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Error::Malformed);
+        }
+        words[len] = u16::from_str_radix(part, 16).map_err(|_| Error::Malformed)?;
+        len += 1;
+    }
+    Ok(len)
+}
+
+fn write_words(f: &mut fmt::Formatter, words: &[u16]) -> fmt::Result {
+    for (i, word) in words.iter().enumerate() {
+        if i > 0 {
+            write!(f, ":")?;
+        }
+        write!(f, "{:x}", word)?;
+    }
+    Ok(())
+}
+
+mod field {
+    use crate::Field;
+
+    pub const VER_TC_FLOW: Field = 0..4;
+    pub const LENGTH:      Field = 4..6;
+    pub const NXT_HDR:     usize = 6;
+    pub const HOP_LIMIT:   usize = 7;
+    pub const SRC_ADDR:    Field = 8..24;
+    pub const DST_ADDR:    Field = 24..40;
+}
+
+pub const HEADER_LEN: usize = field::DST_ADDR.end;
+
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T
+}
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    pub fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < HEADER_LEN ||
+           len < HEADER_LEN + self.payload_len() as usize {
+            Err(Error::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    pub fn header_len() -> usize {
+        HEADER_LEN
+    }
+
+    pub fn version(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::VER_TC_FLOW.start] >> 4
+    }
+
+    pub fn traffic_class(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        (bytes::checked(bytes::get_u16(data, field::VER_TC_FLOW)) >> 4) as u8
+    }
+
+    // the low 20 bits of the first word
+    pub fn flow_label(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u32(data, field::VER_TC_FLOW)) & 0x000F_FFFF
+    }
+
+    // length of the payload in octets, extension headers included
+    pub fn payload_len(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::LENGTH))
+    }
+
+    pub fn next_header(&self) -> Protocol {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u8(data, field::NXT_HDR)).into()
+    }
+
+    pub fn hop_limit(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u8(data, field::HOP_LIMIT))
+    }
+
+    pub fn src_addr(&self) -> Address {
+        let data = self.buffer.as_ref();
+        Address::from_bytes(bytes::checked(bytes::get_slice(data, field::SRC_ADDR)))
+    }
+
+    pub fn dst_addr(&self) -> Address {
+        let data = self.buffer.as_ref();
+        Address::from_bytes(bytes::checked(bytes::get_slice(data, field::DST_ADDR)))
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        let range = HEADER_LEN..HEADER_LEN + self.payload_len() as usize;
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_slice(data, range))
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    pub fn set_version(&mut self, version: u8) {
+        let data = self.buffer.as_mut();
+        let idx = field::VER_TC_FLOW.start;
+        data[idx] = (version & 0x0F) << 4 | (data[idx] & 0x0F);
+    }
+
+    pub fn set_traffic_class(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        let raw = NetworkEndian::read_u16(&data[field::VER_TC_FLOW]);
+        let raw = (raw & 0xF00F) | ((value as u16) << 4);
+        NetworkEndian::write_u16(&mut data[field::VER_TC_FLOW], raw);
+    }
+
+    pub fn set_flow_label(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        let raw = NetworkEndian::read_u32(&data[field::VER_TC_FLOW]);
+        let raw = (raw & 0xFFF0_0000) | (value & 0x000F_FFFF);
+        NetworkEndian::write_u32(&mut data[field::VER_TC_FLOW], raw);
+    }
+
+    pub fn set_payload_len(&mut self, len: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::LENGTH], len);
+    }
+
+    pub fn set_next_header(&mut self, protocol: Protocol) {
+        let data = self.buffer.as_mut();
+        data[field::NXT_HDR] = protocol.into();
+    }
+
+    pub fn set_hop_limit(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::HOP_LIMIT] = value;
+    }
+
+    pub fn set_src_addr(&mut self, addr: Address) {
+        let data = self.buffer.as_mut();
+        data[field::SRC_ADDR].copy_from_slice(addr.as_bytes());
+    }
+
+    pub fn set_dst_addr(&mut self, addr: Address) {
+        let data = self.buffer.as_mut();
+        data[field::DST_ADDR].copy_from_slice(addr.as_bytes());
+    }
+
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let range = HEADER_LEN..HEADER_LEN + self.payload_len() as usize;
+        let data = self.buffer.as_mut();
+        &mut data[range]
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+// Fragment extension header (next header 44), RFC 8200 section 4.5.
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  Next Header  |   Reserved    |      Fragment Offset    |Res|M|
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                         Identification                        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+mod frag_field {
+    use crate::Field;
+
+    pub const NXT_HDR:  usize = 0;
+    pub const RESERVED: usize = 1;
+    pub const FR_OF_M:  Field = 2..4;
+    pub const IDENT:    Field = 4..8;
+
+    pub const OFF_MASK: u16 = 0xfff8;
+    pub const FLG_M:    u16 = 0x0001;
+}
+
+pub const FRAGMENT_HEADER_LEN: usize = frag_field::IDENT.end;
+
+pub struct FragmentHeader<T: AsRef<[u8]>> {
+    buffer: T
+}
+
+impl<T: AsRef<[u8]>> FragmentHeader<T> {
+    pub fn new_unchecked(buffer: T) -> FragmentHeader<T> {
+        FragmentHeader { buffer }
+    }
+
+    pub fn new_checked(buffer: T) -> Result<FragmentHeader<T>> {
+        let header = Self::new_unchecked(buffer);
+        header.check_len()?;
+        Ok(header)
+    }
+
+    pub fn check_len(&self) -> Result<()> {
+        if self.buffer.as_ref().len() < FRAGMENT_HEADER_LEN {
+            Err(Error::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    pub fn header_len() -> usize {
+        FRAGMENT_HEADER_LEN
+    }
+
+    pub fn next_header(&self) -> Protocol {
+        let data = self.buffer.as_ref();
+        data[frag_field::NXT_HDR].into()
+    }
+
+    // Like the IPv4 fragment offset, in octets; the field itself
+    // counts units of 8 octets.
+    pub fn fragment_offset(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, frag_field::FR_OF_M)) & frag_field::OFF_MASK
+    }
+
+    pub fn more_frags(&self) -> bool {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, frag_field::FR_OF_M)) & frag_field::FLG_M != 0
+    }
+
+    pub fn ident(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u32(data, frag_field::IDENT))
+    }
+
+    // The data following the header, i.e. the fragment itself.
+    pub fn payload(&self) -> &[u8] {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_slice(data, FRAGMENT_HEADER_LEN..))
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> FragmentHeader<T> {
+    pub fn set_next_header(&mut self, protocol: Protocol) {
+        let data = self.buffer.as_mut();
+        data[frag_field::NXT_HDR] = protocol.into();
+    }
+
+    // Zero both reserved fields, keeping the offset and the M flag.
+    pub fn clear_reserved(&mut self) {
+        let data = self.buffer.as_mut();
+        data[frag_field::RESERVED] = 0;
+        let raw = NetworkEndian::read_u16(&data[frag_field::FR_OF_M]);
+        let raw = raw & (frag_field::OFF_MASK | frag_field::FLG_M);
+        NetworkEndian::write_u16(&mut data[frag_field::FR_OF_M], raw);
+    }
+
+    pub fn set_fragment_offset(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        let raw = NetworkEndian::read_u16(&data[frag_field::FR_OF_M]);
+        let raw = (raw & !frag_field::OFF_MASK) | (value & frag_field::OFF_MASK);
+        NetworkEndian::write_u16(&mut data[frag_field::FR_OF_M], raw);
+    }
+
+    pub fn set_more_frags(&mut self, value: bool) {
+        let data = self.buffer.as_mut();
+        let raw = NetworkEndian::read_u16(&data[frag_field::FR_OF_M]);
+        let raw = if value { raw | frag_field::FLG_M } else { raw & !frag_field::FLG_M };
+        NetworkEndian::write_u16(&mut data[frag_field::FR_OF_M], raw);
+    }
+
+    pub fn set_ident(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[frag_field::IDENT], value);
+    }
+
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let data = self.buffer.as_mut();
+        &mut data[FRAGMENT_HEADER_LEN..]
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for FragmentHeader<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::protocol::ip::Protocol;
+    use crate::Error;
+    use super::{
+        Packet,
+        Address,
+        FragmentHeader,
+    };
+
+    #[test]
+    fn test_fields() {
+        let mut src = [0; 16];
+        src[..2].copy_from_slice(&[0xfe, 0x80]);
+        src[15] = 0x01;
+        let mut dst = [0; 16];
+        dst[..2].copy_from_slice(&[0xfe, 0x80]);
+        dst[15] = 0x02;
+
+        let mut bytes = vec![0; 40 + 4];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_version(6);
+        packet.set_traffic_class(0xA5);
+        packet.set_flow_label(0xBCDEF);
+        packet.set_payload_len(4);
+        packet.set_next_header(Protocol::UDP);
+        packet.set_hop_limit(64);
+        packet.set_src_addr(Address(src));
+        packet.set_dst_addr(Address(dst));
+        packet.payload_mut().copy_from_slice(&[1, 2, 3, 4]);
+
+        assert_eq!(&packet.as_ref()[..4], &[0x6A, 0x5B, 0xCD, 0xEF]);
+
+        let packet = Packet::new_checked(packet.into_inner()).unwrap();
+        assert_eq!(packet.version(), 6);
+        assert_eq!(packet.traffic_class(), 0xA5);
+        assert_eq!(packet.flow_label(), 0xBCDEF);
+        assert_eq!(packet.payload_len(), 4);
+        assert_eq!(u8::from(packet.next_header()), 0x11);
+        assert_eq!(packet.hop_limit(), 64);
+        assert_eq!(packet.src_addr(), Address(src));
+        assert_eq!(packet.dst_addr(), Address(dst));
+        assert_eq!(packet.payload(), &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_traffic_class_flow_label() {
+        let mut bytes = [0; 40];
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        packet.set_version(6);
+        packet.set_traffic_class(0x12);
+        packet.set_flow_label(0xABCDE);
+        assert_eq!(packet.as_ref()[..4], [0x61, 0x2A, 0xBC, 0xDE]);
+        assert_eq!(packet.version(), 6);
+        assert_eq!(packet.traffic_class(), 0x12);
+        assert_eq!(packet.flow_label(), 0xABCDE);
+
+        // each setter leaves the neighbouring fields alone
+        packet.set_flow_label(0xFFF0_0001);
+        assert_eq!(packet.version(), 6);
+        assert_eq!(packet.traffic_class(), 0x12);
+        assert_eq!(packet.flow_label(), 0x00001);
+
+        packet.set_traffic_class(0xFF);
+        assert_eq!(packet.version(), 6);
+        assert_eq!(packet.traffic_class(), 0xFF);
+        assert_eq!(packet.flow_label(), 0x00001);
+
+        packet.set_version(6);
+        packet.set_traffic_class(0x00);
+        assert_eq!(packet.as_ref()[..4], [0x60, 0x00, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn test_address() {
+        let loopback = Address::new(0, 0, 0, 0, 0, 0, 0, 1);
+        assert_eq!(loopback, Address::LOOPBACK);
+        assert!(loopback.is_loopback());
+        assert!(loopback.is_unicast());
+        assert!(!loopback.is_link_local());
+        assert!(!loopback.is_multicast());
+
+        let link_local = Address::new(0xfe80, 0, 0, 0, 0x0211, 0x22ff, 0xfe33, 0x4455);
+        assert!(link_local.is_link_local());
+        assert!(link_local.is_unicast());
+        assert!(!Address::new(0xfec0, 0, 0, 0, 0, 0, 0, 1).is_link_local());
+
+        assert!(Address::LINK_LOCAL_ALL_NODES.is_multicast());
+        assert!(!Address::LINK_LOCAL_ALL_NODES.is_unicast());
+        assert!(Address::UNSPECIFIED.is_unspecified());
+        assert!(!Address::UNSPECIFIED.is_unicast());
+
+        assert_eq!(
+            link_local.solicited_node_multicast(),
+            Address::new(0xff02, 0, 0, 0, 0, 1, 0xff33, 0x4455)
+        );
+    }
+
+    #[test]
+    fn test_address_display() {
+        let addresses = [
+            (Address::UNSPECIFIED, "::"),
+            (Address::LOOPBACK, "::1"),
+            (Address::LINK_LOCAL_ALL_NODES, "ff02::1"),
+            (Address::new(0xfe80, 0, 0, 0, 0x0211, 0x22ff, 0xfe33, 0x4455), "fe80::211:22ff:fe33:4455"),
+            (Address::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 1), "2001:db8:0:1::1"),
+            (Address::new(0x2001, 0xdb8, 0, 0, 1, 0, 0, 1), "2001:db8::1:0:0:1"),
+            (Address::new(0x2001, 0xdb8, 1, 2, 3, 4, 5, 0), "2001:db8:1:2:3:4:5:0"),
+            (Address::new(1, 0, 0, 0, 0, 0, 0, 0), "1::"),
+        ];
+        for &(address, text) in addresses.iter() {
+            assert_eq!(address.to_string(), text);
+            assert_eq!(text.parse::<Address>(), Ok(address));
+        }
+    }
+
+    #[test]
+    fn test_address_from_str() {
+        assert_eq!(
+            "FE80:0000:0000:0000:0211:22FF:FE33:4455".parse::<Address>(),
+            Ok(Address::new(0xfe80, 0, 0, 0, 0x0211, 0x22ff, 0xfe33, 0x4455))
+        );
+        for text in ["", ":", ":::", "1::2::3", "1:2:3:4:5:6:7", "1:2:3:4:5:6:7:8:9",
+                     "1:2:3:4::5:6:7:8", "12345::", "g::", "::1:", "::ffff:10.0.0.1"].iter() {
+            assert_eq!(text.parse::<Address>(), Err(Error::Malformed), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = vec![0; 39];
+        assert!(Packet::new_checked(&bytes).is_err());
+
+        let mut bytes = vec![0; 40];
+        bytes[5] = 1;
+        assert!(Packet::new_checked(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_fragment_header() {
+        let mut bytes = [0xff; 8 + 4];
+        let mut header = FragmentHeader::new_unchecked(&mut bytes[..]);
+        header.clear_reserved();
+        header.set_next_header(Protocol::UDP);
+        header.set_fragment_offset(1448);
+        header.set_more_frags(true);
+        header.set_ident(0xdeadbeef);
+        header.payload_mut().copy_from_slice(&[1, 2, 3, 4]);
+
+        assert_eq!(&bytes[..8], &[0x11, 0x00, 0x05, 0xa9, 0xde, 0xad, 0xbe, 0xef]);
+
+        let header = FragmentHeader::new_checked(&bytes[..]).unwrap();
+        assert_eq!(header.next_header(), Protocol::UDP);
+        assert_eq!(header.fragment_offset(), 1448);
+        assert!(header.more_frags());
+        assert_eq!(header.ident(), 0xdeadbeef);
+        assert_eq!(header.payload(), &[1, 2, 3, 4]);
+
+        let mut header = FragmentHeader::new_unchecked(&mut bytes[..]);
+        header.set_more_frags(false);
+        assert_eq!(header.fragment_offset(), 1448);
+        assert!(!header.more_frags());
+
+        assert_eq!(FragmentHeader::new_checked(&bytes[..7]).err(), Some(Error::Truncated));
+    }
+}
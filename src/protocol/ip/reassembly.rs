@@ -0,0 +1,351 @@
+#![allow(unused)]
+use alloc::vec::Vec;
+use core::time::Duration;
+use crate::{
+    Result,
+    Error,
+};
+use super::ipv4::{
+    FragmentKey,
+    Packet,
+};
+
+struct Entry {
+    key: FragmentKey,
+    // header of the first fragment, empty until it arrives
+    header: Vec<u8>,
+    payload: Vec<u8>,
+    // received payload ranges, sorted and merged
+    ranges: Vec<(usize, usize)>,
+    // payload length, known once the last fragment arrives
+    total_len: Option<usize>,
+    created_at: Duration,
+}
+
+impl Entry {
+    fn add(&mut self, offset: usize, data: &[u8]) {
+        let end = offset + data.len();
+        if self.payload.len() < end {
+            self.payload.resize(end, 0);
+        }
+        self.payload[offset..end].copy_from_slice(data);
+
+        let (mut start, mut end) = (offset, end);
+        self.ranges.retain(|&(s, e)| {
+            if s > end || e < start {
+                true
+            } else {
+                start = start.min(s);
+                end = end.max(e);
+                false
+            }
+        });
+        let idx = self.ranges.iter()
+            .position(|&(s, _)| s > start)
+            .unwrap_or(self.ranges.len());
+        self.ranges.insert(idx, (start, end));
+    }
+
+    fn is_complete(&self) -> bool {
+        match self.total_len {
+            Some(total_len) => {
+                !self.header.is_empty() &&
+                self.ranges.len() == 1 &&
+                self.ranges[0] == (0, total_len)
+            }
+            None => false
+        }
+    }
+
+    fn assemble(&self) -> Vec<u8> {
+        let total_len = self.total_len.unwrap();
+        let mut datagram = Vec::with_capacity(self.header.len() + total_len);
+        datagram.extend_from_slice(&self.header);
+        datagram.extend_from_slice(&self.payload[..total_len]);
+
+        let mut packet = Packet::new_unchecked(&mut datagram[..]);
+        packet.set_more_frags(false);
+        packet.set_frag_offset(0);
+        packet.set_total_len((self.header.len() + total_len) as u16);
+        packet.fill_checksum();
+        datagram
+    }
+}
+
+// Default number of datagrams reassembled at the same time.
+pub const DEFAULT_CAPACITY: usize = 4;
+// Default time to wait for the rest of a datagram after its first fragment.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct Reassembler {
+    entries: Vec<Entry>,
+    capacity: usize,
+    // the longest payload a datagram may have
+    max_datagram: usize,
+    timeout: Duration,
+}
+
+impl Default for Reassembler {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl Reassembler {
+    pub fn new(capacity: usize) -> Reassembler {
+        Reassembler {
+            entries: Vec::with_capacity(capacity),
+            capacity,
+            max_datagram: u16::MAX as usize,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    // A reassembler bounded in memory and time: fragments reaching past
+    // `max_datagram` bytes of payload are rejected, at most `max_fragments`
+    // datagrams are reassembled at the same time, and a datagram not
+    // completed within `timeout` of its first fragment is dropped.
+    pub fn with_config(max_datagram: usize, max_fragments: usize, timeout: Duration) -> Reassembler {
+        Reassembler {
+            max_datagram,
+            timeout,
+            ..Self::new(max_fragments)
+        }
+    }
+
+    // Number of datagrams currently being reassembled.
+    pub fn in_progress(&self) -> usize {
+        self.entries.len()
+    }
+
+    // Feed a packet to the reassembler. Unfragmented packets are returned
+    // as they are; fragments are buffered until the whole datagram is
+    // received, which is then returned with a rebuilt header. `now` is
+    // the caller's monotonic clock; incomplete datagrams expire by it, so
+    // the table can't fill up for good with fragments that never complete.
+    pub fn accept(&mut self, packet: &Packet<&[u8]>, now: Duration) -> Result<Option<Vec<u8>>> {
+        packet.check_len()?;
+
+        let header_len = packet.header_len() as usize;
+        let total_len = packet.total_len() as usize;
+        let data = &packet.as_ref()[header_len..total_len];
+        let offset = packet.frag_offset() as usize;
+        let more_frags = packet.more_frags();
+
+        if offset == 0 && !more_frags {
+            return Ok(Some(packet.as_ref()[..total_len].to_vec()));
+        }
+        // all fragments but the last carry a multiple of 8 octets
+        if more_frags && data.len() % 8 != 0 {
+            return Err(Error::Malformed);
+        }
+        if header_len + offset + data.len() > u16::MAX as usize {
+            return Err(Error::Malformed);
+        }
+        if offset + data.len() > self.max_datagram {
+            return Err(Error::Malformed);
+        }
+
+        let timeout = self.timeout;
+        self.entries.retain(|entry| now.saturating_sub(entry.created_at) < timeout);
+
+        let key = FragmentKey::from(packet);
+        let idx = match self.entries.iter().position(|entry| entry.key == key) {
+            Some(idx) => idx,
+            None => {
+                if self.entries.len() >= self.capacity {
+                    return Err(Error::Exhausted);
+                }
+                self.entries.push(Entry {
+                    key,
+                    header: Vec::new(),
+                    payload: Vec::new(),
+                    ranges: Vec::new(),
+                    total_len: None,
+                    created_at: now,
+                });
+                self.entries.len() - 1
+            }
+        };
+
+        let entry = &mut self.entries[idx];
+        if offset == 0 {
+            entry.header = packet.as_ref()[..header_len].to_vec();
+        }
+        if !more_frags {
+            entry.total_len = Some(offset + data.len());
+        }
+        entry.add(offset, data);
+
+        if entry.is_complete() {
+            let datagram = entry.assemble();
+            self.entries.remove(idx);
+            Ok(Some(datagram))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::vec::Vec;
+    use crate::Error;
+    use crate::dev::ipv4_datagram as datagram;
+    use crate::protocol::ip::ipv4::Packet;
+    use super::Reassembler;
+    use core::time::Duration;
+
+    // cut the payload of a datagram at the given offsets
+    fn fragments(datagram: &[u8], cuts: &[usize]) -> Vec<Vec<u8>> {
+        let payload = &datagram[20..];
+        let mut bounds = vec![0];
+        bounds.extend_from_slice(cuts);
+        bounds.push(payload.len());
+
+        bounds.windows(2).map(|w| {
+            let mut bytes = datagram[..20].to_vec();
+            bytes.extend_from_slice(&payload[w[0]..w[1]]);
+            let mut packet = Packet::new_unchecked(&mut bytes[..]);
+            packet.set_total_len((20 + w[1] - w[0]) as u16);
+            packet.set_frag_offset(w[0] as u16);
+            packet.set_more_frags(w[1] != payload.len());
+            packet.fill_checksum();
+            bytes
+        }).collect()
+    }
+
+    #[test]
+    fn test_two_fragments() {
+        let original = datagram(1, 100);
+        let frags = fragments(&original, &[48]);
+        let mut reassembler = Reassembler::default();
+        let now = Duration::from_secs(0);
+
+        let packet = Packet::new_checked(&frags[0][..]).unwrap();
+        assert_eq!(reassembler.accept(&packet, now), Ok(None));
+        assert_eq!(reassembler.in_progress(), 1);
+        let packet = Packet::new_checked(&frags[1][..]).unwrap();
+        assert_eq!(reassembler.accept(&packet, now), Ok(Some(original)));
+        assert_eq!(reassembler.in_progress(), 0);
+    }
+
+    #[test]
+    fn test_three_fragments_out_of_order() {
+        let original = datagram(2, 200);
+        let frags = fragments(&original, &[64, 128]);
+        let mut reassembler = Reassembler::default();
+        let now = Duration::from_secs(0);
+
+        for &i in &[2, 0] {
+            let packet = Packet::new_checked(&frags[i][..]).unwrap();
+            assert_eq!(reassembler.accept(&packet, now), Ok(None));
+        }
+        let packet = Packet::new_checked(&frags[1][..]).unwrap();
+        assert_eq!(reassembler.accept(&packet, now), Ok(Some(original)));
+    }
+
+    #[test]
+    fn test_unfragmented() {
+        let original = datagram(3, 16);
+        let mut reassembler = Reassembler::default();
+        let now = Duration::from_secs(0);
+        let packet = Packet::new_checked(&original[..]).unwrap();
+        assert_eq!(reassembler.accept(&packet, now), Ok(Some(original.clone())));
+        assert_eq!(reassembler.in_progress(), 0);
+    }
+
+    #[test]
+    fn test_exhausted() {
+        let mut reassembler = Reassembler::new(2);
+        let now = Duration::from_secs(0);
+        for ident in 0..2 {
+            let frags = fragments(&datagram(ident, 64), &[32]);
+            let packet = Packet::new_checked(&frags[0][..]).unwrap();
+            assert_eq!(reassembler.accept(&packet, now), Ok(None));
+        }
+        let frags = fragments(&datagram(2, 64), &[32]);
+        let packet = Packet::new_checked(&frags[0][..]).unwrap();
+        assert_eq!(reassembler.accept(&packet, now), Err(Error::Exhausted));
+    }
+
+    #[test]
+    fn test_default_timeout() {
+        let mut reassembler = Reassembler::new(1);
+        let now = Duration::from_secs(0);
+        let frags = fragments(&datagram(0, 64), &[32]);
+        let packet = Packet::new_checked(&frags[0][..]).unwrap();
+        assert_eq!(reassembler.accept(&packet, now), Ok(None));
+
+        // only the clock ages the entry, not unrelated traffic
+        let unfragmented = datagram(1, 8);
+        for _ in 0..100 {
+            let packet = Packet::new_checked(&unfragmented[..]).unwrap();
+            reassembler.accept(&packet, now).unwrap();
+        }
+        let other = fragments(&datagram(2, 64), &[32]);
+        let packet = Packet::new_checked(&other[0][..]).unwrap();
+        assert_eq!(reassembler.accept(&packet, now), Err(Error::Exhausted));
+
+        let now = super::DEFAULT_TIMEOUT;
+        assert_eq!(reassembler.accept(&packet, now), Ok(None));
+        assert_eq!(reassembler.in_progress(), 1);
+    }
+
+    #[test]
+    fn test_oversize() {
+        let mut reassembler = Reassembler::with_config(128, 4, Duration::from_secs(30));
+        let now = Duration::from_secs(0);
+
+        let frags = fragments(&datagram(0, 200), &[64, 128]);
+        let packet = Packet::new_checked(&frags[0][..]).unwrap();
+        assert_eq!(reassembler.accept(&packet, now), Ok(None));
+        let packet = Packet::new_checked(&frags[1][..]).unwrap();
+        assert_eq!(reassembler.accept(&packet, now), Ok(None));
+        let packet = Packet::new_checked(&frags[2][..]).unwrap();
+        assert_eq!(reassembler.accept(&packet, now), Err(Error::Malformed));
+
+        let original = datagram(1, 128);
+        let frags = fragments(&original, &[64]);
+        for (i, frag) in frags.iter().enumerate() {
+            let packet = Packet::new_checked(&frag[..]).unwrap();
+            let expected = if i == 0 { None } else { Some(original.clone()) };
+            assert_eq!(reassembler.accept(&packet, now), Ok(expected));
+        }
+    }
+
+    #[test]
+    fn test_timeout_eviction() {
+        let mut reassembler = Reassembler::with_config(1500, 1, Duration::from_secs(30));
+        let now = Duration::from_secs(100);
+        let frags = fragments(&datagram(0, 64), &[32]);
+        let packet = Packet::new_checked(&frags[0][..]).unwrap();
+        assert_eq!(reassembler.accept(&packet, now), Ok(None));
+
+        // the only slot is taken until the first datagram times out
+        let other = fragments(&datagram(1, 64), &[32]);
+        let packet = Packet::new_checked(&other[0][..]).unwrap();
+        let now = Duration::from_secs(129);
+        assert_eq!(reassembler.accept(&packet, now), Err(Error::Exhausted));
+        let now = Duration::from_secs(130);
+        assert_eq!(reassembler.accept(&packet, now), Ok(None));
+        assert_eq!(reassembler.in_progress(), 1);
+
+        // the rest of the first datagram comes too late
+        let packet = Packet::new_checked(&frags[1][..]).unwrap();
+        assert_eq!(reassembler.accept(&packet, now), Err(Error::Exhausted));
+    }
+
+    #[test]
+    fn test_no_timeout() {
+        let mut reassembler = Reassembler::with_config(1500, 1, Duration::MAX);
+        let now = Duration::from_secs(100);
+        let frags = fragments(&datagram(0, 64), &[32]);
+        let packet = Packet::new_checked(&frags[0][..]).unwrap();
+        assert_eq!(reassembler.accept(&packet, now), Ok(None));
+
+        let now = Duration::from_secs(1_000_000);
+        let packet = Packet::new_checked(&frags[1][..]).unwrap();
+        assert_eq!(reassembler.accept(&packet, now), Ok(Some(datagram(0, 64))));
+    }
+}
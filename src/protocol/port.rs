@@ -0,0 +1,50 @@
+#![allow(unused)]
+use core::fmt;
+
+// A UDP or TCP port number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Port(pub u16);
+
+impl Port {
+    pub const DNS:         Port = Port(53);
+    pub const DHCP_SERVER: Port = Port(67);
+    pub const DHCP_CLIENT: Port = Port(68);
+    pub const HTTP:        Port = Port(80);
+    pub const HTTPS:       Port = Port(443);
+
+    pub fn value(&self) -> u16 {
+        self.0
+    }
+}
+
+impl From<u16> for Port {
+    fn from(value: u16) -> Self {
+        Port(value)
+    }
+}
+
+impl From<Port> for u16 {
+    fn from(value: Port) -> Self {
+        value.0
+    }
+}
+
+impl fmt::Display for Port {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Port;
+
+    #[test]
+    fn test_port() {
+        assert_eq!(Port::from(53), Port::DNS);
+        assert_eq!(Port::from(53).to_string(), Port::DNS.to_string());
+        assert_eq!(Port::DNS.to_string(), "53");
+        assert_eq!(u16::from(Port::HTTPS), 443);
+        assert!(Port::HTTP < Port::HTTPS);
+    }
+}
@@ -0,0 +1,297 @@
+// 0                   1                   2                   3
+// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |          Protocol ID          |    Version    |   BPDU Type   |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     Flags     |                                               |
+// +-+-+-+-+-+-+-+-+                                               +
+// |                       Root Identifier                         |
+// +               +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |               |               Root Path Cost                  |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |               |                                               |
+// +-+-+-+-+-+-+-+-+                                               +
+// |                      Bridge Identifier                        |
+// +               +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |               |            Port ID            |  Message Age  |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |               |            Max Age            |  Hello Time   |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |               |         Forward Delay         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// Bridge Protocol Data Units (IEEE 802.1D) are carried in 802.3 frames
+// with an LLC header to the group address 01:80:c2:00:00:00. A Topology
+// Change Notification stops after the BPDU type.
+
+#![allow(unused)]
+use byteorder::{
+    ByteOrder,
+    NetworkEndian,
+};
+use crate::{
+    Result,
+    Error,
+};
+use crate::bytes;
+use super::ethernet::Address as MacAddress;
+
+// DSAP, SSAP and control field of the LLC header in front of a BPDU.
+pub const LLC_HEADER: [u8; 3] = [0x42, 0x42, 0x03];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BpduType {
+    Config,
+    Rst,
+    TopologyChange,
+    Unknown(u8),
+}
+
+impl From<u8> for BpduType {
+    fn from(val: u8) -> Self {
+        match val {
+            0x00 => Self::Config,
+            0x02 => Self::Rst,
+            0x80 => Self::TopologyChange,
+            _ => Self::Unknown(val),
+        }
+    }
+}
+
+impl From<BpduType> for u8 {
+    fn from(bpdu_type: BpduType) -> Self {
+        match bpdu_type {
+            BpduType::Config => 0x00,
+            BpduType::Rst => 0x02,
+            BpduType::TopologyChange => 0x80,
+            BpduType::Unknown(val) => val,
+        }
+    }
+}
+
+pub mod flags {
+    pub const TOPOLOGY_CHANGE:     u8 = 0x01;
+    pub const TOPOLOGY_CHANGE_ACK: u8 = 0x80;
+}
+
+// A bridge priority followed by the bridge's MAC address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BridgeId {
+    pub priority: u16,
+    pub addr: MacAddress,
+}
+
+impl BridgeId {
+    fn from_bytes(data: &[u8]) -> BridgeId {
+        BridgeId {
+            priority: NetworkEndian::read_u16(&data[..2]),
+            addr: MacAddress::from_bytes(&data[2..8]),
+        }
+    }
+}
+
+mod field {
+    use crate::Field;
+
+    pub const PROTOCOL_ID:   Field = 0..2;
+    pub const VERSION:       usize = 2;
+    pub const BPDU_TYPE:     usize = 3;
+    pub const FLAGS:         usize = 4;
+    pub const ROOT_ID:       Field = 5..13;
+    pub const ROOT_COST:     Field = 13..17;
+    pub const BRIDGE_ID:     Field = 17..25;
+    pub const PORT_ID:       Field = 25..27;
+    pub const MESSAGE_AGE:   Field = 27..29;
+    pub const MAX_AGE:       Field = 29..31;
+    pub const HELLO_TIME:    Field = 31..33;
+    pub const FORWARD_DELAY: Field = 33..35;
+}
+
+pub const TCN_LEN: usize = field::BPDU_TYPE + 1;
+pub const CONFIG_LEN: usize = field::FORWARD_DELAY.end;
+
+// The BPDU following the LLC header of an 802.3 payload.
+pub fn llc_payload(data: &[u8]) -> Result<&[u8]> {
+    if data.len() < LLC_HEADER.len() {
+        return Err(Error::Truncated);
+    }
+    if data[..LLC_HEADER.len()] != LLC_HEADER {
+        return Err(Error::Unrecognized);
+    }
+    Ok(&data[LLC_HEADER.len()..])
+}
+
+pub struct Bpdu<T: AsRef<[u8]>> {
+    buffer: T
+}
+
+impl<T: AsRef<[u8]>> Bpdu<T> {
+    pub fn new_unchecked(buffer: T) -> Bpdu<T> {
+        Bpdu { buffer }
+    }
+
+    pub fn new_checked(buffer: T) -> Result<Bpdu<T>> {
+        let bpdu = Self::new_unchecked(buffer);
+        bpdu.check_len()?;
+        Ok(bpdu)
+    }
+
+    // Topology Change Notifications carry no more than their type;
+    // the fields after it are only valid in other BPDUs.
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < TCN_LEN {
+            return Err(Error::Truncated);
+        }
+        match self.bpdu_type() {
+            BpduType::TopologyChange => Ok(()),
+            _ if len < CONFIG_LEN => Err(Error::Truncated),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    pub fn protocol_id(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::PROTOCOL_ID))
+    }
+
+    // 0 for STP, 2 for RSTP
+    pub fn version(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u8(data, field::VERSION))
+    }
+
+    pub fn bpdu_type(&self) -> BpduType {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u8(data, field::BPDU_TYPE)).into()
+    }
+
+    pub fn flags(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u8(data, field::FLAGS))
+    }
+
+    pub fn root_id(&self) -> BridgeId {
+        let data = self.buffer.as_ref();
+        BridgeId::from_bytes(bytes::checked(bytes::get_slice(data, field::ROOT_ID)))
+    }
+
+    pub fn root_path_cost(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u32(data, field::ROOT_COST))
+    }
+
+    pub fn bridge_id(&self) -> BridgeId {
+        let data = self.buffer.as_ref();
+        BridgeId::from_bytes(bytes::checked(bytes::get_slice(data, field::BRIDGE_ID)))
+    }
+
+    pub fn port_id(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::PORT_ID))
+    }
+
+    // The timers are in units of 1/256 second.
+    pub fn message_age(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::MESSAGE_AGE))
+    }
+
+    pub fn max_age(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::MAX_AGE))
+    }
+
+    pub fn hello_time(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::HELLO_TIME))
+    }
+
+    pub fn forward_delay(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::FORWARD_DELAY))
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Bpdu<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Error;
+    use crate::protocol::ethernet::{
+        Address as MacAddress,
+        Frame,
+    };
+    use super::{
+        Bpdu,
+        BpduType,
+        BridgeId,
+        llc_payload,
+        flags,
+    };
+
+    static CONFIG_FRAME: [u8; 60] = [
+        0x01, 0x80, 0xc2, 0x00, 0x00, 0x00, 0x00, 0x1c,
+        0x0e, 0x87, 0x85, 0x04, 0x00, 0x26, 0x42, 0x42,
+        0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x00,
+        0x00, 0x1c, 0x0e, 0x87, 0x78, 0x00, 0x00, 0x00,
+        0x00, 0x04, 0x80, 0x00, 0x00, 0x1c, 0x0e, 0x87,
+        0x85, 0x00, 0x80, 0x04, 0x01, 0x00, 0x14, 0x00,
+        0x02, 0x00, 0x0f, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn test_config_bpdu() {
+        let frame = Frame::new_checked(&CONFIG_FRAME[..]).unwrap();
+        assert!(frame.dst_addr().is_stp_group());
+        assert_eq!(frame.length_field(), Some(38));
+
+        let payload = &frame.payload()[..38];
+        let bpdu = Bpdu::new_checked(llc_payload(payload).unwrap()).unwrap();
+        assert_eq!(bpdu.protocol_id(), 0);
+        assert_eq!(bpdu.version(), 0);
+        assert_eq!(bpdu.bpdu_type(), BpduType::Config);
+        assert_eq!(bpdu.flags() & flags::TOPOLOGY_CHANGE, 0);
+        assert_eq!(bpdu.root_id(), BridgeId {
+            priority: 0x8000,
+            addr: MacAddress([0x00, 0x1c, 0x0e, 0x87, 0x78, 0x00]),
+        });
+        assert_eq!(bpdu.root_path_cost(), 4);
+        assert_eq!(bpdu.bridge_id(), BridgeId {
+            priority: 0x8000,
+            addr: MacAddress([0x00, 0x1c, 0x0e, 0x87, 0x85, 0x00]),
+        });
+        assert!(bpdu.root_id() < bpdu.bridge_id());
+        assert_eq!(bpdu.port_id(), 0x8004);
+        assert_eq!(bpdu.message_age(), 256);
+        assert_eq!(bpdu.max_age(), 20 * 256);
+        assert_eq!(bpdu.hello_time(), 2 * 256);
+        assert_eq!(bpdu.forward_delay(), 15 * 256);
+    }
+
+    #[test]
+    fn test_tcn_bpdu() {
+        let bpdu = Bpdu::new_checked(&[0x00, 0x00, 0x00, 0x80][..]).unwrap();
+        assert_eq!(bpdu.bpdu_type(), BpduType::TopologyChange);
+
+        let bpdu = Bpdu::new_checked(&[0x00, 0x00, 0x00, 0x00][..]);
+        assert_eq!(bpdu.err(), Some(Error::Truncated));
+        assert_eq!(Bpdu::new_checked(&[0x00; 3][..]).err(), Some(Error::Truncated));
+    }
+
+    #[test]
+    fn test_llc_payload() {
+        assert_eq!(llc_payload(&[0x42, 0x42]), Err(Error::Truncated));
+        assert_eq!(llc_payload(&[0xaa, 0xaa, 0x03, 0x00]), Err(Error::Unrecognized));
+        assert_eq!(llc_payload(&[0x42, 0x42, 0x03, 0x00]), Ok(&[0x00][..]));
+    }
+}
@@ -0,0 +1,641 @@
+// 0                   1                   2                   3
+// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |          Source Port          |       Destination Port        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                        Sequence Number                        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                    Acknowledgment Number                      |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  Data |       |C|E|U|A|P|R|S|F|                               |
+// | Offset| Rsrvd |W|C|R|C|S|S|Y|I|            Window             |
+// |       |      N|R|E|G|K|H|T|N|N|                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |           Checksum            |         Urgent Pointer        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                    Options                    |    Padding    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                             data                              |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+#![allow(unused)]
+use byteorder::{
+    ByteOrder,
+    NetworkEndian,
+};
+use crate::{
+    Result,
+    Error,
+};
+use super::ip::Protocol;
+use super::port::Port;
+use super::ip::ipv4::Address as IPv4Address;
+use crate::checksum;
+use crate::device::ChecksumCapabilities;
+use crate::bytes;
+use core::cmp;
+
+// A sequence number, compared in serial number arithmetic (RFC 1982):
+// `a < b` iff `b` is less than 2^31 ahead of `a`, modulo 2^32.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SeqNumber(pub u32);
+
+impl SeqNumber {
+    pub fn wrapping_add(self, n: u32) -> SeqNumber {
+        SeqNumber(self.0.wrapping_add(n))
+    }
+
+    pub fn wrapping_sub(self, n: u32) -> SeqNumber {
+        SeqNumber(self.0.wrapping_sub(n))
+    }
+
+    // How far `other` is ahead of `self`; negative if it is behind.
+    pub fn distance(&self, other: SeqNumber) -> i32 {
+        other.0.wrapping_sub(self.0) as i32
+    }
+}
+
+// Serial number arithmetic (RFC 1982): the order is not transitive, so
+// this is PartialOrd but not Ord. Numbers exactly 2^31 apart are not
+// comparable.
+impl cmp::PartialOrd for SeqNumber {
+    fn partial_cmp(&self, other: &SeqNumber) -> Option<cmp::Ordering> {
+        match self.0.wrapping_sub(other.0) {
+            0x8000_0000 => None,
+            diff => Some((diff as i32).cmp(&0)),
+        }
+    }
+}
+
+mod field {
+    use crate::Field;
+
+    pub const SRC_PORT: Field = 0..2;
+    pub const DST_PORT: Field = 2..4;
+    pub const SEQ_NUM:  Field = 4..8;
+    pub const ACK_NUM:  Field = 8..12;
+    pub const FLAGS:    Field = 12..14;
+    pub const WIN_SIZE: Field = 14..16;
+    pub const CHECKSUM: Field = 16..18;
+    pub const URGENT:   Field = 18..20;
+
+    pub const FLG_FIN: u16 = 0x001;
+    pub const FLG_SYN: u16 = 0x002;
+    pub const FLG_RST: u16 = 0x004;
+    pub const FLG_PSH: u16 = 0x008;
+    pub const FLG_ACK: u16 = 0x010;
+    pub const FLG_URG: u16 = 0x020;
+    pub const FLG_ECE: u16 = 0x040;
+    pub const FLG_CWR: u16 = 0x080;
+    pub const FLG_NS:  u16 = 0x100;
+}
+
+pub const HEADER_LEN: usize = field::URGENT.end;
+
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T
+}
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    pub fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < HEADER_LEN {
+            Err(Error::Truncated)
+        } else if (self.header_len() as usize) < HEADER_LEN {
+            Err(Error::Malformed)
+        } else if len < self.header_len() as usize {
+            Err(Error::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    // A segment cannot both open and close a connection.
+    pub fn check_flags(&self) -> Result<()> {
+        if self.syn() && self.fin() {
+            Err(Error::Malformed)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    pub fn src_port(&self) -> Port {
+        bytes::checked(self.src_port_checked())
+    }
+
+    pub fn src_port_checked(&self) -> Result<Port> {
+        let data = self.buffer.as_ref();
+        bytes::get_u16(data, field::SRC_PORT).map(Port)
+    }
+
+    pub fn dst_port(&self) -> Port {
+        bytes::checked(self.dst_port_checked())
+    }
+
+    pub fn dst_port_checked(&self) -> Result<Port> {
+        let data = self.buffer.as_ref();
+        bytes::get_u16(data, field::DST_PORT).map(Port)
+    }
+
+    pub fn seq_number_typed(&self) -> SeqNumber {
+        SeqNumber(self.seq_number())
+    }
+
+    pub fn seq_number(&self) -> u32 {
+        bytes::checked(self.seq_number_checked())
+    }
+
+    pub fn seq_number_checked(&self) -> Result<u32> {
+        let data = self.buffer.as_ref();
+        bytes::get_u32(data, field::SEQ_NUM)
+    }
+
+    pub fn ack_number(&self) -> u32 {
+        bytes::checked(self.ack_number_checked())
+    }
+
+    pub fn ack_number_checked(&self) -> Result<u32> {
+        let data = self.buffer.as_ref();
+        bytes::get_u32(data, field::ACK_NUM)
+    }
+
+    // length of the header in 32-bit words
+    pub fn data_offset(&self) -> u8 {
+        bytes::checked(self.data_offset_checked())
+    }
+
+    pub fn data_offset_checked(&self) -> Result<u8> {
+        let data = self.buffer.as_ref();
+        bytes::get_u8(data, field::FLAGS.start).map(|value| value >> 4)
+    }
+
+    // length of the header in octets
+    pub fn header_len(&self) -> u8 {
+        self.data_offset() * 4
+    }
+
+    fn flags(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_u16(data, field::FLAGS)) & 0x1ff
+    }
+
+    pub fn fin(&self) -> bool {
+        self.flags() & field::FLG_FIN != 0
+    }
+
+    pub fn syn(&self) -> bool {
+        self.flags() & field::FLG_SYN != 0
+    }
+
+    pub fn rst(&self) -> bool {
+        self.flags() & field::FLG_RST != 0
+    }
+
+    pub fn psh(&self) -> bool {
+        self.flags() & field::FLG_PSH != 0
+    }
+
+    pub fn ack(&self) -> bool {
+        self.flags() & field::FLG_ACK != 0
+    }
+
+    pub fn urg(&self) -> bool {
+        self.flags() & field::FLG_URG != 0
+    }
+
+    pub fn ece(&self) -> bool {
+        self.flags() & field::FLG_ECE != 0
+    }
+
+    pub fn cwr(&self) -> bool {
+        self.flags() & field::FLG_CWR != 0
+    }
+
+    pub fn ns(&self) -> bool {
+        self.flags() & field::FLG_NS != 0
+    }
+
+    pub fn window_len(&self) -> u16 {
+        bytes::checked(self.window_len_checked())
+    }
+
+    pub fn window_len_checked(&self) -> Result<u16> {
+        let data = self.buffer.as_ref();
+        bytes::get_u16(data, field::WIN_SIZE)
+    }
+
+    pub fn checksum(&self) -> u16 {
+        bytes::checked(self.checksum_checked())
+    }
+
+    pub fn checksum_checked(&self) -> Result<u16> {
+        let data = self.buffer.as_ref();
+        bytes::get_u16(data, field::CHECKSUM)
+    }
+
+    pub fn urgent_pointer(&self) -> u16 {
+        bytes::checked(self.urgent_pointer_checked())
+    }
+
+    pub fn urgent_pointer_checked(&self) -> Result<u16> {
+        let data = self.buffer.as_ref();
+        bytes::get_u16(data, field::URGENT)
+    }
+
+    pub fn options(&self) -> &[u8] {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_slice(data, field::URGENT.end..self.header_len() as usize))
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_slice(data, self.header_len() as usize..))
+    }
+
+    // TCP has no length field, the segment spans the whole buffer.
+    pub fn verify_checksum(&self, src: &IPv4Address, dst: &IPv4Address) -> bool {
+        let data = self.buffer.as_ref();
+        let header_len = (self.header_len() as usize).min(data.len());
+        let (header, payload) = data.split_at(header_len);
+        checksum::verify_transport_v4(header, payload, src, dst, Protocol::TCP)
+    }
+
+    pub fn verify_checksum_with(
+        &self,
+        src: &IPv4Address,
+        dst: &IPv4Address,
+        caps: &ChecksumCapabilities
+    ) -> bool {
+        !caps.tcp.rx() || self.verify_checksum(src, dst)
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    pub fn set_src_port(&mut self, port: Port) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::SRC_PORT], port.0);
+    }
+
+    pub fn set_dst_port(&mut self, port: Port) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::DST_PORT], port.0);
+    }
+
+    pub fn set_seq_number(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[field::SEQ_NUM], value);
+    }
+
+    pub fn set_ack_number(&mut self, value: u32) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u32(&mut data[field::ACK_NUM], value);
+    }
+
+    pub fn set_data_offset(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::FLAGS.start] = (data[field::FLAGS.start] & 0x0f) | (value << 4);
+    }
+
+    pub fn set_header_len(&mut self, value: u8) {
+        self.set_data_offset(value / 4)
+    }
+
+    fn set_flag(&mut self, flag: u16, value: bool) {
+        let data = self.buffer.as_mut();
+        let raw = NetworkEndian::read_u16(&data[field::FLAGS]);
+        let raw = if value { raw | flag } else { raw & !flag };
+        NetworkEndian::write_u16(&mut data[field::FLAGS], raw);
+    }
+
+    pub fn clear_flags(&mut self) {
+        let data = self.buffer.as_mut();
+        let raw = NetworkEndian::read_u16(&data[field::FLAGS]);
+        NetworkEndian::write_u16(&mut data[field::FLAGS], raw & !0x1ff);
+    }
+
+    pub fn set_fin(&mut self, value: bool) {
+        self.set_flag(field::FLG_FIN, value)
+    }
+
+    pub fn set_syn(&mut self, value: bool) {
+        self.set_flag(field::FLG_SYN, value)
+    }
+
+    pub fn set_rst(&mut self, value: bool) {
+        self.set_flag(field::FLG_RST, value)
+    }
+
+    pub fn set_psh(&mut self, value: bool) {
+        self.set_flag(field::FLG_PSH, value)
+    }
+
+    pub fn set_ack(&mut self, value: bool) {
+        self.set_flag(field::FLG_ACK, value)
+    }
+
+    pub fn set_urg(&mut self, value: bool) {
+        self.set_flag(field::FLG_URG, value)
+    }
+
+    pub fn set_ece(&mut self, value: bool) {
+        self.set_flag(field::FLG_ECE, value)
+    }
+
+    pub fn set_cwr(&mut self, value: bool) {
+        self.set_flag(field::FLG_CWR, value)
+    }
+
+    pub fn set_ns(&mut self, value: bool) {
+        self.set_flag(field::FLG_NS, value)
+    }
+
+    pub fn set_window_len(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::WIN_SIZE], value);
+    }
+
+    pub fn set_checksum(&mut self, checksum: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::CHECKSUM], checksum);
+    }
+
+    pub fn set_urgent_pointer(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::URGENT], value);
+    }
+
+    pub fn fill_checksum(&mut self, src: &IPv4Address, dst: &IPv4Address) {
+        self.set_checksum(0);
+        let checksum = {
+            let data = self.buffer.as_ref();
+            !checksum::combine(&[
+                checksum::pseudo_header(src, dst, Protocol::TCP, data.len() as u32),
+                checksum::data(data),
+            ])
+        };
+        self.set_checksum(checksum)
+    }
+
+    pub fn fill_checksum_with(
+        &mut self,
+        src: &IPv4Address,
+        dst: &IPv4Address,
+        caps: &ChecksumCapabilities
+    ) {
+        if caps.tcp.tx() {
+            self.fill_checksum(src, dst);
+        }
+    }
+
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let header_len = self.header_len() as usize;
+        let data = self.buffer.as_mut();
+        &mut data[header_len..]
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+pub mod option {
+    pub const END_OF_LIST:    u8 = 0;
+    pub const NO_OPERATION:   u8 = 1;
+    pub const MAX_SEG_SIZE:   u8 = 2;
+    pub const WINDOW_SCALE:   u8 = 3;
+    pub const SACK_PERMITTED: u8 = 4;
+    pub const TIMESTAMPS:     u8 = 8;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcpOption<'a> {
+    EndOfList,
+    NoOperation,
+    MaxSegmentSize(u16),
+    WindowScale(u8),
+    SackPermitted,
+    Timestamps { tsval: u32, tsecr: u32 },
+    Unknown(u8, &'a [u8]),
+}
+
+pub struct OptionsIter<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> OptionsIter<'a> {
+    pub fn new(data: &'a [u8]) -> OptionsIter<'a> {
+        OptionsIter { data }
+    }
+}
+
+impl<'a> Iterator for OptionsIter<'a> {
+    type Item = Result<TcpOption<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let &kind = self.data.first()?;
+        match kind {
+            option::END_OF_LIST => {
+                self.data = &[];
+                Some(Ok(TcpOption::EndOfList))
+            }
+            option::NO_OPERATION => {
+                self.data = &self.data[1..];
+                Some(Ok(TcpOption::NoOperation))
+            }
+            _ => {
+                let len = match self.data.get(1) {
+                    Some(&len) => len as usize,
+                    None => 0,
+                };
+                if len < 2 || len > self.data.len() {
+                    self.data = &[];
+                    return Some(Err(Error::Malformed));
+                }
+                let value = &self.data[2..len];
+                self.data = &self.data[len..];
+                let option = match (kind, value.len()) {
+                    (option::MAX_SEG_SIZE, 2) =>
+                        TcpOption::MaxSegmentSize(NetworkEndian::read_u16(value)),
+                    (option::WINDOW_SCALE, 1) =>
+                        TcpOption::WindowScale(value[0]),
+                    (option::SACK_PERMITTED, 0) =>
+                        TcpOption::SackPermitted,
+                    (option::TIMESTAMPS, 8) => TcpOption::Timestamps {
+                        tsval: NetworkEndian::read_u32(&value[0..4]),
+                        tsecr: NetworkEndian::read_u32(&value[4..8]),
+                    },
+                    (option::MAX_SEG_SIZE, _) |
+                    (option::WINDOW_SCALE, _) |
+                    (option::SACK_PERMITTED, _) |
+                    (option::TIMESTAMPS, _) => {
+                        self.data = &[];
+                        return Some(Err(Error::Malformed));
+                    }
+                    _ => TcpOption::Unknown(kind, value),
+                };
+                Some(Ok(option))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Error;
+    use crate::protocol::ip::ipv4::Address as IPv4Address;
+    use super::{
+        Packet,
+        SeqNumber,
+        OptionsIter,
+        TcpOption,
+        Port,
+    };
+
+    static SRC_ADDR: IPv4Address = IPv4Address([192, 168, 1, 10]);
+    static DST_ADDR: IPv4Address = IPv4Address([192, 168, 1, 1]);
+
+    // SYN 54321 -> 80, seq 0x12345678, win 64240
+    static SYN_BYTES: [u8; 20] = [
+        0xd4, 0x31, 0x00, 0x50, 0x12, 0x34, 0x56, 0x78,
+        0x00, 0x00, 0x00, 0x00, 0x50, 0x02, 0xfa, 0xf0,
+        0xf4, 0x67, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn test_construct() {
+        let mut bytes = vec![0; 20];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_src_port(Port(54321));
+        packet.set_dst_port(Port(80));
+        packet.set_seq_number(0x12345678);
+        packet.set_ack_number(0);
+        packet.set_header_len(20);
+        packet.clear_flags();
+        packet.set_syn(true);
+        packet.set_window_len(64240);
+        packet.set_urgent_pointer(0);
+        packet.fill_checksum(&SRC_ADDR, &DST_ADDR);
+        assert_eq!(packet.as_ref(), &SYN_BYTES[..]);
+    }
+
+    #[test]
+    fn test_deconstruct() {
+        let packet = Packet::new_checked(&SYN_BYTES[..]).unwrap();
+        assert_eq!(packet.src_port(), Port(54321));
+        assert_eq!(packet.dst_port(), Port(80));
+        assert_eq!(packet.seq_number(), 0x12345678);
+        assert_eq!(packet.ack_number(), 0);
+        assert_eq!(packet.data_offset(), 5);
+        assert_eq!(packet.header_len(), 20);
+        assert!(packet.syn());
+        assert!(!packet.ack() && !packet.fin() && !packet.rst());
+        assert!(!packet.psh() && !packet.urg());
+        assert_eq!(packet.window_len(), 64240);
+        assert_eq!(packet.checksum(), 0xf467);
+        assert_eq!(packet.urgent_pointer(), 0);
+        assert!(packet.payload().is_empty());
+        assert!(packet.verify_checksum(&SRC_ADDR, &DST_ADDR));
+        assert!(!packet.verify_checksum(&DST_ADDR, &DST_ADDR));
+        assert_eq!(packet.check_flags(), Ok(()));
+    }
+
+    #[test]
+    fn test_check() {
+        assert_eq!(Packet::new_checked(&SYN_BYTES[..19]).err(), Some(Error::Truncated));
+
+        let mut bytes = SYN_BYTES;
+        bytes[12] = 0x40;
+        assert_eq!(Packet::new_checked(&bytes[..]).err(), Some(Error::Malformed));
+        bytes[12] = 0x60;
+        assert_eq!(Packet::new_checked(&bytes[..]).err(), Some(Error::Truncated));
+
+        let mut bytes = SYN_BYTES;
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        packet.set_fin(true);
+        assert_eq!(packet.check_flags(), Err(Error::Malformed));
+    }
+
+    #[test]
+    fn test_seq_number() {
+        let a = SeqNumber(0xFFFF_FFF0);
+        let b = a.wrapping_add(0x20);
+        assert_eq!(b, SeqNumber(0x0000_0010));
+        assert!(a < b);
+        assert!(b > a);
+        assert_eq!(a.distance(b), 0x20);
+        assert_eq!(b.distance(a), -0x20);
+        assert_eq!(b.wrapping_sub(0x20), a);
+
+        assert!(SeqNumber(0xFFFF_FFFF) < SeqNumber(0));
+        assert!(SeqNumber(0) > SeqNumber(0xFFFF_FFFF));
+        assert!(SeqNumber(0) <= SeqNumber(0));
+        assert!(SeqNumber(0) < SeqNumber(0x7FFF_FFFF));
+        assert!(SeqNumber(0) > SeqNumber(0x8000_0001));
+
+        let (a, b) = (SeqNumber(0), SeqNumber(0x8000_0000));
+        assert_eq!(a.partial_cmp(&b), None);
+        assert_eq!(b.partial_cmp(&a), None);
+
+        let packet = Packet::new_checked(&SYN_BYTES[..]).unwrap();
+        assert_eq!(packet.seq_number_typed(), SeqNumber(0x12345678));
+    }
+
+    #[test]
+    fn test_options() {
+        // SYN with MSS 1460, SACK permitted, NOP, window scale 7
+        let mut bytes = [0; 32];
+        bytes[..20].copy_from_slice(&SYN_BYTES);
+        bytes[12] = 0x80;
+        bytes[20..].copy_from_slice(&[
+            0x02, 0x04, 0x05, 0xb4, 0x04, 0x02, 0x01, 0x03,
+            0x03, 0x07, 0x00, 0x00,
+        ]);
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert_eq!(packet.options().len(), 12);
+
+        let mut options = OptionsIter::new(packet.options());
+        assert_eq!(options.next(), Some(Ok(TcpOption::MaxSegmentSize(1460))));
+        assert_eq!(options.next(), Some(Ok(TcpOption::SackPermitted)));
+        assert_eq!(options.next(), Some(Ok(TcpOption::NoOperation)));
+        assert_eq!(options.next(), Some(Ok(TcpOption::WindowScale(7))));
+        assert_eq!(options.next(), Some(Ok(TcpOption::EndOfList)));
+        assert_eq!(options.next(), None);
+
+        let mut options = OptionsIter::new(&[
+            0x08, 0x0a, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02,
+        ]);
+        assert_eq!(options.next(), Some(Ok(TcpOption::Timestamps { tsval: 1, tsecr: 2 })));
+        assert_eq!(options.next(), None);
+    }
+
+    #[test]
+    fn test_options_malformed() {
+        let mut options = OptionsIter::new(&[0x02, 0x03, 0x05, 0xb4]);
+        assert_eq!(options.next(), Some(Err(Error::Malformed)));
+        assert_eq!(options.next(), None);
+
+        let mut options = OptionsIter::new(&[0x01, 0x03, 0x04, 0x07]);
+        assert_eq!(options.next(), Some(Ok(TcpOption::NoOperation)));
+        assert_eq!(options.next(), Some(Err(Error::Malformed)));
+        assert_eq!(options.next(), None);
+
+        let mut options = OptionsIter::new(&[0x03]);
+        assert_eq!(options.next(), Some(Err(Error::Malformed)));
+    }
+}
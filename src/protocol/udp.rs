@@ -0,0 +1,325 @@
+// 0                   1                   2                   3
+// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |          Source Port          |       Destination Port        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |            Length             |           Checksum            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     Data ...
+// +-+-+-+-+-
+
+#![allow(unused)]
+use byteorder::{
+    ByteOrder,
+    NetworkEndian,
+};
+use crate::{
+    Result,
+    Error,
+};
+use super::ip::Protocol;
+use super::port::Port;
+use super::ip::ipv4::Address as IPv4Address;
+use crate::checksum;
+use crate::device::ChecksumCapabilities;
+use crate::bytes;
+
+mod field {
+    use crate::Field;
+
+    pub const SRC_PORT: Field = 0..2;
+    pub const DST_PORT: Field = 2..4;
+    pub const LENGTH:   Field = 4..6;
+    pub const CHECKSUM: Field = 6..8;
+}
+
+pub const HEADER_LEN: usize = field::CHECKSUM.end;
+
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T
+}
+
+#[allow(clippy::len_without_is_empty)]
+impl<T: AsRef<[u8]>> Packet<T> {
+    pub fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    pub fn check_len(&self) -> Result<()> {
+        let len = self.buffer.as_ref().len();
+        if len < HEADER_LEN {
+            Err(Error::Truncated)
+        } else if (self.len() as usize) < HEADER_LEN {
+            Err(Error::Malformed)
+        } else if len < self.len() as usize {
+            Err(Error::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    pub fn header_len() -> usize {
+        HEADER_LEN
+    }
+
+    pub fn src_port(&self) -> Port {
+        bytes::checked(self.src_port_checked())
+    }
+
+    pub fn src_port_checked(&self) -> Result<Port> {
+        let data = self.buffer.as_ref();
+        bytes::get_u16(data, field::SRC_PORT).map(Port)
+    }
+
+    pub fn dst_port(&self) -> Port {
+        bytes::checked(self.dst_port_checked())
+    }
+
+    pub fn dst_port_checked(&self) -> Result<Port> {
+        let data = self.buffer.as_ref();
+        bytes::get_u16(data, field::DST_PORT).map(Port)
+    }
+
+    // length in octets of the header and the data
+    pub fn len(&self) -> u16 {
+        bytes::checked(self.len_checked())
+    }
+
+    pub fn len_checked(&self) -> Result<u16> {
+        let data = self.buffer.as_ref();
+        bytes::get_u16(data, field::LENGTH)
+    }
+
+    pub fn checksum(&self) -> u16 {
+        bytes::checked(self.checksum_checked())
+    }
+
+    pub fn checksum_checked(&self) -> Result<u16> {
+        let data = self.buffer.as_ref();
+        bytes::get_u16(data, field::CHECKSUM)
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        let range = HEADER_LEN..self.len() as usize;
+        let data = self.buffer.as_ref();
+        bytes::checked(bytes::get_slice(data, range))
+    }
+
+    // A zero checksum means the sender did not compute one.
+    pub fn verify_checksum(&self, src: &IPv4Address, dst: &IPv4Address) -> bool {
+        let data = self.buffer.as_ref();
+        let (header, payload) = data[..self.len() as usize].split_at(HEADER_LEN);
+        checksum::verify_transport_v4(header, payload, src, dst, Protocol::UDP)
+    }
+
+    pub fn verify_checksum_with(
+        &self,
+        src: &IPv4Address,
+        dst: &IPv4Address,
+        caps: &ChecksumCapabilities
+    ) -> bool {
+        !caps.udp.rx() || self.verify_checksum(src, dst)
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    pub fn set_src_port(&mut self, port: Port) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::SRC_PORT], port.0);
+    }
+
+    pub fn set_dst_port(&mut self, port: Port) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::DST_PORT], port.0);
+    }
+
+    pub fn set_len(&mut self, len: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::LENGTH], len);
+    }
+
+    pub fn set_checksum(&mut self, checksum: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::CHECKSUM], checksum);
+    }
+
+    pub fn fill_checksum(&mut self, src: &IPv4Address, dst: &IPv4Address) {
+        self.set_checksum(0);
+        let checksum = {
+            let data = self.buffer.as_ref();
+            !checksum::combine(&[
+                checksum::pseudo_header(src, dst, Protocol::UDP, self.len() as u32),
+                checksum::data(&data[..self.len() as usize]),
+            ])
+        };
+        // a computed checksum of zero is transmitted as all ones
+        self.set_checksum(if checksum == 0 { !0 } else { checksum })
+    }
+
+    pub fn fill_checksum_with(
+        &mut self,
+        src: &IPv4Address,
+        dst: &IPv4Address,
+        caps: &ChecksumCapabilities
+    ) {
+        if caps.udp.tx() {
+            self.fill_checksum(src, dst);
+        }
+    }
+
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let range = HEADER_LEN..self.len() as usize;
+        let data = self.buffer.as_mut();
+        &mut data[range]
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Error;
+    use crate::protocol::ip::ipv4::Address as IPv4Address;
+    use super::{
+        Packet,
+        Port,
+    };
+
+    static SRC_ADDR: IPv4Address = IPv4Address([192, 168, 1, 10]);
+    static DST_ADDR: IPv4Address = IPv4Address([192, 168, 1, 1]);
+
+    // 54321 -> 53, "hello"
+    static PACKET_BYTES: [u8; 13] = [
+        0xd4, 0x31, 0x00, 0x35, 0x00, 0x0d, 0x64, 0x3f,
+        0x68, 0x65, 0x6c, 0x6c, 0x6f,
+    ];
+
+    // 54321 -> 53, a payload for which the computed checksum is zero,
+    // so it was sent as 0xffff
+    static ALL_ONES_BYTES: [u8; 10] = [
+        0xd4, 0x31, 0x00, 0x35, 0x00, 0x0a, 0xff, 0xff,
+        0xa8, 0x17,
+    ];
+
+    #[test]
+    fn test_verify_checksum() {
+        let packet = Packet::new_checked(&PACKET_BYTES[..]).unwrap();
+        assert!(packet.verify_checksum(&SRC_ADDR, &DST_ADDR));
+        assert!(!packet.verify_checksum(&SRC_ADDR, &SRC_ADDR));
+
+        let mut bytes = vec![0; 10];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_src_port(Port(54321));
+        packet.set_dst_port(Port(53));
+        packet.set_len(10);
+        packet.payload_mut().copy_from_slice(&[0xa8, 0x17]);
+        packet.fill_checksum(&SRC_ADDR, &DST_ADDR);
+        assert_eq!(packet.as_ref(), &ALL_ONES_BYTES[..]);
+        assert!(packet.verify_checksum(&SRC_ADDR, &DST_ADDR));
+
+        // no checksum at all
+        let mut bytes = ALL_ONES_BYTES;
+        bytes[6..8].copy_from_slice(&[0, 0]);
+        bytes[9] ^= 0x01;
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert!(packet.verify_checksum(&SRC_ADDR, &DST_ADDR));
+
+        let mut bytes = ALL_ONES_BYTES;
+        bytes[9] ^= 0x01;
+        let packet = Packet::new_checked(&bytes[..]).unwrap();
+        assert!(!packet.verify_checksum(&SRC_ADDR, &DST_ADDR));
+    }
+
+    #[test]
+    fn test_checksum_offload() {
+        use crate::device::{
+            Checksum,
+            ChecksumCapabilities,
+        };
+
+        let mut caps = ChecksumCapabilities {
+            udp: Checksum::Rx,
+            ..ChecksumCapabilities::default()
+        };
+        let mut bytes = PACKET_BYTES;
+        bytes[6..8].copy_from_slice(&[0x12, 0x34]);
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        packet.fill_checksum_with(&SRC_ADDR, &DST_ADDR, &caps);
+        assert_eq!(packet.checksum(), 0x1234);
+        assert!(!packet.verify_checksum_with(&SRC_ADDR, &DST_ADDR, &caps));
+
+        caps.udp = Checksum::Tx;
+        assert!(packet.verify_checksum_with(&SRC_ADDR, &DST_ADDR, &caps));
+        packet.fill_checksum_with(&SRC_ADDR, &DST_ADDR, &caps);
+        assert_eq!(packet.as_ref(), &PACKET_BYTES[..]);
+
+        let caps = ChecksumCapabilities::ignored();
+        packet.set_checksum(0x1234);
+        packet.fill_checksum_with(&SRC_ADDR, &DST_ADDR, &caps);
+        assert_eq!(packet.checksum(), 0x1234);
+        assert!(packet.verify_checksum_with(&SRC_ADDR, &DST_ADDR, &caps));
+    }
+
+    #[test]
+    fn test_construct() {
+        let mut bytes = vec![0; 13];
+        let mut packet = Packet::new_unchecked(&mut bytes);
+        packet.set_src_port(Port(54321));
+        packet.set_dst_port(Port(53));
+        packet.set_len(13);
+        packet.payload_mut().copy_from_slice(b"hello");
+        packet.fill_checksum(&SRC_ADDR, &DST_ADDR);
+        assert_eq!(packet.as_ref(), &PACKET_BYTES[..]);
+    }
+
+    #[test]
+    fn test_deconstruct() {
+        let packet = Packet::new_checked(&PACKET_BYTES[..]).unwrap();
+        assert_eq!(packet.src_port(), Port(54321));
+        assert_eq!(packet.dst_port(), Port(53));
+        assert_eq!(packet.len(), 13);
+        assert_eq!(packet.checksum(), 0x643f);
+        assert_eq!(packet.payload(), b"hello");
+        assert!(packet.verify_checksum(&SRC_ADDR, &DST_ADDR));
+        assert!(!packet.verify_checksum(&DST_ADDR, &DST_ADDR));
+    }
+
+    #[test]
+    fn test_truncated() {
+        assert!(Packet::new_checked(&PACKET_BYTES[..7]).is_err());
+        assert!(Packet::new_checked(&PACKET_BYTES[..12]).is_err());
+    }
+
+    #[test]
+    fn test_checked_accessors() {
+        let packet = Packet::new_unchecked(&PACKET_BYTES[..2]);
+        assert_eq!(packet.src_port_checked(), Ok(Port(54321)));
+        assert_eq!(packet.dst_port_checked(), Err(Error::Truncated));
+        assert_eq!(packet.len_checked(), Err(Error::Truncated));
+        assert_eq!(packet.checksum_checked(), Err(Error::Truncated));
+
+        let packet = Packet::new_unchecked(&PACKET_BYTES[..]);
+        assert_eq!(packet.checksum_checked(), Ok(packet.checksum()));
+    }
+
+    #[test]
+    #[should_panic(expected = "check_len first")]
+    fn test_plain_accessor_truncated() {
+        let packet = Packet::new_unchecked(&PACKET_BYTES[..2]);
+        packet.dst_port();
+    }
+}
@@ -1,14 +1,26 @@
-mod ethernet;
-mod icmp;
-mod ip;
+pub mod ethernet;
+pub mod icmp;
+pub mod ip;
+pub mod udp;
 
+#[cfg(feature = "alloc")]
+pub use self::udp::build_echo_response;
+
+use crate::protocol::ip::Protocol;
+
+// A layer that carries the protocols P one layer up: Ethernet takes
+// Network protocols and IPv4 takes Transport ones.
 pub trait NetworkInterface<P>
 where
-    P: Network + AsRef<[u8]>,
+    P: AsRef<[u8]>,
 {
     fn set_upper_protocol(&mut self, protocol: P);
 }
 
 pub trait Network {}
-pub trait Transport {}
+
+pub trait Transport {
+    fn protocol_number(&self) -> Protocol;
+}
+
 pub trait Application {}
@@ -5,7 +5,21 @@ use core::ops::{
     DerefMut
 };
 
-use crate::protocol::ethernet::Frame;
+use crate::{
+    Result,
+    Error,
+};
+use crate::protocol::ethernet::{
+    Address,
+    EtherType,
+    Frame,
+};
+use crate::protocol::ip::ipv4::{
+    Cidr,
+    Packet as IPv4Packet,
+};
+use crate::protocol::ip::ipv6::Packet as IPv6Packet;
+use crate::device::Device;
 
 use super::{
     Network, 
@@ -58,6 +72,74 @@ impl<T> AsRef<[u8]> for Ethernet<T> where T: AsRef<[u8]> {
     }
 }
 
+impl<T> Ethernet<T> where T: AsRef<[u8]> {
+    // The bytes to put on the wire: the buffer up to the frame's own
+    // length, without whatever follows in an oversized buffer.
+    pub fn wire_bytes(&self) -> &[u8] {
+        &self.frame.as_ref()[..self.frame.total_len()]
+    }
+
+    pub fn send(&self, device: &mut dyn Device) -> Result<()> {
+        device.transmit(self.wire_bytes())
+    }
+
+    // Opt-in sanity check before sending: the destination MAC must match
+    // the kind of the destination IP address, e.g. a unicast datagram must
+    // not go to the broadcast MAC, and a multicast group must go to its
+    // mapped MAC. Only the limited broadcast 255.255.255.255 counts as
+    // broadcast; see check_addressing_in for directed broadcasts.
+    // Returns Error::Illegal on a mismatch and Error::Truncated if the IP
+    // header is incomplete; other EtherTypes are not checked.
+    pub fn check_addressing(&self) -> Result<()> {
+        self.check_addressing_for(None)
+    }
+
+    // Like check_addressing, but the directed broadcast address of
+    // `subnet`, the sending interface's IPv4 network, goes to the
+    // broadcast MAC as well.
+    pub fn check_addressing_in(&self, subnet: &Cidr) -> Result<()> {
+        self.check_addressing_for(Some(subnet))
+    }
+
+    fn check_addressing_for(&self, subnet: Option<&Cidr>) -> Result<()> {
+        let dst_mac = self.frame.dst_addr();
+        let consistent = match self.frame.ether_type() {
+            EtherType::IPv4 => {
+                let dst_ip = IPv4Packet::new_unchecked(self.frame.payload()).dst_addr_checked()?;
+                // /31 and /32 networks have no broadcast address
+                let directed = subnet.is_some_and(|subnet| {
+                    subnet.prefix_len() < 31 && dst_ip == subnet.broadcast()
+                });
+                if dst_ip.is_broadcast() || directed {
+                    dst_mac.is_broadcast()
+                } else if dst_ip.is_multicast() {
+                    dst_mac == Address::from_multicast_ipv4(&dst_ip)
+                } else {
+                    dst_mac.is_unicast()
+                }
+            }
+            EtherType::IPv6 => {
+                let payload = self.frame.payload();
+                if payload.len() < IPv6Packet::<&[u8]>::header_len() {
+                    return Err(Error::Truncated);
+                }
+                let dst_ip = IPv6Packet::new_unchecked(payload).dst_addr();
+                if dst_ip.is_multicast() {
+                    dst_mac == Address::from_multicast_ipv6(&dst_ip)
+                } else {
+                    dst_mac.is_unicast()
+                }
+            }
+            _ => true,
+        };
+        if consistent {
+            Ok(())
+        } else {
+            Err(Error::Illegal)
+        }
+    }
+}
+
 impl<T, P> NetworkInterface<P> for Ethernet<T>
 where
     T: AsRef<[u8]> + AsMut<[u8]>,
@@ -79,8 +161,17 @@ pub mod test {
         send_raw_socket,
         DST_MAC,
         src_mac,
+        MockDevice,
+        ethernet_frame,
+        ipv4_datagram,
+    };
+    use crate::protocol::ip::ipv4::{
+        Address as IPv4Address,
+        Cidr,
+        Packet as IPv4Packet,
     };
     use crate::socket::ethernet::Ethernet;
+    use crate::Error;
      
     #[test]
     fn test_protocol() {
@@ -91,6 +182,90 @@ pub mod test {
         frame.set_ether_type(EtherType::ECTP);
         frame.payload_mut().copy_from_slice(&[0, 0, 0, 0]);
         let ethernet: Ethernet<_> = frame.into();
-        send_raw_socket(ethernet.as_ref());
+        send_raw_socket(ethernet.wire_bytes());
+    }
+
+    #[test]
+    fn test_send() {
+        let mut bytes = vec![0; 14 + 4];
+        let mut frame = Frame::new_unchecked(&mut bytes);
+        frame.set_dst_addr(Address::BROADCAST);
+        frame.set_src_addr(Address([0x02, 0, 0, 0, 0, 0x01]));
+        frame.set_ether_type(EtherType::ECTP);
+        let ethernet: Ethernet<_> = frame.into();
+
+        let mut device = MockDevice::default();
+        ethernet.send(&mut device).unwrap();
+        ethernet.send(&mut device).unwrap();
+        assert_eq!(device.sent.len(), 2);
+        assert_eq!(device.sent[0], ethernet.as_ref());
+    }
+
+    #[test]
+    fn test_check_addressing() {
+        let subnet = Cidr::new(IPv4Address([10, 0, 0, 1]), 24).unwrap();
+        let unicast_mac = Address([0x02, 0, 0, 0, 0, 0x02]);
+        let unicast_ip = IPv4Address([10, 0, 0, 2]);
+        let group = IPv4Address([239, 1, 1, 1]);
+        let frame = |mac: Address, ip: IPv4Address| {
+            let mut datagram = ipv4_datagram(0, 0);
+            IPv4Packet::new_unchecked(&mut datagram[..]).set_dst_addr(ip);
+            ethernet_frame(mac, EtherType::IPv4, &datagram)
+        };
+        let check = |mac: Address, ip: IPv4Address| {
+            let bytes = frame(mac, ip);
+            let ethernet: Ethernet<_> = Frame::new_unchecked(&bytes[..]).into();
+            (ethernet.check_addressing(), ethernet.check_addressing_in(&subnet))
+        };
+
+        let consistent = [
+            (unicast_mac, unicast_ip),
+            (Address::BROADCAST, IPv4Address::BROADCAST),
+            (Address::from_multicast_ipv4(&group), group),
+        ];
+        for &(mac, ip) in consistent.iter() {
+            assert_eq!(check(mac, ip), (Ok(()), Ok(())));
+        }
+
+        let mismatched = [
+            (Address::BROADCAST, unicast_ip),
+            (Address::BROADCAST, IPv4Address([192, 168, 1, 255])),
+            (unicast_mac, IPv4Address::BROADCAST),
+            (unicast_mac, group),
+            (Address::from_multicast_ipv4(&IPv4Address([239, 1, 1, 2])), group),
+        ];
+        for &(mac, ip) in mismatched.iter() {
+            assert_eq!(check(mac, ip), (Err(Error::Illegal), Err(Error::Illegal)));
+        }
+
+        // only a check that knows the subnet sees a directed broadcast
+        let directed = IPv4Address([10, 0, 0, 255]);
+        assert_eq!(check(Address::BROADCAST, directed), (Err(Error::Illegal), Ok(())));
+        assert_eq!(check(unicast_mac, directed), (Ok(()), Err(Error::Illegal)));
+
+        let bytes = frame(unicast_mac, unicast_ip);
+        let ethernet: Ethernet<_> = Frame::new_unchecked(&bytes[..20]).into();
+        assert_eq!(ethernet.check_addressing(), Err(Error::Truncated));
+        assert_eq!(ethernet.check_addressing_in(&subnet), Err(Error::Truncated));
+    }
+
+    #[test]
+    fn test_wire_bytes() {
+        let mut bytes = vec![0xee; 1514];
+        let mut frame = Frame::new_unchecked(&mut bytes);
+        frame.set_dst_addr(Address([0x02, 0, 0, 0, 0, 0x02]));
+        frame.set_src_addr(Address([0x02, 0, 0, 0, 0, 0x01]));
+        frame.set_ether_type(EtherType::IPv4);
+        let mut packet = IPv4Packet::new_unchecked(frame.payload_mut());
+        packet.set_version(4);
+        packet.set_header_len(20);
+        packet.set_dst_addr(IPv4Address([10, 0, 0, 2]));
+        packet.finalize(8).unwrap();
+        let ethernet: Ethernet<_> = frame.into();
+
+        assert_eq!(ethernet.wire_bytes().len(), 14 + 20 + 8);
+        let mut device = MockDevice::default();
+        ethernet.send(&mut device).unwrap();
+        assert_eq!(device.sent[0].len(), 14 + 20 + 8);
     }
 }
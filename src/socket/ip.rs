@@ -4,7 +4,11 @@ use core::ops::{
     DerefMut,
 };
 
-use super::Network;
+use super::{
+    Network,
+    NetworkInterface,
+    Transport,
+};
 
 pub struct IPv4<T>
 where
@@ -52,6 +56,17 @@ where
 
 }
 
+impl<T, P> NetworkInterface<P> for IPv4<T>
+where
+    T: AsRef<[u8]> + AsMut<[u8]>,
+    P: Transport + AsRef<[u8]>,
+{
+    fn set_upper_protocol(&mut self, protocol: P) {
+        self.set_protocol(protocol.protocol_number());
+        self.payload_mut().copy_from_slice(protocol.as_ref());
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::protocol::ethernet::Address as MacAddress;
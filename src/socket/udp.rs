@@ -0,0 +1,264 @@
+use crate::protocol::udp::Packet as UdpPacket;
+use crate::protocol::ip::Protocol;
+use core::ops::{
+    Deref,
+    DerefMut,
+};
+#[cfg(feature = "alloc")]
+use crate::protocol::ip::ipv4::Packet as IPv4Packet;
+#[cfg(feature = "alloc")]
+use crate::protocol::ethernet::{
+    EtherType,
+    Frame,
+};
+#[cfg(feature = "alloc")]
+use crate::{
+    Result,
+    Error,
+};
+#[cfg(feature = "alloc")]
+use alloc::{
+    vec,
+    vec::Vec,
+};
+#[cfg(feature = "alloc")]
+use super::{
+    NetworkInterface,
+    ethernet::Ethernet,
+    ip::IPv4,
+};
+
+use super::Transport;
+
+pub struct Udp<T>
+where
+    T: AsRef<[u8]>,
+{
+    packet: UdpPacket<T>,
+}
+
+impl<T> Deref for Udp<T> 
+where 
+    T: AsRef<[u8]>
+{
+    type Target = UdpPacket<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.packet
+    }
+}
+
+impl<T> DerefMut for Udp<T>
+where 
+    T: AsRef<[u8]> + AsMut<[u8]>
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.packet
+    }
+}
+
+impl<T> From<UdpPacket<T>> for Udp<T> where T: AsRef<[u8]> {
+    fn from(packet: UdpPacket<T>) -> Self {
+        Self { packet }
+    }
+}
+
+impl<T> AsRef<[u8]> for Udp<T> where T: AsRef<[u8]> {
+    fn as_ref(&self) -> &[u8] {
+        self.packet.as_ref()
+    }
+}
+
+impl<T> Transport for Udp<T>
+where 
+    T: AsRef<[u8]>
+{
+    fn protocol_number(&self) -> Protocol {
+        Protocol::UDP
+    }
+}
+
+// Answer an Ethernet/IPv4/UDP datagram with the same payload sent back
+// from where it came: addresses and ports swapped, the IP and UDP
+// checksums recomputed. The frame check sequence is left to the device.
+// Requests with a bad checksum are rejected with Error::Checksum.
+#[cfg(feature = "alloc")]
+pub fn build_echo_response(request: &Frame<&[u8]>) -> Result<Vec<u8>> {
+    request.check_len()?;
+    if request.ether_type() != EtherType::IPv4 {
+        return Err(Error::Unrecognized);
+    }
+    let ip = IPv4Packet::new_checked(request.payload())?;
+    if ip.protocol() != Protocol::UDP {
+        return Err(Error::Unrecognized);
+    }
+    if ip.more_frags() || ip.frag_offset() != 0 {
+        return Err(Error::Fragmented);
+    }
+    if !ip.verify_checksum() {
+        return Err(Error::Checksum);
+    }
+    let (src_addr, dst_addr) = (ip.src_addr(), ip.dst_addr());
+    let udp = UdpPacket::new_checked(ip.payload())?;
+    if !udp.verify_checksum(&src_addr, &dst_addr) {
+        return Err(Error::Checksum);
+    }
+
+    // build the reply from the inside out, handing each layer to the
+    // one below it
+    let header_len = ip.header_len() as usize;
+    let udp_len = udp.len() as usize;
+    let ip_len = header_len + udp_len;
+    let mut udp_bytes = udp.as_ref()[..udp_len].to_vec();
+    let mut reply = UdpPacket::new_unchecked(&mut udp_bytes[..]);
+    reply.set_src_port(udp.dst_port());
+    reply.set_dst_port(udp.src_port());
+    reply.fill_checksum(&dst_addr, &src_addr);
+    let udp: Udp<_> = reply.into();
+
+    let mut ip_bytes = vec![0; ip_len];
+    ip_bytes[..header_len].copy_from_slice(&ip.as_ref()[..header_len]);
+    let mut reply = IPv4Packet::new_unchecked(&mut ip_bytes[..]);
+    reply.set_total_len(ip_len as u16);
+    reply.set_src_addr(dst_addr);
+    reply.set_dst_addr(src_addr);
+    let mut ip: IPv4<_> = reply.into();
+    ip.set_upper_protocol(udp);
+    ip.fill_checksum();
+
+    let mut frame_bytes = vec![0; Frame::<&[u8]>::frame_len(ip_len)];
+    let mut reply = Frame::new_unchecked(&mut frame_bytes[..]);
+    reply.set_dst_addr(request.src_addr());
+    reply.set_src_addr(request.dst_addr());
+    reply.set_ether_type(EtherType::IPv4);
+    let mut ethernet: Ethernet<_> = reply.into();
+    ethernet.set_upper_protocol(ip);
+
+    let mut frame = Frame::new_unchecked(frame_bytes);
+    frame.pad_to_minimum();
+    Ok(frame.into_inner())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::protocol::ethernet::Address as MacAddress;
+    use crate::protocol::ethernet::EtherType;
+    use crate::protocol::ethernet::Frame;
+    use crate::dev::{
+        send_raw_socket,
+        DST_MAC,
+        src_mac,
+    };
+    use crate::protocol::ip::ipv4::{
+        Packet as IPv4Packet,
+        Address as IPAddress,
+    };
+    use crate::protocol::udp::Packet as UdpPacket;
+    use crate::protocol::port::Port;
+    use crate::socket::NetworkInterface;
+    use crate::socket::ethernet::Ethernet;
+    use crate::socket::ip::IPv4;
+    use super::Udp;
+
+    #[test]
+    fn test_protocol() {
+        let src_addr = IPAddress([10, 10, 10, 2]);
+        let dst_addr = IPAddress([10, 10, 10, 1]);
+
+        let mut frame_bytes = vec![0; 14 + 20 + 12];
+        let mut frame = Frame::new_unchecked(&mut frame_bytes);
+        frame.set_dst_addr(MacAddress(DST_MAC));
+        frame.set_src_addr(MacAddress(src_mac()));
+        frame.set_ether_type(EtherType::IPv4);
+        let mut ethernet: Ethernet<_> = frame.into();
+
+        let mut bytes = vec![0; 12];
+        let mut packet = UdpPacket::new_unchecked(&mut bytes);
+        packet.set_src_port(Port(54321));
+        packet.set_dst_port(Port(7));
+        packet.set_len(12);
+        packet.payload_mut().copy_from_slice(b"ABCD");
+        packet.fill_checksum(&src_addr, &dst_addr);
+        let udp: Udp<_> = packet.into();
+        assert_eq!(udp.payload(), b"ABCD");
+
+        let mut bytes = vec![0; 20 + 12];
+        let mut packet = IPv4Packet::new_unchecked(&mut bytes);
+        packet.set_version(4);
+        packet.set_header_len(20);
+        packet.set_total_len(20 + 12);
+        packet.set_dont_frag(true);
+        packet.set_hop_limit(0x20);
+        packet.set_src_addr(src_addr);
+        packet.set_dst_addr(dst_addr);
+        let mut ip: IPv4<_> = packet.into();
+        ip.set_upper_protocol(udp);
+        ip.fill_checksum();
+        assert_eq!(ip.as_ref()[9], 0x11);
+        assert!(ip.verify_checksum());
+
+        ethernet.set_upper_protocol(ip);
+        let udp = UdpPacket::new_checked(&ethernet.payload()[20..]).unwrap();
+        assert!(udp.verify_checksum(&src_addr, &dst_addr));
+        send_raw_socket(ethernet.as_ref());
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_echo_response() {
+        use crate::Error;
+        use super::build_echo_response;
+
+        // 10.10.10.1:40000 -> 10.10.10.2:7 carrying "ping", padded
+        static REQUEST_FRAME: [u8; 60] = [
+            0x02, 0xfc, 0x00, 0x00, 0x00, 0x02, 0x02, 0xfc,
+            0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x45, 0x00,
+            0x00, 0x20, 0x12, 0x34, 0x40, 0x00, 0x40, 0x11,
+            0x00, 0x00, 0x0a, 0x0a, 0x0a, 0x01, 0x0a, 0x0a,
+            0x0a, 0x02, 0x9c, 0x40, 0x00, 0x07, 0x00, 0x0c,
+            0x00, 0x00, 0x70, 0x69, 0x6e, 0x67, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00,
+        ];
+        let mut request_bytes = REQUEST_FRAME;
+        {
+            let mut frame = Frame::new_unchecked(&mut request_bytes[..]);
+            let mut ip = IPv4Packet::new_unchecked(&mut frame.payload_mut()[..32]);
+            ip.fill_checksum();
+            let (src_addr, dst_addr) = (ip.src_addr(), ip.dst_addr());
+            UdpPacket::new_unchecked(&mut ip.payload_mut()[..12])
+                .fill_checksum(&src_addr, &dst_addr);
+        }
+        let request = Frame::new_checked(&request_bytes[..]).unwrap();
+
+        let response = build_echo_response(&request).unwrap();
+        let frame = Frame::new_checked(&response[..]).unwrap();
+        assert_eq!(response.len(), 60);
+        assert_eq!(frame.dst_addr(), MacAddress([0x02, 0xfc, 0, 0, 0, 0x01]));
+        assert_eq!(frame.src_addr(), MacAddress([0x02, 0xfc, 0, 0, 0, 0x02]));
+        assert_eq!(frame.ether_type(), EtherType::IPv4);
+
+        let ip = IPv4Packet::new_checked(frame.payload()).unwrap();
+        assert!(ip.verify_checksum());
+        assert_eq!(ip.src_addr(), IPAddress([10, 10, 10, 2]));
+        assert_eq!(ip.dst_addr(), IPAddress([10, 10, 10, 1]));
+        assert_eq!(ip.total_len(), 32);
+
+        let udp = UdpPacket::new_checked(ip.payload()).unwrap();
+        assert!(udp.verify_checksum(&ip.src_addr(), &ip.dst_addr()));
+        assert_ne!(udp.checksum(), 0);
+        assert_eq!(udp.src_port(), Port(7));
+        assert_eq!(udp.dst_port(), Port(40000));
+        assert_eq!(udp.payload(), b"ping");
+
+        // a corrupted request isn't answered
+        request_bytes[42] ^= 0xff;
+        let request = Frame::new_checked(&request_bytes[..]).unwrap();
+        assert_eq!(build_echo_response(&request), Err(Error::Checksum));
+
+        let mut bytes = REQUEST_FRAME;
+        bytes[23] = 0x06;
+        let request = Frame::new_checked(&bytes[..]).unwrap();
+        assert_eq!(build_echo_response(&request), Err(Error::Unrecognized));
+    }
+}